@@ -12,50 +12,99 @@ fn main() {
     env_logger::init();
     let args : Vec<String> = env::args().collect();
 
-    if args.is_empty() {
+    if args.len() < 2 {
         println!("No arguments provided");
         exit(-1);
     }
+    if args[1] == "info" {
+        match args.get(2) {
+            Some(file) => print!("{}", state::model_info(file)),
+            None => println!("usage: info <model.json>"),
+        }
+        return;
+    }
+    if args[1] == "export" {
+        let (Some(file), Some(out)) = (args.get(2), args.get(3)) else {
+            println!("usage: export <model.json> <out.csv|out.json> [node|element] [set]");
+            return;
+        };
+        let location = args.get(4).map_or("node", String::as_str);
+        let set = args.get(5).map_or("", String::as_str);
+        match state::export_results(file, out, location, set) {
+            Ok(message) => println!("{}", message),
+            Err(e) => {
+                eprintln!("export failed: {}", e);
+                exit(1);
+            }
+        }
+        return;
+    }
+    if args[1] == "render" {
+        let (Some(file), Some(dir)) = (args.get(2), args.get(3)) else {
+            println!("usage: render <model.json> <out_dir> [steps|mode|orbit] [size=<w>x<h>] [fps=<n>] [seconds=<s>] [field=<name>]");
+            return;
+        };
+        match pollster::block_on(state::record_frames(file, dir, &args[4..])) {
+            Ok(message) => println!("{}", message),
+            Err(e) => {
+                eprintln!("render failed: {}", e);
+                exit(1);
+            }
+        }
+        return;
+    }
+    if args[1] == "surface" {
+        let (Some(file), Some(out)) = (args.get(2), args.get(3)) else {
+            println!("usage: surface <model.json> <out.stl|.obj|.ply|.gltf|.glb> [ascii] [deformed] [animate] [field=<name>]");
+            return;
+        };
+        match state::export_surface(file, out, &args[4..]) {
+            Ok(message) => println!("{}", message),
+            Err(e) => {
+                eprintln!("export failed: {}", e);
+                exit(1);
+            }
+        }
+        return;
+    }
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
-    window.set_title(&*format!("{}", "View"));
+    window.set_title("View");
     let mut state = pollster::block_on( state::State::new(&window,&args[1]));
     event_loop.run(move |event, _, control_flow| {
         match event {
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion{ delta, },
                 .. //Not using device_id
-            } => if state.mouse_pressed {
+            } if state.mouse_pressed => {
                 state.controller.process_mouse(delta.0, delta.1)
             }
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == window.id() => {
-                if !state.input(event) {
-                    match event {
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            input:
-                                KeyboardInput {
-                                    state: ElementState::Pressed,
-                                    virtual_keycode: Some(VirtualKeyCode::Escape),
-                                    ..
-                                },
-                            ..
-                        } => *control_flow = ControlFlow::Exit,
-                        WindowEvent::Resized(physical_size) => {
-                            state.resize(*physical_size);
-                        }
-                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                            state.resize(**new_inner_size);
-                        }
-                        _ => {}
+            } if window_id == window.id() && !state.input(event) => {
+                match event {
+                    WindowEvent::CloseRequested
+                    | WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Escape),
+                                ..
+                            },
+                        ..
+                    } => *control_flow = ControlFlow::Exit,
+                    WindowEvent::Resized(physical_size) => {
+                        state.resize(*physical_size);
+                    }
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        state.resize(**new_inner_size);
                     }
+                    _ => {}
                 }
             }
             Event::RedrawRequested(_) => {
-                state.update();
+                state.update(&window);
                 match state.render() {
                     Ok(_) => {}
                     Err(wgpu::SurfaceError::Lost) => state.resize(state.init.size),
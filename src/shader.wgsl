@@ -1,24 +1,174 @@
+// viewport.xy the target size in pixels
 struct CameraUniform {
     view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+};
+
+// planes packed first, params.x active count, params.y tolerance
+struct ClipUniform {
+    planes: array<vec4<f32>, 4>,
+    params: vec4<f32>,
 };
 
 @binding(0) @group(0) var<uniform> camera: CameraUniform;
+@binding(1) @group(0) var<uniform> clip: ClipUniform;
 
 struct Output {
     @builtin(position) Position :vec4<f32>,
     @location(0) vColor : vec4<f32>,
+    @location(1) vWorld : vec3<f32>,
 };
 
 
 @vertex
 fn vs_main(@location(0) pos: vec4<f32>, @location(1) color: vec4<f32>) -> Output {
     var output: Output;
-    output.Position = camera.view_proj * pos; 
+    output.Position = camera.view_proj * pos;
     output.vColor = color ;
+    output.vWorld = pos.xyz ;
     return output;
 }
 
+fn clipped(p: vec3<f32>) -> bool {
+    for (var i = 0u; i < u32(clip.params.x); i = i + 1u) {
+        if (dot(clip.planes[i].xyz, p) + clip.planes[i].w > clip.params.y) {
+            return true;
+        }
+    }
+    return false;
+}
+
 @fragment
-fn fs_main(@location(0) vColor: vec4<f32> ) -> @location(0) vec4<f32> {
+fn fs_main(@location(0) vColor: vec4<f32>, @location(1) vWorld: vec3<f32>) -> @location(0) vec4<f32> {
+    if (clipped(vWorld)) {
+        discard;
+    }
     return vColor ;
-}
\ No newline at end of file
+}
+
+// element edges drawn over a surface, darker so they stand out
+@fragment
+fn fs_edge(@location(0) vColor: vec4<f32>, @location(1) vWorld: vec3<f32>) -> @location(0) vec4<f32> {
+    if (clipped(vWorld)) {
+        discard;
+    }
+    return vec4<f32>(vColor.rgb * 0.3, vColor.a) ;
+}
+
+// a line segment per instance drawn as a quad a little wider than a pixel,
+// the sides faded out in fs_line so the line comes out smooth
+struct LineOutput {
+    @builtin(position) Position :vec4<f32>,
+    @location(0) vColor : vec4<f32>,
+    @location(1) vWorld : vec3<f32>,
+    @location(2) side : f32,
+};
+
+@vertex
+fn vs_line(
+    @builtin(vertex_index) index: u32,
+    @location(0) pos_a: vec4<f32>,
+    @location(1) color_a: vec4<f32>,
+    @location(2) pos_b: vec4<f32>,
+    @location(3) color_b: vec4<f32>,
+) -> LineOutput {
+    // two triangles, along 0 at a and 1 at b, side -1 or 1
+    var along = array<f32, 6>(0.0, 1.0, 0.0, 0.0, 1.0, 1.0);
+    var sides = array<f32, 6>(-1.0, -1.0, 1.0, 1.0, -1.0, 1.0);
+    let t = along[index];
+    let side = sides[index];
+    let a = camera.view_proj * pos_a;
+    let b = camera.view_proj * pos_b;
+    let half = camera.viewport.xy * 0.5;
+    var dir = (b.xy / b.w - a.xy / a.w) * half;
+    if (length(dir) < 1e-6) {
+        dir = vec2<f32>(1.0, 0.0);
+    }
+    let normal = normalize(vec2<f32>(-dir.y, dir.x)) / half;
+    var output: LineOutput;
+    let p = mix(a, b, t);
+    output.Position = vec4<f32>(p.xy + normal * side * p.w, p.zw);
+    output.vColor = mix(color_a, color_b, t);
+    output.vWorld = mix(pos_a.xyz, pos_b.xyz, t);
+    output.side = side;
+    return output;
+}
+
+fn line_alpha(side: f32) -> f32 {
+    return 1.0 - smoothstep(0.5, 1.0, abs(side));
+}
+
+@fragment
+fn fs_line(in: LineOutput) -> @location(0) vec4<f32> {
+    if (clipped(in.vWorld)) {
+        discard;
+    }
+    return vec4<f32>(in.vColor.rgb, in.vColor.a * line_alpha(in.side));
+}
+
+@fragment
+fn fs_line_edge(in: LineOutput) -> @location(0) vec4<f32> {
+    if (clipped(in.vWorld)) {
+        discard;
+    }
+    return vec4<f32>(in.vColor.rgb * 0.3, in.vColor.a * line_alpha(in.side));
+}
+
+// instanced arrows, the mesh points along +z with length 1
+struct GlyphOutput {
+    @builtin(position) Position :vec4<f32>,
+    @location(0) vColor : vec4<f32>,
+    @location(1) vWorld : vec3<f32>,
+    @location(2) vNormal : vec3<f32>,
+};
+
+@vertex
+fn vs_glyph(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) origin: vec4<f32>,
+    @location(3) dir: vec4<f32>,
+    @location(4) color: vec4<f32>,
+) -> GlyphOutput {
+    let z = normalize(dir.xyz);
+    var helper = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(z.y) > 0.99) {
+        helper = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let x = normalize(cross(helper, z));
+    let y = cross(z, x);
+    let local = pos * origin.w;
+    let world = origin.xyz + x * local.x + y * local.y + z * local.z;
+
+    var output: GlyphOutput;
+    output.Position = camera.view_proj * vec4<f32>(world, 1.0);
+    output.vColor = color;
+    output.vWorld = world;
+    output.vNormal = x * normal.x + y * normal.y + z * normal.z;
+    return output;
+}
+
+// lit triangles built on the cpu: beam profiles, shell slabs
+@vertex
+fn vs_lit(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+) -> GlyphOutput {
+    var output: GlyphOutput;
+    output.Position = camera.view_proj * vec4<f32>(pos, 1.0);
+    output.vColor = color;
+    output.vWorld = pos;
+    output.vNormal = normal;
+    return output;
+}
+
+@fragment
+fn fs_glyph(in: GlyphOutput) -> @location(0) vec4<f32> {
+    if (clipped(in.vWorld)) {
+        discard;
+    }
+    let light = normalize(vec3<f32>(0.3, 1.0, 0.5));
+    let diffuse = abs(dot(normalize(in.vNormal), light));
+    return vec4<f32>(in.vColor.rgb * (0.35 + 0.65 * diffuse), in.vColor.a);
+}
@@ -1,45 +1,220 @@
 use bytemuck::cast_slice;
+use std::fs;
 use std::iter;
+use std::time::{Duration, Instant};
 use wgpu::util::DeviceExt;
 use winit::{
-    event::{ElementState, KeyboardInput, WindowEvent},
+    event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent},
     window::Window,
 };
 
 use self::camera::{CameraUniform, FpsCamera, FpsController, Projection};
+use self::fields::{CellMode, ColorBy, Field, Location, VectorField};
+use self::hud::{Hud, HudAction, HudInfo, ViewSettings};
+use self::iso::IsoMesh;
+use self::measure::Tool;
+use self::pipelines::Pipelines;
+use self::section::ClipUniform;
+use self::tensor::TensorField;
 use self::td_comp::{TdObject, Vertex};
 //  TODO :
 //  1.Implement a better 3d camera [v]
 //  2. A parser of own 3d plot file format
 //     or just use vtk if it works [v ( own parser )]
 
+mod bc;
 mod camera;
+mod deform;
+mod elements;
+mod export;
+mod fields;
+mod frame;
+mod glyph;
+mod gltf;
+mod hud;
 mod init;
+mod iso;
+mod march;
+mod measure;
+mod model;
+mod overlay;
+mod pipelines;
+mod probe;
+mod profile;
+mod quality;
+mod record;
+mod section;
+mod shell;
+mod solver;
+mod surface_export;
 mod td_comp;
+mod tensor;
+mod topology;
 
 const CAMERA_MOVE_SPEED: f32 = 0.1;
 const CAMERA_SENSITIVITY: f32 = 0.01;
+// pixels the cursor may move between press and release for a pick
+const CLICK_SLOP: f32 = 4.0;
+// how offscreen frames look at the model: a little wider than its bounding sphere,
+// from above at an angle
+const FRAME_MARGIN: f32 = 1.1;
+const FRAME_YAW: f32 = -2.2; // radians
+const FRAME_PITCH: f32 = -25.0; // degrees
+const BACKGROUND: wgpu::Color = wgpu::Color { r: 0.2, g: 0.247, b: 0.314, a: 1.0 };
+
+// fields of a model file as a table, the format from the extension
+pub fn export_results(filename: &str, out: &str, location: &str, set: &str) -> Result<String, String> {
+    let format = export::Format::from_filename(out).ok_or("the output must end in .csv or .json")?;
+    let location = export::parse_location(location).ok_or(format!("unknown location {}, use node or element", location))?;
+    let table = export::table(&TdObject::new(filename), location, set)?;
+    table.save(out, format, set)?;
+    Ok(format!("saved {} rows, {} columns to {}", table.ids.len(), table.header().len(), out))
+}
+
+// the outer surface of a model file; options are ascii (for stl), deformed,
+// animate (a gltf mode shape swings) and field=<name> for vertex colors
+pub fn export_surface(filename: &str, out: &str, options: &[String]) -> Result<String, String> {
+    let mut obj = TdObject::new(filename);
+    if let Some(name) = options.iter().find_map(|o| o.strip_prefix("field=")) {
+        let field = obj.fields.iter().position(|f| f.name == name).ok_or(format!("no field {}", name))?;
+        obj.apply_colors(Some(field), fields::Colormap::Rainbow);
+    }
+    let has = |option: &str| options.iter().any(|o| o == option);
+    let format = surface_export::MeshFormat::from_filename(out, has("ascii"))
+        .ok_or("the output must end in .stl, .obj, .ply, .gltf or .glb")?;
+    let settings = surface_export::SurfaceExportSettings { format, deformed: has("deformed"), colors: true };
+    let deform = deform::DeformSettings {
+        field: displacement_field(&obj),
+        animate: has("animate"),
+        ..Default::default()
+    };
+    if settings.deformed {
+        obj.deformation = deform::offsets(&obj, &deform::DeformSettings { animate: false, ..deform }, 0.0);
+    }
+    let tris = surface_export::save(&obj, out, &settings, &deform)?;
+    Ok(format!("saved {} triangles to {}", tris, out))
+}
+
+// displacement by name, or the first nodal vector field
+fn displacement_field(obj: &TdObject) -> Option<usize> {
+    let nodal = |v: &VectorField| v.location == Location::Node;
+    let field = obj.vectors.iter().position(|v| nodal(v) && v.name == "Displacement");
+    field.or(obj.vectors.iter().position(nodal))
+}
+
+// an animation of a model file rendered offscreen to numbered pngs; options are
+// steps, mode or orbit, size=<w>x<h>, fps=<n>, seconds=<s> (of an orbit) and field=<name>
+pub async fn record_frames(filename: &str, dir: &str, options: &[String]) -> Result<String, String> {
+    let mut settings = record::RecordSettings { dir: dir.to_string(), ..Default::default() };
+    let mut field = None;
+    for option in options {
+        let (key, value) = option.split_once('=').unwrap_or((option, ""));
+        let bad = || format!("bad option {}", option);
+        match key {
+            "size" => {
+                let (w, h) = value.split_once('x').ok_or_else(bad)?;
+                settings.size = [w.parse().map_err(|_| bad())?, h.parse().map_err(|_| bad())?];
+            }
+            "fps" => settings.fps = value.parse().map_err(|_| bad())?,
+            "seconds" => settings.seconds = value.parse().map_err(|_| bad())?,
+            "field" => field = Some(value),
+            _ => settings.sequence = record::Sequence::parse(key).ok_or_else(bad)?,
+        }
+    }
+    if settings.size.contains(&0) || settings.fps == 0 {
+        return Err("size and fps must be positive".to_string());
+    }
+    let mut state = State::headless(filename, settings.size[0], settings.size[1]).await?;
+    if let Some(name) = field {
+        state.view.field = Some(state.obj.fields.iter().position(|f| f.name == name).ok_or(format!("no field {}", name))?);
+    }
+    let count = state.record(&settings)?;
+    Ok(format!("saved {} frames to {}", count, dir))
+}
+
+// the info output of a model file, without opening a window
+pub fn model_info(filename: &str) -> String {
+    TdObject::new(filename).info()
+}
 
 pub struct State {
     obj: TdObject, // Model
+    filename: String,
     // Camera vars
     camera: FpsCamera,
     camera_uniform: CameraUniform,
     camera_buffer: wgpu::Buffer,
     pub controller: FpsController,
     pub mouse_pressed: bool,
+    cursor: [f32; 2],          // in pixels
+    press_at: Option<[f32; 2]>, // where the left button went down
+    picks: Vec<u32>,
+    picked_face: Option<(usize, usize)>,
+    measurement: Option<measure::Measurement>,
+    measure_line_buffer: Option<(wgpu::Buffer, u32)>,
+    probe: Option<probe::Probe>,
+    probe_line_buffer: Option<(wgpu::Buffer, u32)>,
     projection: camera::Projection,
     // wgpu vars
     pub init: init::InitWgpu, // this one has the config , etc.
-    pipeline: wgpu::RenderPipeline,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: Pipelines,
     vertex_buffer: wgpu::Buffer,
     index_buffer : wgpu::Buffer,
+    surface_buffer: wgpu::Buffer,
+    edge_buffer: wgpu::Buffer,
+    smooth_edge_buffer: Option<(wgpu::Buffer, u32)>,  // edges as a line list, for smoothed lines
+    smooth_strip_buffer: Option<(wgpu::Buffer, u32)>, // the same for the line strip
+    clip_buffer: wgpu::Buffer,
+    cap_buffer: Option<(wgpu::Buffer, u32)>,
+    iso_buffer: Option<(wgpu::Buffer, u32)>,
+    iso_line_buffer: Option<(wgpu::Buffer, u32)>,
+    arrow_vertex_buffer: wgpu::Buffer,
+    arrow_index_buffer: wgpu::Buffer,
+    arrow_index_count: u32,
+    glyph_buffer: Option<(wgpu::Buffer, u32)>,
+    cell_buffer: Option<(wgpu::Buffer, u32)>,
+    cell_line_buffer: Option<(wgpu::Buffer, u32)>,
+    profile_buffer: Option<(wgpu::Buffer, u32)>,
+    shell_buffer: Option<(wgpu::Buffer, u32)>,
+    bc_line_buffer: Option<(wgpu::Buffer, u32)>,
+    diagnostics: Option<topology::Diagnostics>,
+    topology_line_buffer: Option<(wgpu::Buffer, u32)>,
+    bc_face_buffer: Option<(wgpu::Buffer, u32)>,
+    bc_arrow_buffer: Option<(wgpu::Buffer, u32)>,
     uniform_bind_group: wgpu::BindGroup,
+    // ui
+    hud: Hud,
+    view: ViewSettings,
+    last_frame: Instant,
+    frame_time: Duration,
+    status: String,
+    results_changed: bool,
+    screenshot_requested: bool, // taken from the next frame rendered
+    anim_time: f32,  // seconds of deformation animation
+    step_clock: f32, // seconds since the last step while playing
 }
 
 impl State {
     pub async fn new(window: &Window, filename: &str) -> Self {
         let init = init::InitWgpu::init_wgpu(window).await;
+        let hud = Hud::new(window, &init.device, init.config.format);
+        Self::build(init, hud, filename)
+    }
+
+    // no window, frames are rendered to textures and read back
+    pub async fn headless(filename: &str, width: u32, height: u32) -> Result<Self, String> {
+        let init = init::InitWgpu::headless(width, height).await?;
+        let hud = Hud::headless(&init.device, init.config.format, [width, height]);
+        let mut state = Self::build(init, hud, filename);
+        state.frame_model(0.0);
+        // egui sizes anchored overlays on their first frame, that one is thrown away
+        state.advance(None);
+        Ok(state)
+    }
+
+    fn build(init: init::InitWgpu, hud: Hud, filename: &str) -> Self {
         let obj = TdObject::new(filename);
         let camera = FpsCamera::new((0.0, 3.0,0.0), cgmath::Deg(-90.0), cgmath::Deg(-90.0));
         let projection = Projection::new(
@@ -61,6 +236,14 @@ impl State {
                 contents: bytemuck::cast_slice(&[camera_uniform]),
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             });
+        let (center, radius) = obj.center_radius();
+        let clip_buffer = init
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Clip Buffer"),
+                contents: bytemuck::cast_slice(&[ClipUniform::new(&[], center, radius)]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
         let shader = init
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -71,25 +254,43 @@ impl State {
         let uniform_bind_group_layout =
             init.device
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::VERTEX,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
                         },
-                        count: None,
-                    }],
+                    ],
                     label: Some("Uniform Bind Group Layout"),
                 });
 
         let uniform_bind_group = init.device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &uniform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: clip_buffer.as_entire_binding(),
+                },
+            ],
             label: Some("Uniform Bind Group"),
         });
 
@@ -101,74 +302,103 @@ impl State {
                 push_constant_ranges: &[],
             });
 
-        let pipeline = init
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[Vertex::desc()],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: init.config.format,
-                        blend: Some(wgpu::BlendState {
-                            color: wgpu::BlendComponent::REPLACE,
-                            alpha: wgpu::BlendComponent::REPLACE,
-                        }),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::LineStrip, // this in particular!!
-                    strip_index_format: None,
-                    ..Default::default()
-                },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: wgpu::TextureFormat::Depth24Plus,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::LessEqual,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    ..Default::default()
-                },
-                multiview: None,
-            });
+        // 4x when the adapter has it, the hud can change it
+        let samples = if init.sample_counts.contains(&4) { 4 } else { 1 };
+        let pipelines = Pipelines::new(&init, &pipeline_layout, &shader, samples);
+        let view = ViewSettings { msaa: samples, ..ViewSettings::default() };
 
         let vertex_buffer = init
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Vertex Buffer"),
                 contents: cast_slice(&obj.vertices), 
-                usage: wgpu::BufferUsages::VERTEX,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             });
         let index_buffer  = init .device .create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
             contents: cast_slice(&obj.indices),
             usage : wgpu::BufferUsages::INDEX
         });
+        let surface_buffer = init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Surface Index Buffer"),
+            contents: cast_slice(&obj.surface),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let edge_buffer = init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Edge Index Buffer"),
+            contents: cast_slice(&obj.edges),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let (arrow_verts, arrow_inds) = glyph::arrow_mesh();
+        let arrow_vertex_buffer = init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Arrow Vertex Buffer"),
+            contents: cast_slice(&arrow_verts),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let arrow_index_buffer = init.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Arrow Index Buffer"),
+            contents: cast_slice(&arrow_inds),
+            usage: wgpu::BufferUsages::INDEX,
+        });
 
-        Self {
+        let mut state = Self {
             obj,
+            filename: filename.to_string(),
             camera,
             camera_uniform,
             camera_buffer,
             controller,
             init,
-            pipeline,
+            shader,
+            pipeline_layout,
+            pipelines,
             vertex_buffer,
             index_buffer,
+            surface_buffer,
+            edge_buffer,
+            smooth_edge_buffer: None,
+            smooth_strip_buffer: None,
+            clip_buffer,
+            cap_buffer: None,
+            iso_buffer: None,
+            iso_line_buffer: None,
+            arrow_vertex_buffer,
+            arrow_index_buffer,
+            arrow_index_count: arrow_inds.len() as u32,
+            glyph_buffer: None,
+            cell_buffer: None,
+            cell_line_buffer: None,
+            profile_buffer: None,
+            shell_buffer: None,
+            bc_line_buffer: None,
+            diagnostics: None,
+            topology_line_buffer: None,
+            bc_face_buffer: None,
+            bc_arrow_buffer: None,
             projection,
             uniform_bind_group,
             mouse_pressed: false,
-        }
+            cursor: [0.0; 2],
+            press_at: None,
+            picks: Vec::new(),
+            picked_face: None,
+            measurement: None,
+            measure_line_buffer: None,
+            probe: None,
+            probe_line_buffer: None,
+            hud,
+            view,
+            last_frame: Instant::now(),
+            frame_time: Duration::ZERO,
+            status: String::new(),
+            results_changed: false,
+            screenshot_requested: false,
+            anim_time: 0.0,
+            step_clock: 0.0,
+        };
+        state.update_bcs();
+        state.update_profiles();
+        state
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -177,21 +407,50 @@ impl State {
             self.init.size = new_size;
             self.init.config.width = new_size.width;
             self.init.config.height = new_size.height;
-            self.init
-                .surface
-                .configure(&self.init.device, &self.init.config);
+            if let Some(surface) = &self.init.surface {
+                surface.configure(&self.init.device, &self.init.config);
+            }
             self.projection.resize(new_size.width, new_size.height);
             self.init.size = new_size;
             self.init.config.width = new_size.width;
             self.init.config.height = new_size.height;
-            self.init
-                .surface
-                .configure(&self.init.device, &self.init.config);
+            if let Some(surface) = &self.init.surface {
+                surface.configure(&self.init.device, &self.init.config);
+            }
         }
     }
 
     pub fn input(&mut self, event: &WindowEvent) -> bool {
+        if self.hud.on_window_event(event) {
+            // don't leave the camera dragging when the pointer ends up over the panel
+            self.mouse_pressed = false;
+            return true;
+        }
         match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(VirtualKeyCode::F1),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                self.hud.visible = !self.hud.visible;
+                true
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(VirtualKeyCode::F12),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                self.screenshot_requested = true;
+                true
+            }
             WindowEvent::KeyboardInput {
                 input:
                     KeyboardInput {
@@ -205,12 +464,26 @@ impl State {
                 self.controller.process_scroll(delta);
                 true
             }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = [position.x as f32, position.y as f32];
+                false
+            }
             WindowEvent::MouseInput {
                 button: winit::event::MouseButton::Left,
                 state,
                 ..
             } => {
-                self.mouse_pressed = *state == ElementState::Pressed;
+                let pressed = *state == ElementState::Pressed;
+                self.mouse_pressed = pressed && !self.hud.wants_pointer();
+                if self.mouse_pressed {
+                    self.press_at = Some(self.cursor);
+                } else if let Some(at) = self.press_at.take() {
+                    // a click, not the end of a camera drag
+                    let moved = (at[0] - self.cursor[0]).abs() + (at[1] - self.cursor[1]).abs();
+                    if !pressed && moved < CLICK_SLOP {
+                        self.pick();
+                    }
+                }
                 true
             }
             WindowEvent::MouseInput {
@@ -224,10 +497,153 @@ impl State {
         }
     }
 
-    pub fn update(&mut self) {
+    pub fn update(&mut self, window: &Window) {
+        let now = Instant::now();
+        self.frame_time = now - self.last_frame;
+        self.last_frame = now;
+        self.advance(Some(window));
+    }
+
+    // one frame of hud and view changes, the clocks move by frame_time
+    fn advance(&mut self, window: Option<&Window>) {
+        let old_view = self.view.clone();
+        let actions = self.hud.run(
+            window,
+            HudInfo {
+                obj: &self.obj,
+                camera: &self.camera,
+                projection: &self.projection,
+                controller: &self.controller,
+                frame_time: self.frame_time,
+                status: &self.status,
+                measurement: self.measurement.as_ref(),
+                probe: self.probe.as_ref(),
+                sample_counts: &self.init.sample_counts,
+            },
+            &mut self.view,
+        );
+        for action in actions {
+            match action {
+                HudAction::ExportIso => self.export_iso(),
+                HudAction::DeriveField => self.derive_field(),
+                HudAction::DeriveDirections => self.derive_directions(),
+                HudAction::Solve => self.solve(),
+                HudAction::Modes => self.modes(),
+                HudAction::Quality => self.quality(),
+                HudAction::Diagnose => self.diagnose(),
+                HudAction::MergeNodes => self.merge_nodes(),
+                HudAction::ProbeFromPicks => self.probe_from_picks(),
+                HudAction::ExportProbe => self.export_probe(),
+                HudAction::ExportResults => self.export_results(),
+                HudAction::ExportSurface => self.export_surface(),
+                HudAction::Screenshot => self.screenshot_requested = true,
+                HudAction::ClearPicks => {
+                    self.picks.clear();
+                    self.picked_face = None;
+                    self.update_measurement();
+                }
+            }
+        }
+
+        // steps advance on their own while playing
+        let dt = self.frame_time.as_secs_f32();
+        if self.view.play_steps && !self.obj.steps.is_empty() {
+            self.step_clock += dt;
+            if self.step_clock >= deform::STEP_INTERVAL {
+                self.step_clock = 0.0;
+                let count = self.obj.steps.len();
+                self.view.step = Some(self.view.step.map_or(0, |i| (i + 1) % count));
+            }
+        }
+        if self.view.step != old_view.step {
+            if let Some(i) = self.view.step {
+                self.show_step(i);
+            }
+        }
+
+        // results were replaced in place, everything derived from them is stale
+        let stale = std::mem::take(&mut self.results_changed);
+        if self.view.layer != old_view.layer {
+            self.select_layer();
+        }
+        let colors_changed =
+            stale || self.view.field != old_view.field || self.view.colormap != old_view.colormap;
+        if colors_changed {
+            self.obj.apply_colors(self.view.field, self.view.colormap);
+        }
+        if self.view.deform.animate {
+            self.anim_time += dt;
+        }
+        let deform_changed = stale || self.view.deform != old_view.deform || self.view.deform.animate;
+        if deform_changed {
+            self.obj.deformation = deform::offsets(&self.obj, &self.view.deform, self.anim_time);
+        }
+        if colors_changed || deform_changed {
+            self.init
+                .queue
+                .write_buffer(&self.vertex_buffer, 0, cast_slice(&self.obj.display_vertices()));
+        }
+        if self.view.msaa != old_view.msaa {
+            self.pipelines = Pipelines::new(&self.init, &self.pipeline_layout, &self.shader, self.view.msaa);
+        }
+        if colors_changed || deform_changed || self.view.smooth_lines != old_view.smooth_lines {
+            self.update_smooth_lines();
+        }
+        if colors_changed
+            || deform_changed
+            || self.view.color_by != old_view.color_by
+            || self.view.cell_mode != old_view.cell_mode
+            || self.view.average_groups != old_view.average_groups
+            || self.view.quality != old_view.quality
+            || self.view.topology.regions != old_view.topology.regions
+        {
+            self.update_cells();
+        }
+        if self.view.planes != old_view.planes || self.view.cap != old_view.cap || colors_changed || deform_changed {
+            self.update_sections();
+        }
+        if colors_changed
+            || deform_changed
+            || self.view.color_by != old_view.color_by
+            || self.view.profiles != old_view.profiles
+        {
+            self.update_profiles();
+        }
+        if colors_changed
+            || deform_changed
+            || self.view.color_by != old_view.color_by
+            || self.view.shells != old_view.shells
+        {
+            self.update_shells();
+        }
+        if deform_changed || self.view.topology.highlight != old_view.topology.highlight {
+            self.update_topology();
+        }
+        if self.view.measure.tool != old_view.measure.tool {
+            self.picks.clear();
+            self.picked_face = None;
+        }
+        if self.view.measure != old_view.measure || (deform_changed && self.view.measure.deformed) {
+            self.update_measurement();
+        }
+        if stale || self.view.field != old_view.field || self.view.probe != old_view.probe {
+            self.update_probe();
+        }
+        if deform_changed || self.view.bcs != old_view.bcs || self.view.colormap != old_view.colormap {
+            self.update_bcs();
+        }
+        if stale || deform_changed || self.view.iso != old_view.iso || self.view.colormap != old_view.colormap {
+            self.update_iso();
+        }
+        if stale || deform_changed || self.view.glyphs != old_view.glyphs || self.view.colormap != old_view.colormap {
+            self.update_glyphs();
+        }
+
         self.controller.update_camera(&mut self.camera);
         self.camera_uniform
             .update_view_proj(&self.camera, &self.projection);
+        // in window pixels, so a larger screenshot keeps the line width the window shows
+        self.camera_uniform.viewport = [self.init.config.width as f32, self.init.config.height as f32, 0.0, 0.0];
         self.init.queue.write_buffer(
             &self.camera_buffer,
             0,
@@ -235,26 +651,580 @@ impl State {
         );
     }
 
+    // the indexed edges and line strip spelled out as line lists, which the
+    // smoothed line pipelines draw a quad per segment of
+    fn update_smooth_lines(&mut self) {
+        self.smooth_edge_buffer = None;
+        self.smooth_strip_buffer = None;
+        if !self.view.smooth_lines {
+            return;
+        }
+        let verts = self.obj.display_vertices();
+        let edges: Vec<Vertex> = self.obj.edges.iter().map(|i| verts[*i as usize]).collect();
+        let strip: Vec<Vertex> = self
+            .obj
+            .indices
+            .windows(2)
+            .flat_map(|w| [verts[w[0] as usize], verts[w[1] as usize]])
+            .collect();
+        self.smooth_edge_buffer = vertex_buffer(&self.init.device, "Smooth Edge Buffer", &edges);
+        self.smooth_strip_buffer = vertex_buffer(&self.init.device, "Smooth Strip Buffer", &strip);
+    }
+
+    // clip planes to the shader and cut faces rebuilt on the cpu
+    fn update_sections(&mut self) {
+        let (center, radius) = self.obj.center_radius();
+        let uniform = ClipUniform::new(&self.view.planes, center, radius);
+        self.init
+            .queue
+            .write_buffer(&self.clip_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        self.cap_buffer = None;
+        if !self.view.cap {
+            return;
+        }
+        let field = self.view.field.map(|i| &self.obj.fields[i]);
+        let caps = section::caps(&self.obj, &self.view.planes, field, self.view.colormap);
+        self.cap_buffer = vertex_buffer(&self.init.device, "Cap Vertex Buffer", &caps);
+    }
+
+    fn update_profiles(&mut self) {
+        self.profile_buffer = None;
+        if !self.view.profiles.show {
+            return;
+        }
+        let field = self.view.field.map(|i| &self.obj.fields[i]);
+        let verts = profile::beams(&self.obj, field, self.view.color_by, self.view.colormap, &self.view.profiles);
+        self.profile_buffer = vertex_buffer(&self.init.device, "Profile Vertex Buffer", &verts);
+    }
+
+    fn update_shells(&mut self) {
+        self.shell_buffer = None;
+        if !self.view.shells.extrude {
+            return;
+        }
+        let field = self.view.field.map(|i| &self.obj.fields[i]);
+        let verts = shell::slabs(&self.obj, field, self.view.color_by, self.view.colormap, &self.view.shells);
+        self.shell_buffer = vertex_buffer(&self.init.device, "Shell Vertex Buffer", &verts);
+    }
+
+    // the shown field's twin on the chosen shell layer
+    fn select_layer(&mut self) {
+        let Some(current) = self.view.field.map(|i| &self.obj.fields[i]) else {
+            return;
+        };
+        if current.layer.is_none() {
+            return;
+        }
+        let layer = Some(self.view.layer);
+        if let Some(i) = self.obj.fields.iter().position(|f| f.name == current.name && f.layer == layer) {
+            self.view.field = Some(i);
+        }
+    }
+
+    // a node or face under the cursor for the measure tool
+    fn pick(&mut self) {
+        let Some(tool) = self.view.measure.tool else {
+            return;
+        };
+        let view_proj = self.projection.calc_matrix() * self.camera.calc_matrix();
+        let size = [self.init.size.width as f32, self.init.size.height as f32];
+        let deformed = self.view.measure.deformed;
+        if tool == Tool::Area {
+            self.picked_face = measure::pick_face(&self.obj, view_proj, size, self.cursor, deformed);
+        } else if let Some(n) = measure::pick_node(&self.obj, view_proj, size, self.cursor, deformed) {
+            if tool.node_count().is_some_and(|c| self.picks.len() >= c) {
+                self.picks.clear();
+            }
+            self.picks.push(n);
+        }
+        self.update_measurement();
+    }
+
+    fn update_measurement(&mut self) {
+        self.measurement = None;
+        self.measure_line_buffer = None;
+        let Some(tool) = self.view.measure.tool else {
+            return;
+        };
+        let m = measure::evaluate(&self.obj, tool, &self.picks, self.picked_face, self.view.measure.deformed);
+        self.measure_line_buffer = vertex_buffer(&self.init.device, "Measure Line Buffer", &m.lines);
+        self.measurement = Some(m);
+    }
+
+    fn update_probe(&mut self) {
+        self.probe = None;
+        self.probe_line_buffer = None;
+        if !self.view.probe.show {
+            return;
+        }
+        self.probe_line_buffer = vertex_buffer(&self.init.device, "Probe Line Buffer", &probe::line(&self.view.probe));
+        if let Some(field) = self.view.field.map(|i| &self.obj.fields[i]) {
+            self.probe = Some(probe::sample(&self.obj, field, &self.view.probe));
+        }
+    }
+
+    // picked nodes as they are in the model, the probe samples the undeformed mesh
+    fn probe_from_picks(&mut self) {
+        let [.., a, b] = self.picks[..] else {
+            self.status = "pick two nodes with a measure tool first".to_string();
+            return;
+        };
+        let point = |n: u32| {
+            let p = self.obj.vertices[n as usize].position;
+            [p[0], p[1], p[2]]
+        };
+        self.view.probe.from = point(a);
+        self.view.probe.to = point(b);
+        self.view.probe.show = true;
+    }
+
+    fn export_probe(&mut self) {
+        let Some(probe) = &self.probe else {
+            return;
+        };
+        let stem = self.filename.strip_suffix(".json").unwrap_or(&self.filename);
+        let out = format!("{}_probe.csv", stem);
+        match probe.save_csv(&out) {
+            Ok(_) => {
+                self.status = format!("saved probe to {}", out);
+                log::info!("{}", self.status);
+            }
+            Err(e) => {
+                self.status = format!("could not save {}: {}", out, e);
+                log::warn!("{}", self.status);
+            }
+        }
+    }
+
+    fn export_results(&mut self) {
+        let settings = &self.view.export;
+        let out = export::filename(&self.filename, settings);
+        let saved = export::table(&self.obj, settings.location, &settings.set)
+            .and_then(|t| t.save(&out, settings.format, &settings.set));
+        self.status = match saved {
+            Ok(_) => format!("saved results to {}", out),
+            Err(e) => format!("could not save {}: {}", out, e),
+        };
+        log::info!("{}", self.status);
+    }
+
+    fn export_surface(&mut self) {
+        let settings = &self.view.surface_export;
+        let stem = self.filename.strip_suffix(".json").unwrap_or(&self.filename);
+        let out = format!("{}_surface.{}", stem, settings.format.extension());
+        self.status = match surface_export::save(&self.obj, &out, settings, &self.view.deform) {
+            Ok(tris) => format!("saved {} triangles to {}", tris, out),
+            Err(e) => format!("could not save {}: {}", out, e),
+        };
+        log::info!("{}", self.status);
+    }
+
+    fn update_topology(&mut self) {
+        self.topology_line_buffer = None;
+        let Some(d) = self.diagnostics.as_ref().filter(|_| self.view.topology.highlight) else {
+            return;
+        };
+        self.topology_line_buffer = vertex_buffer(&self.init.device, "Topology Line Buffer", &d.lines(&self.obj));
+    }
+
+    // supports, loads and thermal boundaries from the model data
+    fn update_bcs(&mut self) {
+        self.bc_line_buffer = None;
+        self.bc_face_buffer = None;
+        self.bc_arrow_buffer = None;
+        if !self.view.bcs.show {
+            return;
+        }
+        let geometry = bc::geometry(&self.obj, &self.view.bcs, self.view.colormap);
+        let device = &self.init.device;
+        self.bc_line_buffer = vertex_buffer(device, "BC Line Buffer", &geometry.lines);
+        self.bc_face_buffer = vertex_buffer(device, "BC Face Buffer", &geometry.faces);
+        self.bc_arrow_buffer = vertex_buffer(device, "BC Arrow Buffer", &geometry.arrows);
+    }
+
+    // element fields that can't live on the shared vertices
+    fn update_cells(&mut self) {
+        self.cell_buffer = None;
+        self.cell_line_buffer = None;
+        if let Some(d) = self.diagnostics.as_ref().filter(|_| self.view.topology.regions) {
+            let (tris, lines) = self.obj.element_vertices(&d.region_colors());
+            self.cell_buffer = vertex_buffer(&self.init.device, "Cell Vertex Buffer", &tris);
+            self.cell_line_buffer = vertex_buffer(&self.init.device, "Cell Line Buffer", &lines);
+            return;
+        }
+        if self.view.color_by != ColorBy::Field {
+            let (tris, lines) = self.obj.category_vertices(self.view.color_by);
+            self.cell_buffer = vertex_buffer(&self.init.device, "Cell Vertex Buffer", &tris);
+            self.cell_line_buffer = vertex_buffer(&self.init.device, "Cell Line Buffer", &lines);
+            return;
+        }
+        let Some(field) = self.view.field.map(|i| &self.obj.fields[i]) else {
+            return;
+        };
+        let q = &self.view.quality;
+        if q.highlight && quality::metric_of(field) == Some(q.metric) {
+            let colors = quality::colors(field, q.metric, q.threshold, self.view.colormap);
+            let (tris, lines) = self.obj.element_vertices(&colors);
+            self.cell_buffer = vertex_buffer(&self.init.device, "Cell Vertex Buffer", &tris);
+            self.cell_line_buffer = vertex_buffer(&self.init.device, "Cell Line Buffer", &lines);
+            return;
+        }
+        let shared = self.view.cell_mode == CellMode::Averaged && self.view.average_groups;
+        if field.location != Location::Element || shared {
+            return;
+        }
+        let verts = self.obj.cell_vertices(
+            field,
+            self.view.cell_mode,
+            self.view.average_groups,
+            self.view.colormap,
+        );
+        self.cell_buffer = vertex_buffer(&self.init.device, "Cell Vertex Buffer", &verts);
+    }
+
+    fn update_iso(&mut self) {
+        self.iso_buffer = None;
+        self.iso_line_buffer = None;
+        let iso = &self.view.iso;
+        let Some(field) = iso.field.map(|i| &self.obj.fields[i]) else {
+            return;
+        };
+        if iso.surfaces {
+            let mesh = IsoMesh::new(&self.obj, field, &iso.levels);
+            let verts = mesh.vertices(self.view.colormap, field.range());
+            self.iso_buffer = vertex_buffer(&self.init.device, "Iso Vertex Buffer", &verts);
+        }
+        if iso.lines {
+            let verts = iso::lines(&self.obj, field, &iso.levels, self.view.colormap);
+            self.iso_line_buffer = vertex_buffer(&self.init.device, "Iso Line Buffer", &verts);
+        }
+    }
+
+    fn update_glyphs(&mut self) {
+        self.glyph_buffer = None;
+        let Some(field) = self.view.glyphs.field.map(|i| &self.obj.vectors[i]) else {
+            return;
+        };
+        let instances = glyph::instances(&self.obj, field, &self.view.glyphs, self.view.colormap);
+        self.glyph_buffer = vertex_buffer(&self.init.device, "Glyph Instance Buffer", &instances);
+    }
+
+    fn solve(&mut self) {
+        match solver::solve(&self.obj, self.view.analysis) {
+            Ok(results) => {
+                self.status = results.summary.clone();
+                self.add_results(results);
+            }
+            Err(e) => self.status = format!("solve failed: {}", e),
+        }
+        log::info!("{}", self.status);
+    }
+
+    fn modes(&mut self) {
+        match solver::modes(&self.obj, self.view.analysis, self.view.modes, self.view.lumped) {
+            Ok(results) => {
+                self.status = results.summary.clone();
+                self.add_results(results);
+                // a mode shape only reads well moving
+                self.view.deform.field = self.obj.vectors.iter().position(|v| v.name == "Displacement");
+                self.view.deform.animate = true;
+            }
+            Err(e) => self.status = format!("modal analysis failed: {}", e),
+        }
+        log::info!("{}", self.status);
+    }
+
+    // replaces results of the same name, returns the index of the first field
+    fn merge(&mut self, fields: Vec<Field>, vectors: Vec<VectorField>, tensors: Vec<TensorField>) -> Option<usize> {
+        let mut first = None;
+        for field in fields {
+            let index = match self.obj.fields.iter().position(|f| f.name == field.name && f.layer == field.layer) {
+                Some(i) => {
+                    self.obj.fields[i] = field;
+                    i
+                }
+                None => {
+                    self.obj.fields.push(field);
+                    self.obj.fields.len() - 1
+                }
+            };
+            first.get_or_insert(index);
+        }
+        for vectors in vectors {
+            match self.obj.vectors.iter().position(|f| f.name == vectors.name) {
+                Some(i) => self.obj.vectors[i] = vectors,
+                None => self.obj.vectors.push(vectors),
+            }
+        }
+        for tensors in tensors {
+            match self.obj.tensors.iter().position(|t| t.name == tensors.name) {
+                Some(i) => self.obj.tensors[i] = tensors,
+                None => self.obj.tensors.push(tensors),
+            }
+        }
+        first
+    }
+
+    // solver output shown right away, new steps replace the old ones
+    fn add_results(&mut self, results: solver::Results) {
+        self.view.field = self.merge(results.fields, results.vectors, results.tensors);
+        if !results.steps.is_empty() {
+            self.obj.steps = results.steps;
+            self.view.step = Some(0);
+            self.show_step(0);
+        }
+        self.results_changed = true;
+    }
+
+    // the step's results take the place of the ones with the same names
+    fn show_step(&mut self, i: usize) {
+        let step = self.obj.steps[i].clone();
+        let first = self.merge(step.fields, step.vectors, Vec::new());
+        if self.view.field.is_none() {
+            self.view.field = first;
+        }
+        self.results_changed = true;
+    }
+
+    fn diagnose(&mut self) {
+        let d = topology::check(&self.obj, &self.view.topology);
+        self.status = d.report().trim_end().to_string();
+        log::info!("{}", self.status);
+        self.diagnostics = Some(d);
+        self.update_topology();
+        self.update_cells();
+    }
+
+    fn merge_nodes(&mut self) {
+        let groups = topology::check(&self.obj, &self.view.topology).coincident;
+        let (merged, skipped) = topology::merge(&mut self.obj, &groups);
+        let device = &self.init.device;
+        self.index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: cast_slice(&self.obj.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        self.surface_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Surface Index Buffer"),
+            contents: cast_slice(&self.obj.surface),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        self.edge_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Edge Index Buffer"),
+            contents: cast_slice(&self.obj.edges),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        self.results_changed = true;
+        self.diagnose();
+        let skipped = if skipped > 0 {
+            format!(", skipped {} groups that would collapse an element", skipped)
+        } else {
+            String::new()
+        };
+        self.status = format!("merged {} nodes{}\n{}", merged, skipped, self.status);
+        self.update_bcs();
+    }
+
+    // the metric of every element, shown flat like any element result
+    fn quality(&mut self) {
+        let field = quality::field(&self.obj, self.view.quality.metric);
+        self.view.field = self.merge(vec![field], Vec::new(), Vec::new());
+        self.view.color_by = ColorBy::Field;
+        self.view.cell_mode = CellMode::Flat;
+        self.results_changed = true;
+    }
+
+    // computed from the tensors as they are now, replacing an earlier one
+    // of the same name, then shown
+    fn derive_field(&mut self) {
+        let field = self.obj.tensors[self.view.tensor].derive(self.view.quantity);
+        self.view.field = self.merge(vec![field], Vec::new(), Vec::new());
+        self.results_changed = true;
+    }
+
+    fn derive_directions(&mut self) {
+        let tensor::Quantity::Principal(i) = self.view.quantity else {
+            return;
+        };
+        let vectors = self.obj.tensors[self.view.tensor].principal_directions(i);
+        let index = match self.obj.vectors.iter().position(|f| f.name == vectors.name) {
+            Some(i) => {
+                self.obj.vectors[i] = vectors;
+                i
+            }
+            None => {
+                self.obj.vectors.push(vectors);
+                self.obj.vectors.len() - 1
+            }
+        };
+        self.view.glyphs.field = Some(index);
+        self.results_changed = true;
+    }
+
+    // next to the input file as <name>_iso.json
+    fn export_iso(&mut self) {
+        let Some(field) = self.view.iso.field.map(|i| &self.obj.fields[i]) else {
+            return;
+        };
+        let mesh = IsoMesh::new(&self.obj, field, &self.view.iso.levels);
+        let stem = self.filename.strip_suffix(".json").unwrap_or(&self.filename);
+        let out = format!("{}_iso.json", stem);
+        match mesh.save(&out, field) {
+            Ok(_) => {
+                self.status = format!("saved iso-surface to {}", out);
+                log::info!("{}", self.status);
+            }
+            Err(e) => {
+                self.status = format!("could not save {}: {}", out, e);
+                log::warn!("{}", self.status);
+            }
+        }
+    }
+
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.init.surface.get_current_texture()?;
+        if std::mem::take(&mut self.screenshot_requested) {
+            self.screenshot();
+        }
+        let Some(surface) = &self.init.surface else {
+            return Ok(());
+        };
+        let output = surface.get_current_texture()?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
-        let depth_texture = self.init.device.create_texture(&wgpu::TextureDescriptor {
+        self.draw(&view, [self.init.config.width, self.init.config.height], BACKGROUND);
+        output.present();
+
+        Ok(())
+    }
+
+    // the frame as shown, at scale times the window size and named by the time;
+    // the view is the same, only the pixels are smaller
+    fn screenshot(&mut self) {
+        let settings = self.view.screenshot;
+        let window = [self.init.config.width, self.init.config.height];
+        let max = self.init.device.limits().max_texture_dimension_2d;
+        let scale = settings.scale.clamp(1, (max / window[0].max(window[1]).max(1)).max(1));
+        let size = window.map(|v| v * scale);
+        let background = if settings.transparent { wgpu::Color::TRANSPARENT } else { BACKGROUND };
+        let stem = self.filename.strip_suffix(".json").unwrap_or(&self.filename);
+        let out = format!("{}_{}.png", stem, frame::timestamp());
+        let saved = self.render_offscreen(size, background).and_then(|mut pixels| {
+            let size = if settings.supersample && scale > 1 {
+                pixels = frame::downsample(&pixels, size, scale);
+                window
+            } else {
+                size
+            };
+            if settings.transparent {
+                frame::unpremultiply(&mut pixels);
+            }
+            frame::save_png(&out, size[0], size[1], &pixels).map(|_| size)
+        });
+        self.status = match saved {
+            Ok(size) => format!("saved {}x{} screenshot to {}", size[0], size[1], out),
+            Err(e) => format!("could not save {}: {}", out, e),
+        };
+        log::info!("{}", self.status);
+    }
+
+    // the frame into a texture of its own, read back as rgba rows
+    fn render_offscreen(&mut self, size: [u32; 2], background: wgpu::Color) -> Result<Vec<u8>, String> {
+        let texture = self.init.device.create_texture(&wgpu::TextureDescriptor {
             size: wgpu::Extent3d {
-                width: self.init.config.width,
-                height: self.init.config.height,
+                width: size[0],
+                height: size[1],
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
+            format: self.init.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            label: Some("Offscreen Texture"),
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.draw(&view, size, background);
+        frame::read_texture(&self.init.device, &self.init.queue, &texture)
+    }
+
+    // the whole model in view, looking down at it from the side turned by `angle`
+    fn frame_model(&mut self, angle: f32) {
+        let (center, radius) = self.obj.center_radius();
+        let distance = radius.max(f32::EPSILON) / (self.projection.fov.0 / 2.0).sin() * FRAME_MARGIN;
+        self.camera.yaw = cgmath::Rad(FRAME_YAW + angle);
+        self.camera.pitch = cgmath::Deg(FRAME_PITCH).into();
+        self.camera.aim_at(center.into(), distance);
+        let size = self.init.size;
+        self.projection = Projection::new(size.width, size.height, cgmath::Deg(45.0), distance * 0.01, distance * 10.0);
+    }
+
+    // every frame of the sequence rendered and saved as png, returns how many;
+    // the clocks stand still and each frame sets them so runs come out the same
+    fn record(&mut self, settings: &record::RecordSettings) -> Result<usize, String> {
+        let frames = record::frames(settings, self.obj.steps.len());
+        if frames.is_empty() {
+            return Err("the model has no steps to play".to_string());
+        }
+        fs::create_dir_all(&settings.dir).map_err(|e| e.to_string())?;
+        if self.view.deform.field.is_none() {
+            self.view.deform.field = displacement_field(&self.obj);
+        }
+        self.view.deform.animate = settings.sequence == record::Sequence::Mode;
+        self.view.play_steps = false;
+        self.frame_time = Duration::ZERO;
+        for (i, f) in frames.iter().enumerate() {
+            if let Some(step) = f.step.filter(|s| self.view.step != Some(*s)) {
+                self.view.step = Some(step);
+                self.show_step(step);
+            }
+            self.anim_time = f.time;
+            // settings made outside the hud are only picked up on stale results
+            self.results_changed = true;
+            self.frame_model(f.angle);
+            self.advance(None);
+            let pixels = self.render_offscreen(settings.size, BACKGROUND)?;
+            frame::save_png(&record::filename(&settings.dir, i), settings.size[0], settings.size[1], &pixels)?;
+        }
+        Ok(frames.len())
+    }
+
+    // the model and the hud on top of it into `view`
+    fn draw(&mut self, view: &wgpu::TextureView, size: [u32; 2], background: wgpu::Color) {
+        let depth_texture = self.init.device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: self.view.msaa,
+            dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Depth24Plus,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             label: Some("depth texture"),
             view_formats: &[],
         });
         let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // the model is drawn multisampled and resolved into `view`, the hud goes
+        // on after that
+        let msaa_view = (self.view.msaa > 1).then(|| {
+            self.init
+                .device
+                .create_texture(&wgpu::TextureDescriptor {
+                    size: depth_texture.size(),
+                    mip_level_count: 1,
+                    sample_count: self.view.msaa,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: self.init.config.format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    label: Some("Multisample Texture"),
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        });
 
         let mut encoder =
             self.init
@@ -266,17 +1236,22 @@ impl State {
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.2,
-                            g: 0.247,
-                            b: 0.314,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
+                color_attachments: &[Some(match &msaa_view {
+                    Some(msaa_view) => wgpu::RenderPassColorAttachment {
+                        view: msaa_view,
+                        resolve_target: Some(view),
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(background),
+                            store: wgpu::StoreOp::Discard,
+                        },
+                    },
+                    None => wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(background),
+                            store: wgpu::StoreOp::Store,
+                        },
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
@@ -291,16 +1266,132 @@ impl State {
                 occlusion_query_set: None,
             });
 
-            render_pass.set_pipeline(&self.pipeline);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..),wgpu::IndexFormat::Uint16);
             render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.draw_indexed(0..self.obj.indices.len() as u32 , 0,0..1);
+            let show_surface = self.view.show_surface && !self.obj.surface.is_empty();
+            if show_surface {
+                render_pass.set_pipeline(&self.pipelines.surface);
+                if let Some((buffer, count)) = &self.cell_buffer {
+                    render_pass.set_vertex_buffer(0, buffer.slice(..));
+                    render_pass.draw(0..*count, 0..1);
+                    render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                } else {
+                    render_pass.set_index_buffer(self.surface_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    render_pass.draw_indexed(0..self.obj.surface.len() as u32, 0, 0..1);
+                }
+            }
+            let smooth = self.view.smooth_lines;
+            if let (true, Some((buffer, count))) = (self.view.show_edges, &self.smooth_edge_buffer) {
+                draw_lines(&mut render_pass, &self.pipelines, show_surface, smooth, buffer, *count);
+                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            } else if self.view.show_edges && !self.obj.edges.is_empty() {
+                render_pass.set_pipeline(if show_surface { &self.pipelines.edge } else { &self.pipelines.bare_edge });
+                render_pass.set_index_buffer(self.edge_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..self.obj.edges.len() as u32, 0, 0..1);
+            }
+            if let (true, Some((buffer, count))) = (self.view.show_lines, &self.smooth_strip_buffer) {
+                draw_lines(&mut render_pass, &self.pipelines, false, smooth, buffer, *count);
+                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            } else if self.view.show_lines && !self.obj.indices.is_empty() {
+                render_pass.set_pipeline(&self.pipelines.strip);
+                render_pass.set_index_buffer(self.index_buffer.slice(..),wgpu::IndexFormat::Uint16);
+                render_pass.draw_indexed(0..self.obj.indices.len() as u32 , 0,0..1);
+            }
+            if self.view.show_nodes {
+                render_pass.set_pipeline(&self.pipelines.node);
+                render_pass.draw(0..self.obj.vertices.len() as u32, 0..1);
+            }
+            for (buffer, count) in self.cap_buffer.iter().chain(&self.iso_buffer) {
+                render_pass.set_pipeline(&self.pipelines.surface);
+                render_pass.set_vertex_buffer(0, buffer.slice(..));
+                render_pass.draw(0..*count, 0..1);
+            }
+            if let Some((buffer, count)) = &self.glyph_buffer {
+                render_pass.set_pipeline(&self.pipelines.glyph);
+                render_pass.set_vertex_buffer(0, self.arrow_vertex_buffer.slice(..));
+                render_pass.set_vertex_buffer(1, buffer.slice(..));
+                render_pass.set_index_buffer(self.arrow_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                render_pass.draw_indexed(0..self.arrow_index_count, 0, 0..*count);
+            }
+            if let Some((buffer, count)) = &self.iso_line_buffer {
+                draw_lines(&mut render_pass, &self.pipelines, show_surface, smooth, buffer, *count);
+            }
+            for (buffer, count) in [&self.profile_buffer, &self.shell_buffer].into_iter().flatten() {
+                render_pass.set_pipeline(&self.pipelines.lit);
+                render_pass.set_vertex_buffer(0, buffer.slice(..));
+                render_pass.draw(0..*count, 0..1);
+            }
+            if let Some((buffer, count)) = &self.cell_line_buffer {
+                draw_lines(&mut render_pass, &self.pipelines, false, smooth, buffer, *count);
+            }
+            if let Some((buffer, count)) = &self.bc_face_buffer {
+                render_pass.set_pipeline(&self.pipelines.surface);
+                render_pass.set_vertex_buffer(0, buffer.slice(..));
+                render_pass.draw(0..*count, 0..1);
+            }
+            if let Some((buffer, count)) = &self.probe_line_buffer {
+                draw_lines(&mut render_pass, &self.pipelines, false, smooth, buffer, *count);
+            }
+            if let Some((buffer, count)) = &self.measure_line_buffer {
+                draw_lines(&mut render_pass, &self.pipelines, false, smooth, buffer, *count);
+            }
+            if let Some((buffer, count)) = &self.topology_line_buffer {
+                draw_lines(&mut render_pass, &self.pipelines, false, smooth, buffer, *count);
+            }
+            if let Some((buffer, count)) = &self.bc_line_buffer {
+                draw_lines(&mut render_pass, &self.pipelines, false, smooth, buffer, *count);
+            }
+            if let Some((buffer, count)) = &self.bc_arrow_buffer {
+                render_pass.set_pipeline(&self.pipelines.glyph);
+                render_pass.set_vertex_buffer(0, self.arrow_vertex_buffer.slice(..));
+                render_pass.set_vertex_buffer(1, buffer.slice(..));
+                render_pass.set_index_buffer(self.arrow_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                render_pass.draw_indexed(0..self.arrow_index_count, 0, 0..*count);
+            }
         }
+        // second pass on top of the model : panel, legend and axis triad
+        self.hud.paint(
+            &self.init.device,
+            &self.init.queue,
+            &mut encoder,
+            view,
+            size,
+            size[0] as f32 / self.init.config.width as f32,
+        );
 
         self.init.queue.submit(iter::once(encoder.finish()));
-        output.present();
+    }
+}
 
-        Ok(())
+// a vertex line list, darker over the surface; smoothed it is a quad per
+// segment, the two ends of each read as one instance
+fn draw_lines<'a>(
+    render_pass: &mut wgpu::RenderPass<'a>,
+    pipelines: &'a Pipelines,
+    over_surface: bool,
+    smooth: bool,
+    buffer: &'a wgpu::Buffer,
+    count: u32,
+) {
+    render_pass.set_vertex_buffer(0, buffer.slice(..));
+    if smooth {
+        render_pass.set_pipeline(if over_surface { &pipelines.edge_line } else { &pipelines.line });
+        render_pass.draw(0..6, 0..count / 2);
+    } else {
+        render_pass.set_pipeline(if over_surface { &pipelines.edge } else { &pipelines.bare_edge });
+        render_pass.draw(0..count, 0..1);
+    }
+}
+
+// None for empty geometry so there is nothing to draw
+fn vertex_buffer<T: bytemuck::Pod>(device: &wgpu::Device, label: &str, verts: &[T]) -> Option<(wgpu::Buffer, u32)> {
+    if verts.is_empty() {
+        return None;
     }
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: cast_slice(verts),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+    Some((buffer, verts.len() as u32))
 }
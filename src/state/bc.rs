@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use super::fields::Colormap;
+use super::glyph::GlyphInstance;
+use super::td_comp::{TdObject, Vertex};
+
+// symbol size at scale 1, relative to the model radius
+const SYMBOL_SIZE: f32 = 0.04;
+// longest force arrow at scale 1, relative to the model radius
+const ARROW_LENGTH: f32 = 0.15;
+// loaded faces are lifted off the surface so they win the depth test
+const FACE_LIFT: f32 = 0.003;
+
+const SUPPORT_COLOR: [f32; 3] = [0.1, 0.8, 0.2];
+const PRESCRIBED_COLOR: [f32; 3] = [0.9, 0.2, 0.9];
+const FORCE_COLOR: [f32; 3] = [0.9, 0.1, 0.1];
+const MOMENT_COLOR: [f32; 3] = [1.0, 0.6, 0.0];
+const FLUX_COLOR: [f32; 3] = [1.0, 0.5, 0.2];
+const CONVECTION_COLOR: [f32; 3] = [0.2, 0.7, 1.0];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BcSettings {
+    pub show: bool,
+    pub scale: f32,
+}
+
+impl Default for BcSettings {
+    fn default() -> Self {
+        Self { show: true, scale: 1.0 }
+    }
+}
+
+// what gets drawn for the model data: symbols as a line list, loaded faces
+// as triangles and forces as arrows
+#[derive(Default)]
+pub struct BcGeometry {
+    pub lines: Vec<Vertex>,
+    pub faces: Vec<Vertex>,
+    pub arrows: Vec<GlyphInstance>,
+}
+
+fn vertex(p: [f32; 3], c: [f32; 3]) -> Vertex {
+    Vertex {
+        position: [p[0], p[1], p[2], 1.0],
+        color: [c[0], c[1], c[2], 1.0],
+    }
+}
+
+fn add(a: [f32; 3], b: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] + b[0] * s, a[1] + b[1] * s, a[2] + b[2] * s]
+}
+
+fn axis(d: usize) -> [f32; 3] {
+    let mut e = [0.0; 3];
+    e[d] = 1.0;
+    e
+}
+
+fn length(v: [f32; 3]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+impl BcGeometry {
+    fn line(&mut self, a: [f32; 3], b: [f32; 3], c: [f32; 3]) {
+        self.lines.extend([vertex(a, c), vertex(b, c)]);
+    }
+
+    // triangle with its tip on the node, pointing along the held direction
+    fn support(&mut self, p: [f32; 3], d: usize, size: f32, c: [f32; 3]) {
+        // the side stays in the xy plane for flat models
+        let side = axis(if d == 0 { 1 } else { 0 });
+        let base = add(p, axis(d), -size);
+        let (l, r) = (add(base, side, -size / 2.0), add(base, side, size / 2.0));
+        self.line(p, l, c);
+        self.line(l, r, c);
+        self.line(r, p, c);
+    }
+
+    // square around the node for held rotations
+    fn clamp(&mut self, p: [f32; 3], size: f32, c: [f32; 3]) {
+        let h = size * 0.3;
+        let corners = [[-h, -h], [h, -h], [h, h], [-h, h]].map(|[x, y]| [p[0] + x, p[1] + y, p[2]]);
+        for i in 0..4 {
+            self.line(corners[i], corners[(i + 1) % 4], c);
+        }
+    }
+
+    fn cross(&mut self, p: [f32; 3], size: f32, c: [f32; 3]) {
+        for d in 0..3 {
+            self.line(add(p, axis(d), -size / 2.0), add(p, axis(d), size / 2.0), c);
+        }
+    }
+
+    // a loaded face lifted outwards, returns its centroid and outward normal
+    fn face(&mut self, obj: &TdObject, element: u32, face: usize, lift: f32, c: [f32; 3]) -> Option<([f32; 3], [f32; 3])> {
+        let nodes = obj.face_nodes(element as usize, face)?;
+        let p: Vec<[f32; 3]> = nodes
+            .iter()
+            .map(|n| {
+                let v = obj.display_position(*n);
+                [v[0], v[1], v[2]]
+            })
+            .collect();
+        let center = p.iter().fold([0.0; 3], |a, q| add(a, *q, 1.0 / p.len() as f32));
+        let out = add(center, obj.display_centroid(&obj.elements[element as usize]), -1.0);
+        let l = length(out);
+        if l == 0.0 {
+            return None;
+        }
+        let normal = out.map(|v| v / l);
+        let p: Vec<[f32; 3]> = p.iter().map(|q| add(*q, normal, lift)).collect();
+        if p.len() == 2 {
+            self.line(p[0], p[1], c);
+        } else {
+            // fan, enough for triangles and quads
+            for i in 1..p.len() - 1 {
+                self.faces.extend([vertex(p[0], c), vertex(p[i], c), vertex(p[i + 1], c)]);
+            }
+        }
+        Some((center, normal))
+    }
+}
+
+pub fn geometry(obj: &TdObject, settings: &BcSettings, cmap: Colormap) -> BcGeometry {
+    let mut out = BcGeometry::default();
+    let model = &obj.model;
+    let (_, radius) = obj.center_radius();
+    let size = SYMBOL_SIZE * radius * settings.scale;
+    let arrow = ARROW_LENGTH * radius * settings.scale;
+    let node = |n: u32| {
+        ((n as usize) < obj.vertices.len()).then(|| {
+            let p = obj.display_position(n);
+            [p[0], p[1], p[2]]
+        })
+    };
+
+    for s in &model.supports {
+        let Some(p) = node(s.node) else { continue };
+        let moved: [f32; 3] = std::array::from_fn(|d| s.prescribed(d).unwrap_or(0.0) as f32);
+        let c = if length(moved) > 0.0 { PRESCRIBED_COLOR } else { SUPPORT_COLOR };
+        for d in (0..3).filter(|d| s.is_fixed(*d)) {
+            out.support(p, d, size, c);
+        }
+        if (3..6).any(|d| s.is_fixed(d)) {
+            out.clamp(p, size, c);
+        }
+        if length(moved) > 0.0 {
+            out.arrows.push(GlyphInstance::new(p, 2.0 * size, moved, PRESCRIBED_COLOR));
+        }
+    }
+
+    // forces end on their node, moments start from it
+    let max_force = model.loads.iter().map(|l| length(l.force.map(|v| v as f32))).fold(0.0, f32::max);
+    let max_moment = model.loads.iter().map(|l| length(l.moment.map(|v| v as f32))).fold(0.0, f32::max);
+    for load in &model.loads {
+        let Some(p) = node(load.node) else { continue };
+        let f = load.force.map(|v| v as f32);
+        let m = load.moment.map(|v| v as f32);
+        if length(f) > 0.0 {
+            let l = arrow * length(f) / max_force;
+            out.arrows.push(GlyphInstance::new(add(p, f, -l / length(f)), l, f, FORCE_COLOR));
+        }
+        if length(m) > 0.0 {
+            let l = arrow * length(m) / max_moment;
+            out.arrows.push(GlyphInstance::new(p, l, m, MOMENT_COLOR));
+            out.arrows.push(GlyphInstance::new(p, l * 0.8, m, MOMENT_COLOR));
+        }
+    }
+
+    // pressures shaded by value, arrows pushing on the face
+    let lift = FACE_LIFT * radius;
+    let (lo, hi) = model
+        .pressures
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p.value as f32), hi.max(p.value as f32)));
+    for pressure in &model.pressures {
+        let c = cmap.map(pressure.value as f32, lo, hi);
+        let Some((center, normal)) = out.face(obj, pressure.element, pressure.face, lift, c) else {
+            continue;
+        };
+        if pressure.value > 0.0 {
+            out.arrows.push(GlyphInstance::new(add(center, normal, size * 2.0), size * 2.0, normal.map(|v| -v), c));
+        } else if pressure.value < 0.0 {
+            out.arrows.push(GlyphInstance::new(center, size * 2.0, normal, c));
+        }
+    }
+
+    // fixed temperatures as crosses colored by value, heat inputs as faces
+    let temps: HashMap<u32, f32> = model
+        .temperatures
+        .iter()
+        .flat_map(|t| {
+            let nodes = model.node_set(&t.set).unwrap_or(&[]);
+            nodes.iter().map(move |n| (*n, t.value as f32))
+        })
+        .collect();
+    let (lo, hi) = temps.values().fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+    for (n, t) in &temps {
+        if let Some(p) = node(*n) {
+            out.cross(p, size, cmap.map(*t, lo, hi));
+        }
+    }
+    let sets = model
+        .fluxes
+        .iter()
+        .map(|f| (&f.set, FLUX_COLOR))
+        .chain(model.convections.iter().map(|c| (&c.set, CONVECTION_COLOR)));
+    for (set, c) in sets {
+        for face in model.face_set(set).unwrap_or(&[]) {
+            out.face(obj, face.element, face.face, lift, c);
+        }
+    }
+    out
+}
@@ -17,8 +17,8 @@ const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
  
 pub struct  FpsCamera {
   pub pos: Point3<f32>,
-  yaw: Rad<f32>,
-  pitch: Rad<f32>,
+  pub yaw: Rad<f32>,
+  pub pitch: Rad<f32>,
 }
 
 impl FpsCamera {
@@ -45,6 +45,14 @@ impl FpsCamera {
           Vector3::unit_y(),
       )
   }
+
+  // moved back from `target` along the view direction until it is `distance` away
+  pub fn aim_at(&mut self, target: Point3<f32>, distance: f32) {
+      let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
+      let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+      let dir = Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw);
+      self.pos = target - dir * distance;
+  }
 }
 
 impl std::fmt::Display for FpsCamera {
@@ -57,7 +65,7 @@ impl std::fmt::Display for FpsCamera {
 }
 pub struct Projection {
   aspect: f32,
-  fov: Rad<f32>,
+  pub fov: Rad<f32>,
   z_near: f32,
   z_far: f32,
 }
@@ -99,7 +107,7 @@ pub struct FpsController {
     rotate_horizontal: f32,
     rotate_vertical: f32,
     scroll: f32,
-    speed: f32,
+    pub speed: f32,
     sensitivity: f32,
 }
 
@@ -200,7 +208,6 @@ impl FpsController {
         } else if camera.pitch > Rad(SAFE_FRAC_PI_2) {
             camera.pitch = Rad(SAFE_FRAC_PI_2);
         }
-        println!("postion : {:}",camera);
     }
     
     pub fn set_to_origin(&self, camera : &mut FpsCamera) {
@@ -215,12 +222,14 @@ impl FpsController {
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
    pub  view_proj: [[f32; 4]; 4],
+   pub  viewport: [f32; 4], // width and height in pixels, for lines drawn as quads
 }
 
 impl CameraUniform {
   pub  fn new() -> Self {
         Self {
             view_proj: cgmath::Matrix4::identity().into(),
+            viewport: [1.0; 4],
         }
     }
 
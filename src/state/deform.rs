@@ -0,0 +1,51 @@
+use std::f32::consts::TAU;
+
+use super::fields::{Location, VectorField};
+use super::td_comp::TdObject;
+
+// one back and forth of an animated mode, seconds
+pub const PERIOD: f32 = 1.5;
+// time each step stays on screen while playing, seconds
+pub const STEP_INTERVAL: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeformSettings {
+    pub field: Option<usize>, // a nodal vector field
+    pub scale: f32,           // largest displacement relative to the model radius
+    pub animate: bool,        // swing between -scale and +scale
+}
+
+impl Default for DeformSettings {
+    fn default() -> Self {
+        Self {
+            field: None,
+            scale: 0.1,
+            animate: false,
+        }
+    }
+}
+
+impl DeformSettings {
+    // factor on the displacement at `time` seconds into the animation
+    pub fn factor(&self, field: &VectorField, radius: f32, time: f32) -> f32 {
+        let max = field.magnitudes().into_iter().fold(0.0, f32::max);
+        if max == 0.0 {
+            return 0.0;
+        }
+        let swing = if self.animate { (time * TAU / PERIOD).sin() } else { 1.0 };
+        self.scale * radius / max * swing
+    }
+}
+
+// displacement per node to draw, empty when nothing is deformed
+pub fn offsets(obj: &TdObject, settings: &DeformSettings, time: f32) -> Vec<[f32; 3]> {
+    let Some(field) = settings.field.and_then(|i| obj.vectors.get(i)) else {
+        return Vec::new();
+    };
+    if field.location != Location::Node {
+        return Vec::new();
+    }
+    let (_, radius) = obj.center_radius();
+    let k = settings.factor(field, radius, time);
+    field.values.iter().map(|v| v.map(|c| c * k)).collect()
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// node ordering follows vtk (hex: bottom face ccw then top face ccw)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ElementKind {
+    Line2,
+    Tri3,
+    Quad4,
+    Tet4,
+    Hex8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Element {
+    pub kind: ElementKind,
+    pub nodes: Vec<u32>,
+    // material / part name, nodal averaging can stop at its boundaries
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub group: String,
+}
+
+const LINE_EDGES: [[usize; 2]; 1] = [[0, 1]];
+const TRI_EDGES: [[usize; 2]; 3] = [[0, 1], [1, 2], [2, 0]];
+const QUAD_EDGES: [[usize; 2]; 4] = [[0, 1], [1, 2], [2, 3], [3, 0]];
+const TET_EDGES: [[usize; 2]; 6] = [[0, 1], [1, 2], [2, 0], [0, 3], [1, 3], [2, 3]];
+#[rustfmt::skip]
+const HEX_EDGES: [[usize; 2]; 12] = [
+    [0, 1], [1, 2], [2, 3], [3, 0],
+    [4, 5], [5, 6], [6, 7], [7, 4],
+    [0, 4], [1, 5], [2, 6], [3, 7],
+];
+
+const TET_FACES: [&[usize]; 4] = [&[0, 2, 1], &[0, 1, 3], &[1, 2, 3], &[0, 3, 2]];
+const HEX_FACES: [&[usize]; 6] = [
+    &[0, 3, 2, 1],
+    &[4, 5, 6, 7],
+    &[0, 1, 5, 4],
+    &[1, 2, 6, 5],
+    &[2, 3, 7, 6],
+    &[3, 0, 4, 7],
+];
+// split along the 0-6 diagonal so every tet shares it
+const HEX_TETS: [[usize; 4]; 6] = [
+    [0, 1, 2, 6],
+    [0, 2, 3, 6],
+    [0, 3, 7, 6],
+    [0, 7, 4, 6],
+    [0, 4, 5, 6],
+    [0, 5, 1, 6],
+];
+
+impl ElementKind {
+    pub fn node_count(self) -> usize {
+        match self {
+            ElementKind::Line2 => 2,
+            ElementKind::Tri3 => 3,
+            ElementKind::Quad4 => 4,
+            ElementKind::Tet4 => 4,
+            ElementKind::Hex8 => 8,
+        }
+    }
+
+    pub fn is_volume(self) -> bool {
+        matches!(self, ElementKind::Tet4 | ElementKind::Hex8)
+    }
+
+    pub fn edges(self) -> &'static [[usize; 2]] {
+        match self {
+            ElementKind::Line2 => &LINE_EDGES,
+            ElementKind::Tri3 => &TRI_EDGES,
+            ElementKind::Quad4 => &QUAD_EDGES,
+            ElementKind::Tet4 => &TET_EDGES,
+            ElementKind::Hex8 => &HEX_EDGES,
+        }
+    }
+
+    // faces as local node lists, a surface element is its own single face
+    pub fn faces(self) -> &'static [&'static [usize]] {
+        match self {
+            ElementKind::Line2 => &[],
+            ElementKind::Tri3 => &[&[0, 1, 2]],
+            ElementKind::Quad4 => &[&[0, 1, 2, 3]],
+            ElementKind::Tet4 => &TET_FACES,
+            ElementKind::Hex8 => &HEX_FACES,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ElementKind::Line2 => "line2",
+            ElementKind::Tri3 => "tri3",
+            ElementKind::Quad4 => "quad4",
+            ElementKind::Tet4 => "tet4",
+            ElementKind::Hex8 => "hex8",
+        }
+    }
+}
+
+impl Element {
+    // volume elements split into linear tets, empty for lines and surfaces
+    pub fn tets(&self) -> Vec<[u32; 4]> {
+        let n = &self.nodes;
+        match self.kind {
+            ElementKind::Tet4 => vec![[n[0], n[1], n[2], n[3]]],
+            ElementKind::Hex8 => HEX_TETS
+                .iter()
+                .map(|t| [n[t[0]], n[t[1]], n[t[2]], n[t[3]]])
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+// drops elements with the wrong node count or nodes out of range
+pub fn validate(elements: Vec<Element>, node_count: usize) -> Vec<Element> {
+    elements
+        .into_iter()
+        .enumerate()
+        .filter(|(i, e)| {
+            let ok = e.nodes.len() == e.kind.node_count()
+                && e.nodes.iter().all(|n| (*n as usize) < node_count);
+            if !ok {
+                log::warn!("element {} ({}) has bad connectivity, skipped", i, e.kind.name());
+            }
+            ok
+        })
+        .map(|(_, e)| e)
+        .collect()
+}
+
+// unique element edges as a line list
+pub fn edge_indices(elements: &[Element]) -> Vec<u32> {
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for e in elements {
+        for [a, b] in e.kind.edges() {
+            let (a, b) = (e.nodes[*a], e.nodes[*b]);
+            if seen.insert((a.min(b), a.max(b))) {
+                out.extend([a, b]);
+            }
+        }
+    }
+    out
+}
+
+// outer faces of volumes plus all surface elements, fanned into triangles,
+// with the element each triangle came from
+pub fn surface_indices(elements: &[Element]) -> (Vec<u32>, Vec<u32>) {
+    let mut faces: HashMap<Vec<u32>, (Vec<u32>, u32, usize)> = HashMap::new();
+    let mut out = Vec::new();
+    let mut owners = Vec::new();
+    for (id, e) in elements.iter().enumerate() {
+        for face in e.kind.faces() {
+            let nodes: Vec<u32> = face.iter().map(|i| e.nodes[*i]).collect();
+            if !e.kind.is_volume() {
+                fan(&nodes, id as u32, &mut out, &mut owners);
+                continue;
+            }
+            let mut key = nodes.clone();
+            key.sort_unstable();
+            faces.entry(key).or_insert((nodes, id as u32, 0)).2 += 1;
+        }
+    }
+    // a face shared by two volumes is inside the body
+    let mut outer: Vec<_> = faces.into_values().filter(|(_, _, n)| *n == 1).collect();
+    outer.sort();
+    for (nodes, id, _) in outer {
+        fan(&nodes, id, &mut out, &mut owners);
+    }
+    (out, owners)
+}
+
+fn fan(nodes: &[u32], owner: u32, out: &mut Vec<u32>, owners: &mut Vec<u32>) {
+    for i in 1..nodes.len() - 1 {
+        out.extend([nodes[0], nodes[i], nodes[i + 1]]);
+        owners.push(owner);
+    }
+}
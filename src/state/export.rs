@@ -0,0 +1,259 @@
+use serde_json::{json, Value};
+use std::fs;
+
+use super::fields::Location;
+use super::td_comp::TdObject;
+use super::tensor::Quantity;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+        }
+    }
+
+    pub fn from_filename(filename: &str) -> Option<Format> {
+        match filename.rsplit('.').next()?.to_lowercase().as_str() {
+            "csv" => Some(Format::Csv),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportSettings {
+    pub location: Location,
+    pub set: String, // node set or element group, empty for all
+    pub format: Format,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self { location: Location::Node, set: String::new(), format: Format::Csv }
+    }
+}
+
+// one row per node or element: id, text columns, then numbers
+pub struct Table {
+    pub location: Location,
+    pub ids: Vec<u32>,
+    pub labels: Vec<(String, Vec<String>)>,
+    pub columns: Vec<(String, Vec<f32>)>,
+}
+
+fn location_name(location: Location) -> &'static str {
+    match location {
+        Location::Node => "node",
+        Location::Element => "element",
+    }
+}
+
+pub fn parse_location(name: &str) -> Option<Location> {
+    match name {
+        "node" | "nodes" => Some(Location::Node),
+        "element" | "elements" => Some(Location::Element),
+        _ => None,
+    }
+}
+
+// coordinates, then every field, vector and tensor living at the location;
+// tensors come with their derived quantities
+pub fn table(obj: &TdObject, location: Location, set: &str) -> Result<Table, String> {
+    let ids: Vec<u32> = match (location, set) {
+        (Location::Node, "") => (0..obj.vertices.len() as u32).collect(),
+        (Location::Node, set) => {
+            let nodes = obj.model.node_set(set)?;
+            nodes.iter().cloned().filter(|n| (*n as usize) < obj.vertices.len()).collect()
+        }
+        (Location::Element, "") => (0..obj.elements.len() as u32).collect(),
+        (Location::Element, set) => {
+            let ids: Vec<u32> = (0..obj.elements.len() as u32).filter(|i| obj.elements[*i as usize].group == set).collect();
+            if ids.is_empty() {
+                return Err(format!("no elements in group {}", set));
+            }
+            ids
+        }
+    };
+    let mut labels = Vec::new();
+    let points: Vec<[f32; 3]> = match location {
+        Location::Node => ids
+            .iter()
+            .map(|n| {
+                let p = obj.vertices[*n as usize].position;
+                [p[0], p[1], p[2]]
+            })
+            .collect(),
+        Location::Element => {
+            let elements = ids.iter().map(|i| &obj.elements[*i as usize]);
+            labels.push(("kind".to_string(), elements.clone().map(|e| e.kind.name().to_string()).collect()));
+            labels.push(("group".to_string(), elements.clone().map(|e| e.group.clone()).collect()));
+            elements.map(|e| obj.centroid(e)).collect()
+        }
+    };
+    let pick = |values: &dyn Fn(usize) -> f32| ids.iter().map(|i| values(*i as usize)).collect::<Vec<f32>>();
+
+    let mut columns = Vec::new();
+    for (k, axis) in ["x", "y", "z"].iter().enumerate() {
+        columns.push((axis.to_string(), points.iter().map(|p| p[k]).collect()));
+    }
+    for f in obj.fields.iter().filter(|f| f.location == location) {
+        let name = match f.layer {
+            Some(layer) => format!("{} ({})", f.name, layer.name().to_lowercase()),
+            None => f.name.clone(),
+        };
+        columns.push((name, pick(&|i| f.values[i])));
+    }
+    for v in obj.vectors.iter().filter(|v| v.location == location) {
+        for (k, axis) in ["X", "Y", "Z"].iter().enumerate() {
+            columns.push((format!("{} {}", v.name, axis), pick(&|i| v.values[i][k])));
+        }
+    }
+    for t in obj.tensors.iter().filter(|t| t.location == location) {
+        for q in Quantity::ALL {
+            columns.push((format!("{} {}", t.name, q.name()), pick(&|i| q.eval(&t.values[i]))));
+        }
+    }
+    Ok(Table { location, ids, labels, columns })
+}
+
+// quoted when the text would break the row
+fn csv_cell(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl Table {
+    pub fn header(&self) -> Vec<String> {
+        let names = self.labels.iter().map(|(n, _)| n).chain(self.columns.iter().map(|(n, _)| n));
+        std::iter::once("id".to_string()).chain(names.cloned()).collect()
+    }
+
+    // NaN cells are left empty
+    pub fn to_csv(&self) -> String {
+        let header: Vec<String> = self.header().iter().map(|h| csv_cell(h)).collect();
+        let mut out = header.join(",") + "\n";
+        for (row, id) in self.ids.iter().enumerate() {
+            let mut cells = vec![id.to_string()];
+            cells.extend(self.labels.iter().map(|(_, l)| csv_cell(&l[row])));
+            cells.extend(self.columns.iter().map(|(_, c)| if c[row].is_nan() { String::new() } else { c[row].to_string() }));
+            out += &(cells.join(",") + "\n");
+        }
+        out
+    }
+
+    // column names and rows, reads straight into a data frame; NaN becomes null
+    pub fn to_json(&self, set: &str) -> Value {
+        let rows: Vec<Value> = (0..self.ids.len())
+            .map(|row| {
+                let mut cells = vec![json!(self.ids[row])];
+                cells.extend(self.labels.iter().map(|(_, l)| json!(l[row])));
+                cells.extend(self.columns.iter().map(|(_, c)| json!(c[row])));
+                Value::Array(cells)
+            })
+            .collect();
+        json!({
+            "location": location_name(self.location),
+            "set": set,
+            "columns": self.header(),
+            "rows": rows,
+        })
+    }
+
+    pub fn save(&self, filename: &str, format: Format, set: &str) -> Result<(), String> {
+        let text = match format {
+            Format::Csv => self.to_csv(),
+            Format::Json => serde_json::to_string(&self.to_json(set)).map_err(|e| e.to_string())?,
+        };
+        fs::write(filename, text).map_err(|e| e.to_string())
+    }
+}
+
+// the file the viewer writes next to the model
+pub fn filename(model: &str, settings: &ExportSettings) -> String {
+    let stem = model.strip_suffix(".json").unwrap_or(model);
+    let set = if settings.set.is_empty() { String::new() } else { format!("_{}", settings.set) };
+    format!("{}_{}{}.{}", stem, location_name(settings.location), set, settings.format.extension())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // two triangles in different groups, one group name that needs quoting
+    fn plate() -> TdObject {
+        let doc = json!({
+            "vers": [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+            "elems": [
+                { "kind": "tri3", "nodes": [0, 1, 2], "group": "steel" },
+                { "kind": "tri3", "nodes": [0, 2, 3], "group": "plate, \"thin\"" },
+            ],
+            "fields": [
+                { "name": "Temp", "values": [10.0, 20.0, 30.0, 40.0] },
+                { "name": "S, Mises", "location": "element", "values": [1.5, 2.5] },
+            ],
+            "node_sets": { "top": [2, 3, 99] },
+        });
+        TdObject::from_json(&doc.to_string())
+    }
+
+    #[test]
+    fn node_set_filters_the_rows() {
+        let obj = plate();
+        let nodes = table(&obj, Location::Node, "top").unwrap();
+        // the node past the end of the mesh is dropped
+        assert_eq!(nodes.ids, vec![2, 3]);
+        assert_eq!(nodes.header(), vec!["id", "x", "y", "z", "Temp"]);
+        assert_eq!(nodes.columns[3].1, vec![30.0, 40.0]);
+        assert!(table(&obj, Location::Node, "bottom").is_err());
+    }
+
+    #[test]
+    fn element_group_filters_the_rows() {
+        let obj = plate();
+        let steel = table(&obj, Location::Element, "steel").unwrap();
+        assert_eq!(steel.ids, vec![0]);
+        assert_eq!(steel.labels[1].1, vec!["steel"]);
+        assert_eq!(steel.columns[3], ("S, Mises".to_string(), vec![1.5]));
+        assert_eq!(table(&obj, Location::Element, "").unwrap().ids, vec![0, 1]);
+        let err = table(&obj, Location::Element, "copper").err().unwrap();
+        assert_eq!(err, "no elements in group copper");
+    }
+
+    #[test]
+    fn names_with_commas_or_quotes_are_quoted() {
+        assert_eq!(csv_cell("steel"), "steel");
+        assert_eq!(csv_cell("S, Mises"), "\"S, Mises\"");
+        assert_eq!(csv_cell("plate, \"thin\""), "\"plate, \"\"thin\"\"\"");
+        let csv = table(&plate(), Location::Element, "").unwrap().to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "id,kind,group,x,y,z,\"S, Mises\"");
+        assert!(lines[2].starts_with("1,tri3,\"plate, \"\"thin\"\"\","));
+    }
+
+    #[test]
+    fn nan_is_an_empty_cell_and_null() {
+        let mut obj = plate();
+        obj.fields[0].values[1] = f32::NAN;
+        let nodes = table(&obj, Location::Node, "").unwrap();
+        let csv = nodes.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[2], "1,1,0,0,");
+        assert_eq!(lines[3], "2,1,1,0,30");
+        let doc = nodes.to_json("");
+        assert_eq!(doc["location"], "node");
+        assert_eq!(doc["rows"][1], json!([1, 1.0, 0.0, 0.0, null]));
+        assert_eq!(doc["rows"][2][4], json!(30.0));
+    }
+}
@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+
+// where the values of a field live
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Location {
+    #[default]
+    Node,
+    Element,
+}
+
+// surface of a shell a result is taken at
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layer {
+    Top,
+    #[default]
+    Middle,
+    Bottom,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 3] = [Layer::Top, Layer::Middle, Layer::Bottom];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Layer::Top => "Top",
+            Layer::Middle => "Middle",
+            Layer::Bottom => "Bottom",
+        }
+    }
+}
+
+// a scalar result per node, or per element for stresses and the like
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(default)]
+    pub units: String,
+    #[serde(default)]
+    pub location: Location,
+    pub values: Vec<f32>,
+    // shell results come as one field of the same name per layer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<Layer>,
+}
+
+impl Field {
+    pub fn range(&self) -> (f32, f32) {
+        self.values
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)))
+    }
+}
+
+// a vector result per node or per element (drawn at the centroid)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VectorField {
+    pub name: String,
+    #[serde(default)]
+    pub units: String,
+    #[serde(default)]
+    pub location: Location,
+    pub values: Vec<[f32; 3]>,
+}
+
+impl VectorField {
+    pub fn magnitudes(&self) -> Vec<f32> {
+        self.values
+            .iter()
+            .map(|v| (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt())
+            .collect()
+    }
+}
+
+// one result set of a sequence (a time step, a mode shape), its fields
+// and vectors replace the ones of the same name when it is shown
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Step {
+    pub name: String,
+    #[serde(default)]
+    pub value: f32, // time, frequency
+    #[serde(default)]
+    pub fields: Vec<Field>,
+    #[serde(default)]
+    pub vectors: Vec<VectorField>,
+}
+
+// how element fields are put on the surface
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellMode {
+    Flat,     // one color per element
+    Averaged, // element values averaged at the nodes
+}
+
+// what the model is colored by, a result field or a property of the elements
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorBy {
+    Field,
+    Group,
+    Material,
+    Section,
+}
+
+impl ColorBy {
+    pub const ALL: [ColorBy; 4] = [ColorBy::Field, ColorBy::Group, ColorBy::Material, ColorBy::Section];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorBy::Field => "Field",
+            ColorBy::Group => "Group",
+            ColorBy::Material => "Material",
+            ColorBy::Section => "Section",
+        }
+    }
+}
+
+// distinct colors for categories, repeated when there are more
+const PALETTE: [[f32; 3]; 10] = [
+    [0.122, 0.467, 0.706],
+    [1.000, 0.498, 0.055],
+    [0.173, 0.627, 0.173],
+    [0.839, 0.153, 0.157],
+    [0.580, 0.404, 0.741],
+    [0.549, 0.337, 0.294],
+    [0.890, 0.467, 0.761],
+    [0.498, 0.498, 0.498],
+    [0.737, 0.741, 0.133],
+    [0.090, 0.745, 0.812],
+];
+
+pub fn category_color(i: usize) -> [f32; 3] {
+    PALETTE[i % PALETTE.len()]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colormap {
+    Rainbow,
+    Viridis,
+    CoolWarm,
+    Grayscale,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 4] = [
+        Colormap::Rainbow,
+        Colormap::Viridis,
+        Colormap::CoolWarm,
+        Colormap::Grayscale,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Colormap::Rainbow => "Rainbow",
+            Colormap::Viridis => "Viridis",
+            Colormap::CoolWarm => "Cool-warm",
+            Colormap::Grayscale => "Grayscale",
+        }
+    }
+
+    // color of v on a scale from lo to hi
+    pub fn map(self, v: f32, lo: f32, hi: f32) -> [f32; 3] {
+        let span = if hi > lo { hi - lo } else { 1.0 };
+        self.sample((v - lo) / span)
+    }
+
+    // t is clamped to [0,1]
+    pub fn sample(self, t: f32) -> [f32; 3] {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        match self {
+            // classic blue -> cyan -> green -> yellow -> red
+            Colormap::Rainbow => [
+                (1.5 - (4.0 * t - 3.0).abs()).clamp(0.0, 1.0),
+                (1.5 - (4.0 * t - 2.0).abs()).clamp(0.0, 1.0),
+                (1.5 - (4.0 * t - 1.0).abs()).clamp(0.0, 1.0),
+            ],
+            Colormap::Viridis => lerp_table(
+                &[
+                    [0.267, 0.005, 0.329],
+                    [0.231, 0.322, 0.545],
+                    [0.129, 0.569, 0.549],
+                    [0.369, 0.788, 0.384],
+                    [0.993, 0.906, 0.144],
+                ],
+                t,
+            ),
+            Colormap::CoolWarm => lerp_table(
+                &[[0.230, 0.299, 0.754], [0.865, 0.865, 0.865], [0.706, 0.016, 0.150]],
+                t,
+            ),
+            Colormap::Grayscale => [t, t, t],
+        }
+    }
+}
+
+fn lerp_table(table: &[[f32; 3]], t: f32) -> [f32; 3] {
+    let x = t * (table.len() - 1) as f32;
+    let i = (x.floor() as usize).min(table.len() - 2);
+    let f = x - i as f32;
+    let (a, b) = (table[i], table[i + 1]);
+    [
+        a[0] + (b[0] - a[0]) * f,
+        a[1] + (b[1] - a[1]) * f,
+        a[2] + (b[2] - a[2]) * f,
+    ]
+}
@@ -0,0 +1,144 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenshotSettings {
+    pub scale: u32,        // times the window resolution
+    pub supersample: bool, // rendered at scale, saved at the window resolution
+    pub transparent: bool, // no background
+}
+
+impl Default for ScreenshotSettings {
+    fn default() -> Self {
+        Self { scale: 1, supersample: false, transparent: false }
+    }
+}
+
+// a rendered texture copied back to the cpu as tightly packed rgba rows
+pub fn read_texture(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Result<Vec<u8>, String> {
+    let (width, height) = (texture.width(), texture.height());
+    // rows of a texture copy are padded to 256 bytes
+    let row = width * 4;
+    let padded = row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Readback Encoder") });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    let data = slice.get_mapped_range();
+    let mut pixels = Vec::with_capacity((row * height) as usize);
+    for y in 0..height as usize {
+        let start = y * padded as usize;
+        pixels.extend_from_slice(&data[start..start + row as usize]);
+    }
+    drop(data);
+    buffer.unmap();
+    // the window surface is often bgra
+    if matches!(texture.format(), wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb) {
+        pixels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+    }
+    Ok(pixels)
+}
+
+pub fn save_png(filename: &str, width: u32, height: u32, rgba: &[u8]) -> Result<(), String> {
+    let file = File::create(filename).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(rgba).map_err(|e| e.to_string())
+}
+
+// each output pixel the average of a scale x scale block, weighted by alpha so
+// a transparent background does not darken the edges
+pub fn downsample(rgba: &[u8], size: [u32; 2], scale: u32) -> Vec<u8> {
+    let (w, h) = (size[0] / scale, size[1] / scale);
+    let mut out = Vec::with_capacity((w * h * 4) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let mut sum = [0u32; 4];
+            for dy in 0..scale {
+                for dx in 0..scale {
+                    let i = (((y * scale + dy) * size[0] + x * scale + dx) * 4) as usize;
+                    let a = rgba[i + 3] as u32;
+                    for k in 0..3 {
+                        sum[k] += rgba[i + k] as u32 * a;
+                    }
+                    sum[3] += a;
+                }
+            }
+            let n = scale * scale;
+            for k in 0..3 {
+                out.push(sum[k].checked_div(sum[3]).unwrap_or(0) as u8);
+            }
+            out.push((sum[3] / n) as u8);
+        }
+    }
+    out
+}
+
+// the hud blends premultiplied, png wants straight alpha
+pub fn unpremultiply(rgba: &mut [u8]) {
+    for p in rgba.chunks_exact_mut(4) {
+        let a = p[3] as u32;
+        if a > 0 && a < 255 {
+            for c in &mut p[..3] {
+                *c = (*c as u32 * 255 / a).min(255) as u8;
+            }
+        }
+    }
+}
+
+// utc, there is no timezone database to find local time in
+pub fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // days since 1970 to a civil date, from howard hinnant's date algorithms
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+    format!(
+        "{:04}{:02}{:02}_{:02}{:02}{:02}_{:03}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60,
+        now.subsec_millis()
+    )
+}
@@ -0,0 +1,286 @@
+use bytemuck::cast_slice;
+use serde_json::{json, Value};
+use std::f32::consts::TAU;
+
+use super::deform::{self, DeformSettings};
+use super::fields::{Location, VectorField};
+use super::surface_export::SurfaceMesh;
+use super::td_comp::TdObject;
+
+// keyframes over one back and forth of a mode shape
+const MODE_KEYS: usize = 24;
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+// the binary buffer with its views and accessors, filled as data is added
+#[derive(Default)]
+struct Buffer {
+    bin: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl Buffer {
+    fn view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        let mut view = json!({ "buffer": 0, "byteOffset": self.bin.len(), "byteLength": bytes.len() });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.bin.extend_from_slice(bytes);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    fn accessor(&mut self, accessor: Value) -> usize {
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    // positions need their bounds, so every vec3 gets them
+    fn vec3(&mut self, values: &[[f32; 3]]) -> usize {
+        let view = self.view(cast_slice(values), Some(ARRAY_BUFFER));
+        let bound = |f: fn(f32, f32) -> f32, start: f32| -> Vec<f32> {
+            (0..3).map(|k| values.iter().map(|v| v[k]).fold(start, f)).collect()
+        };
+        let (min, max) = (bound(f32::min, f32::INFINITY), bound(f32::max, f32::NEG_INFINITY));
+        self.accessor(json!({
+            "bufferView": view, "componentType": FLOAT, "count": values.len(), "type": "VEC3", "min": min, "max": max,
+        }))
+    }
+
+    fn scalars(&mut self, values: &[f32]) -> usize {
+        let view = self.view(cast_slice(values), None);
+        let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        self.accessor(json!({
+            "bufferView": view, "componentType": FLOAT, "count": values.len(), "type": "SCALAR", "min": [min], "max": [max],
+        }))
+    }
+
+    fn indices(&mut self, tris: &[[u32; 3]]) -> usize {
+        let view = self.view(cast_slice(tris), Some(ELEMENT_ARRAY_BUFFER));
+        self.accessor(json!({ "bufferView": view, "componentType": UNSIGNED_INT, "count": tris.len() * 3, "type": "SCALAR" }))
+    }
+}
+
+// displacement per morph target and the weight keyframes that play them
+#[derive(Default)]
+struct Morph {
+    names: Vec<String>,
+    targets: Vec<Vec<[f32; 3]>>,
+    weights: Vec<f32>, // at rest
+    times: Vec<f32>,   // seconds, empty when nothing plays
+    keys: Vec<f32>,    // one weight per target per time
+}
+
+fn largest(fields: &[&VectorField]) -> f32 {
+    fields.iter().flat_map(|f| f.magnitudes()).fold(0.0, f32::max)
+}
+
+// steps carrying the deformed field play one after another, a single field
+// swings like the viewer's animation
+fn morph(obj: &TdObject, mesh: &SurfaceMesh, settings: &DeformSettings) -> Morph {
+    let Some(field) = settings.field.and_then(|i| obj.vectors.get(i)).filter(|f| f.location == Location::Node) else {
+        return Morph::default();
+    };
+    let (_, radius) = obj.center_radius();
+    let on_surface = |f: &VectorField, k: f32| -> Vec<[f32; 3]> {
+        mesh.nodes.iter().map(|n| f.values[*n as usize].map(|c| c * k)).collect()
+    };
+    let steps: Vec<(&str, &VectorField)> = obj
+        .steps
+        .iter()
+        .filter_map(|s| {
+            let v = s.vectors.iter().find(|v| v.name == field.name && v.location == Location::Node)?;
+            Some((s.name.as_str(), v))
+        })
+        .collect();
+    let mut m = Morph::default();
+    if steps.len() > 1 {
+        // one scale for all steps so their sizes compare
+        let max = largest(&steps.iter().map(|(_, v)| *v).collect::<Vec<_>>());
+        let k = if max > 0.0 { settings.scale * radius / max } else { 0.0 };
+        for (i, (name, v)) in steps.iter().enumerate() {
+            m.names.push(name.to_string());
+            m.targets.push(on_surface(v, k));
+            m.times.push(i as f32 * deform::STEP_INTERVAL);
+            m.keys.extend((0..steps.len()).map(|j| if i == j { 1.0 } else { 0.0 }));
+        }
+        m.weights = vec![0.0; steps.len()];
+        m.weights[0] = 1.0;
+        return m;
+    }
+    let still = DeformSettings { animate: false, ..*settings };
+    m.names.push(field.name.clone());
+    m.targets.push(on_surface(field, still.factor(field, radius, 0.0)));
+    m.weights.push(1.0);
+    if settings.animate {
+        for i in 0..=MODE_KEYS {
+            let t = i as f32 * deform::PERIOD / MODE_KEYS as f32;
+            m.times.push(t);
+            m.keys.push((t * TAU / deform::PERIOD).sin());
+        }
+        m.weights[0] = 0.0;
+    }
+    m
+}
+
+// the json document and its binary buffer, vertex colors are the drawn ones
+fn document(obj: &TdObject, colors: bool, deform: Option<&DeformSettings>) -> (Value, Vec<u8>) {
+    let mesh = SurfaceMesh::new(obj, false);
+    let mut buffer = Buffer::default();
+    let indices = buffer.indices(&mesh.tris);
+    let mut attributes = json!({ "POSITION": buffer.vec3(&mesh.positions) });
+    if colors {
+        attributes["COLOR_0"] = json!(buffer.vec3(&mesh.colors));
+    }
+    let morph = deform.map(|d| morph(obj, &mesh, d)).unwrap_or_default();
+    let targets: Vec<Value> = morph.targets.iter().map(|t| json!({ "POSITION": buffer.vec3(t) })).collect();
+
+    let mut primitive = json!({ "attributes": attributes, "indices": indices, "material": 0, "mode": 4 });
+    let mut gltf_mesh = json!({ "name": "surface", "primitives": [] });
+    if !targets.is_empty() {
+        primitive["targets"] = json!(targets);
+        gltf_mesh["weights"] = json!(morph.weights);
+        gltf_mesh["extras"] = json!({ "targetNames": morph.names });
+    }
+    gltf_mesh["primitives"] = json!([primitive]);
+
+    let mut doc = json!({
+        "asset": { "version": "2.0", "generator": "femjsonwinit" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0, "name": "model" }],
+        "meshes": [gltf_mesh],
+        "materials": [{
+            "pbrMetallicRoughness": { "baseColorFactor": [1.0, 1.0, 1.0, 1.0], "metallicFactor": 0.0, "roughnessFactor": 1.0 },
+            "doubleSided": true,
+        }],
+    });
+    if !morph.times.is_empty() {
+        let input = buffer.scalars(&morph.times);
+        let output = buffer.scalars(&morph.keys);
+        doc["animations"] = json!([{
+            "name": "deformation",
+            "samplers": [{ "input": input, "output": output, "interpolation": "LINEAR" }],
+            "channels": [{ "sampler": 0, "target": { "node": 0, "path": "weights" } }],
+        }]);
+    }
+    doc["accessors"] = json!(buffer.accessors);
+    doc["bufferViews"] = json!(buffer.views);
+    doc["buffers"] = json!([{ "byteLength": buffer.bin.len() }]);
+    (doc, buffer.bin)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [0, 1, 2].map(|i| chunk.get(i).cloned().unwrap_or(0) as u32);
+        let n = b[0] << 16 | b[1] << 8 | b[2];
+        for i in 0..4 {
+            out.push(if i <= chunk.len() { TABLE[(n >> (18 - 6 * i)) as usize & 63] as char } else { '=' });
+        }
+    }
+    out
+}
+
+// a .gltf with the buffer inlined as a data uri
+pub fn gltf(obj: &TdObject, colors: bool, deform: Option<&DeformSettings>) -> String {
+    let (mut doc, bin) = document(obj, colors, deform);
+    doc["buffers"][0]["uri"] = json!(format!("data:application/octet-stream;base64,{}", base64(&bin)));
+    doc.to_string()
+}
+
+// header, then the json and the binary chunks, each padded to 4 bytes
+pub fn glb(obj: &TdObject, colors: bool, deform: Option<&DeformSettings>) -> Vec<u8> {
+    let (doc, mut bin) = document(obj, colors, deform);
+    let mut text = doc.to_string().into_bytes();
+    text.resize(text.len().next_multiple_of(4), b' ');
+    bin.resize(bin.len().next_multiple_of(4), 0);
+    let length = 12 + 8 + text.len() + 8 + bin.len();
+    let mut out = Vec::with_capacity(length);
+    out.extend_from_slice(b"glTF");
+    out.extend_from_slice(&2u32.to_le_bytes());
+    out.extend_from_slice(&(length as u32).to_le_bytes());
+    for (chunk, kind) in [(&text, b"JSON"), (&bin, b"BIN\0")] {
+        out.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        out.extend_from_slice(kind);
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // one tet whose tip moves further in each of three steps
+    fn stepped() -> TdObject {
+        let lift = |z: f32| json!([[0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, z]]);
+        let step = |name: &str, z: f32| json!({ "name": name, "vectors": [{ "name": "Displacement", "values": lift(z) }] });
+        let doc = json!({
+            "vers": [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            "elems": [{ "kind": "tet4", "nodes": [0, 1, 2, 3] }],
+            "vectors": [{ "name": "Displacement", "values": lift(0.1) }],
+            "steps": [step("1", 0.1), step("2", 0.2), step("3", 0.3)],
+        });
+        TdObject::from_json(&doc.to_string())
+    }
+
+    #[test]
+    fn base64_rfc4648_vectors() {
+        let cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (text, encoded) in cases {
+            assert_eq!(base64(text.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn glb_header_and_padded_chunks() {
+        let glb = glb(&stepped(), true, None);
+        let word = |at: usize| u32::from_le_bytes(glb[at..at + 4].try_into().unwrap()) as usize;
+        assert_eq!(&glb[..4], b"glTF");
+        assert_eq!(word(4), 2);
+        assert_eq!(word(8), glb.len());
+        let json_length = word(12);
+        assert_eq!(&glb[16..20], b"JSON");
+        assert_eq!(json_length % 4, 0);
+        let bin_at = 20 + json_length;
+        let bin_length = word(bin_at);
+        assert_eq!(&glb[bin_at + 4..bin_at + 8], b"BIN\0");
+        assert_eq!(bin_length % 4, 0);
+        assert_eq!(bin_at + 8 + bin_length, glb.len());
+        let doc: Value = serde_json::from_slice(&glb[20..bin_at]).unwrap();
+        assert!(doc["buffers"][0]["byteLength"].as_u64().unwrap() as usize <= bin_length);
+    }
+
+    #[test]
+    fn steps_key_every_target_at_every_time() {
+        let obj = stepped();
+        let mesh = SurfaceMesh::new(&obj, false);
+        let m = morph(&obj, &mesh, &DeformSettings { field: Some(0), ..Default::default() });
+        assert_eq!(m.names, vec!["1", "2", "3"]);
+        assert_eq!(m.targets.len(), 3);
+        assert_eq!(m.times.len(), 3);
+        assert_eq!(m.keys.len(), m.times.len() * m.targets.len());
+        // each time shows its own step alone
+        for (i, keys) in m.keys.chunks(3).enumerate() {
+            assert_eq!(keys.iter().sum::<f32>(), 1.0);
+            assert_eq!(keys[i], 1.0);
+        }
+        assert_eq!(m.weights, vec![1.0, 0.0, 0.0]);
+    }
+}
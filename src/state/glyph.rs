@@ -0,0 +1,148 @@
+use bytemuck::{Pod, Zeroable};
+use std::f32::consts::TAU;
+
+use super::fields::{Colormap, Location, VectorField};
+use super::td_comp::TdObject;
+
+const ARROW_SEGMENTS: usize = 12;
+const SHAFT_RADIUS: f32 = 0.03;
+const HEAD_RADIUS: f32 = 0.08;
+const HEAD_START: f32 = 0.7;
+// longest arrow at scale 1, relative to the model radius
+const BASE_LENGTH: f32 = 0.15;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphSettings {
+    pub field: Option<usize>,
+    pub scale: f32,
+    pub uniform_length: bool,
+}
+
+impl Default for GlyphSettings {
+    fn default() -> Self {
+        Self {
+            field: None,
+            scale: 1.0,
+            uniform_length: false,
+        }
+    }
+}
+
+// unit arrow along +z, from 0 to 1
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct GlyphVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
+impl GlyphVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0=>Float32x3, 1=>Float32x3];
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GlyphVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// one arrow: origin.w is its length, dir does not need to be normalized
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct GlyphInstance {
+    origin: [f32; 4],
+    dir: [f32; 4],
+    color: [f32; 4],
+}
+
+impl GlyphInstance {
+    pub fn new(origin: [f32; 3], length: f32, dir: [f32; 3], color: [f32; 3]) -> Self {
+        Self {
+            origin: [origin[0], origin[1], origin[2], length],
+            dir: [dir[0], dir[1], dir[2], 0.0],
+            color: [color[0], color[1], color[2], 1.0],
+        }
+    }
+
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![2=>Float32x4, 3=>Float32x4, 4=>Float32x4];
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GlyphInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// shaft cylinder, head cone and the two caps, as a triangle list
+pub fn arrow_mesh() -> (Vec<GlyphVertex>, Vec<u16>) {
+    let mut verts = Vec::new();
+    let mut inds: Vec<u16> = Vec::new();
+    let ring = |r: f32, i: usize| {
+        let (s, c) = (i as f32 / ARROW_SEGMENTS as f32 * TAU).sin_cos();
+        ([r * c, r * s], [c, s])
+    };
+    // cone normals lean forward by the slope of the head
+    let slope = HEAD_RADIUS / (1.0 - HEAD_START);
+    for i in 0..ARROW_SEGMENTS {
+        let (p, n) = ring(SHAFT_RADIUS, i);
+        let (h, _) = ring(HEAD_RADIUS, i);
+        let side = [n[0], n[1], 0.0];
+        let cone = [n[0], n[1], slope];
+        verts.extend([
+            GlyphVertex { position: [p[0], p[1], 0.0], normal: side },
+            GlyphVertex { position: [p[0], p[1], HEAD_START], normal: side },
+            GlyphVertex { position: [h[0], h[1], HEAD_START], normal: cone },
+            GlyphVertex { position: [0.0, 0.0, 1.0], normal: cone },
+            GlyphVertex { position: [h[0], h[1], HEAD_START], normal: [0.0, 0.0, -1.0] },
+            GlyphVertex { position: [p[0], p[1], 0.0], normal: [0.0, 0.0, -1.0] },
+        ]);
+    }
+    let base = verts.len() as u16;
+    verts.push(GlyphVertex { position: [0.0, 0.0, HEAD_START], normal: [0.0, 0.0, -1.0] });
+    verts.push(GlyphVertex { position: [0.0, 0.0, 0.0], normal: [0.0, 0.0, -1.0] });
+
+    let stride = 6;
+    for i in 0..ARROW_SEGMENTS {
+        let a = (i * stride) as u16;
+        let b = (((i + 1) % ARROW_SEGMENTS) * stride) as u16;
+        // shaft
+        inds.extend([a, b, a + 1, b, b + 1, a + 1]);
+        // head
+        inds.extend([a + 2, b + 2, a + 3]);
+        // back of the head and bottom of the shaft
+        inds.extend([base, b + 4, a + 4]);
+        inds.extend([base + 1, b + 5, a + 5]);
+    }
+    (verts, inds)
+}
+
+// one instance per node or element, colored and sized by magnitude
+pub fn instances(obj: &TdObject, field: &VectorField, settings: &GlyphSettings, cmap: Colormap) -> Vec<GlyphInstance> {
+    let mags = field.magnitudes();
+    let max = mags.iter().cloned().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return Vec::new();
+    }
+    let (_, radius) = obj.center_radius();
+    let length = BASE_LENGTH * radius * settings.scale;
+
+    let origins: Vec<[f32; 3]> = match field.location {
+        Location::Node => obj.display_positions(),
+        Location::Element => obj.elements.iter().map(|e| obj.display_centroid(e)).collect(),
+    };
+    origins
+        .iter()
+        .zip(&field.values)
+        .zip(&mags)
+        .filter(|(_, m)| **m > 0.0)
+        .map(|((o, v), m)| {
+            let l = if settings.uniform_length { length } else { length * m / max };
+            let c = cmap.map(*m, 0.0, max);
+            GlyphInstance::new(*o, l, *v, c)
+        })
+        .collect()
+}
@@ -0,0 +1,823 @@
+use std::time::Duration;
+
+use winit::{event::WindowEvent, window::Window};
+
+use super::bc::BcSettings;
+use super::camera::{FpsCamera, FpsController, Projection};
+use super::deform::DeformSettings;
+use super::elements::ElementKind;
+use super::export::{ExportSettings, Format};
+use super::fields::{CellMode, ColorBy, Colormap, Layer, Location};
+use super::frame::ScreenshotSettings;
+use super::glyph::GlyphSettings;
+use super::iso::IsoSettings;
+use super::measure::{MeasureSettings, Measurement, Tool};
+use super::overlay;
+use super::probe::{Probe, ProbeSettings};
+use super::profile::ProfileSettings;
+use super::quality::{self, Metric, QualitySettings};
+use super::shell::{self, ShellSettings};
+use super::section::{self, ClipPlane, MAX_CLIP_PLANES};
+use super::solver::{Analysis, ANALYSES};
+use super::surface_export::{MeshFormat, SurfaceExportSettings};
+use super::tensor::Quantity;
+use super::topology::TopologySettings;
+use super::td_comp::TdObject;
+
+// what the user can switch from the panel
+#[derive(Clone, PartialEq)]
+pub struct ViewSettings {
+    pub field: Option<usize>,
+    pub color_by: ColorBy,
+    pub colormap: Colormap,
+    pub cell_mode: CellMode,
+    pub average_groups: bool,
+    pub show_lines: bool,
+    pub show_nodes: bool,
+    pub show_surface: bool,
+    pub show_edges: bool,
+    pub show_legend: bool,
+    pub show_triad: bool,
+    pub planes: [ClipPlane; MAX_CLIP_PLANES],
+    pub cap: bool,
+    pub iso: IsoSettings,
+    pub glyphs: GlyphSettings,
+    pub tensor: usize,
+    pub quantity: Quantity,
+    pub analysis: Analysis,
+    pub modes: usize,
+    pub lumped: bool,
+    pub step: Option<usize>,
+    pub play_steps: bool,
+    pub deform: DeformSettings,
+    pub bcs: BcSettings,
+    pub profiles: ProfileSettings,
+    pub shells: ShellSettings,
+    pub layer: Layer, // surface of the shell results shown
+    pub quality: QualitySettings,
+    pub topology: TopologySettings,
+    pub measure: MeasureSettings,
+    pub probe: ProbeSettings,
+    pub export: ExportSettings,
+    pub surface_export: SurfaceExportSettings,
+    pub screenshot: ScreenshotSettings,
+    pub msaa: u32, // samples per pixel
+    pub smooth_lines: bool,
+}
+
+impl Default for ViewSettings {
+    fn default() -> Self {
+        Self {
+            field: None,
+            color_by: ColorBy::Field,
+            colormap: Colormap::Rainbow,
+            cell_mode: CellMode::Flat,
+            average_groups: true,
+            show_lines: true,
+            show_nodes: false,
+            show_surface: true,
+            show_edges: true,
+            show_legend: true,
+            show_triad: true,
+            planes: section::default_planes(),
+            cap: true,
+            iso: IsoSettings::default(),
+            glyphs: GlyphSettings::default(),
+            tensor: 0,
+            quantity: Quantity::VonMises,
+            analysis: Analysis::Truss,
+            modes: 6,
+            lumped: false,
+            step: None,
+            play_steps: false,
+            deform: DeformSettings::default(),
+            bcs: BcSettings::default(),
+            profiles: ProfileSettings::default(),
+            shells: ShellSettings::default(),
+            layer: Layer::default(),
+            quality: QualitySettings::default(),
+            topology: TopologySettings::default(),
+            measure: MeasureSettings::default(),
+            probe: ProbeSettings::default(),
+            export: ExportSettings::default(),
+            surface_export: SurfaceExportSettings::default(),
+            screenshot: ScreenshotSettings::default(),
+            msaa: 1,
+            smooth_lines: false,
+        }
+    }
+}
+
+// one-shot requests from the panel, handled by State after the frame
+pub enum HudAction {
+    ExportIso,
+    DeriveField,      // view.quantity of view.tensor as the displayed field
+    DeriveDirections, // principal directions of view.tensor as glyphs
+    Solve,            // view.analysis on the model
+    Modes,            // view.modes natural modes of view.analysis
+    Quality,          // view.quality.metric of every element as the displayed field
+    Diagnose,         // topology checks with view.topology, reported in the status
+    MergeNodes,       // coincident nodes joined into one
+    ClearPicks,       // measurement started over
+    ProbeFromPicks,   // probe line between the last two picked nodes
+    ExportProbe,      // probe samples to csv
+    ExportResults,    // fields at view.export.location to a table file
+    ExportSurface,    // outer surface to stl, obj, ply, gltf or glb
+    Screenshot,       // the next frame to a png, as F12 does
+}
+
+// everything the panel reads from State for one frame
+pub struct HudInfo<'a> {
+    pub obj: &'a TdObject,
+    pub camera: &'a FpsCamera,
+    pub projection: &'a Projection,
+    pub controller: &'a FpsController,
+    pub frame_time: Duration,
+    pub status: &'a str, // last solver / export message
+    pub measurement: Option<&'a Measurement>,
+    pub probe: Option<&'a Probe>,
+    pub sample_counts: &'a [u32], // msaa choices the adapter has
+}
+
+pub struct Hud {
+    ctx: egui::Context,
+    winit_state: Option<egui_winit::State>, // none offscreen, where there is no input
+    screen: [u32; 2],                       // offscreen size in pixels
+    frames: u32,                            // offscreen frames so far, egui's clock
+    renderer: egui_wgpu::Renderer,
+    paint_jobs: Vec<egui::ClippedPrimitive>,
+    textures_delta: egui::TexturesDelta,
+    pixels_per_point: f32,
+    pub visible: bool,
+}
+
+impl Hud {
+    pub fn new(window: &Window, device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let ctx = egui::Context::default();
+        let winit_state = egui_winit::State::new(
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+        let renderer = egui_wgpu::Renderer::new(device, format, None, 1);
+        Self {
+            ctx,
+            winit_state: Some(winit_state),
+            screen: [0, 0],
+            frames: 0,
+            renderer,
+            paint_jobs: Vec::new(),
+            textures_delta: egui::TexturesDelta::default(),
+            pixels_per_point: window.scale_factor() as f32,
+            visible: true,
+        }
+    }
+
+    // only the overlays, drawn on frames rendered without a window
+    pub fn headless(device: &wgpu::Device, format: wgpu::TextureFormat, size: [u32; 2]) -> Self {
+        Self {
+            ctx: egui::Context::default(),
+            winit_state: None,
+            screen: size,
+            frames: 0,
+            renderer: egui_wgpu::Renderer::new(device, format, None, 1),
+            paint_jobs: Vec::new(),
+            textures_delta: egui::TexturesDelta::default(),
+            pixels_per_point: 1.0,
+            visible: false,
+        }
+    }
+
+    // true when egui used the event and the camera should not see it
+    pub fn on_window_event(&mut self, event: &WindowEvent) -> bool {
+        let Some(winit_state) = self.winit_state.as_mut() else {
+            return false;
+        };
+        let response = winit_state.on_window_event(&self.ctx, event);
+        self.visible && response.consumed
+    }
+
+    pub fn wants_pointer(&self) -> bool {
+        self.visible && self.ctx.wants_pointer_input()
+    }
+
+    // runs the ui for this frame, the output is painted by `paint`
+    pub fn run(&mut self, window: Option<&Window>, info: HudInfo, view: &mut ViewSettings) -> Vec<HudAction> {
+        let raw_input = match (window, self.winit_state.as_mut()) {
+            (Some(window), Some(winit_state)) => winit_state.take_egui_input(window),
+            // a fixed frame rate so offscreen frames come out the same every time
+            _ => {
+                self.frames += 1;
+                egui::RawInput {
+                    screen_rect: Some(egui::Rect::from_min_size(
+                        egui::Pos2::ZERO,
+                        egui::vec2(self.screen[0] as f32, self.screen[1] as f32),
+                    )),
+                    time: Some(self.frames as f64 / 60.0),
+                    predicted_dt: 1.0 / 60.0,
+                    ..Default::default()
+                }
+            }
+        };
+        let visible = self.visible;
+        let mut actions = Vec::new();
+        let output = self.ctx.run(raw_input, |ctx| {
+            if visible {
+                panel(ctx, &info, view, &mut actions);
+            }
+            // the overlays stay when the panel is hidden
+            if view.show_legend {
+                if view.color_by != ColorBy::Field {
+                    overlay::categories(ctx, view.color_by.name(), &info.obj.categories(view.color_by));
+                } else if let Some(f) = view.field.map(|i| &info.obj.fields[i]) {
+                    overlay::legend(ctx, f, view.colormap);
+                }
+            }
+            if view.show_triad {
+                overlay::triad(ctx, info.camera);
+            }
+            if let Some(p) = info.probe {
+                egui::Window::new("Probe").default_width(360.0).show(ctx, |ui| {
+                    let units = if p.units.is_empty() { String::new() } else { format!(" [{}]", p.units) };
+                    overlay::plot(ui, &p.distance, &p.values, "distance", &format!("{}{}", p.field, units));
+                });
+            }
+            if let Some(m) = info.measurement {
+                let view_proj = info.projection.calc_matrix() * info.camera.calc_matrix();
+                overlay::annotations(ctx, view_proj, &m.labels);
+            }
+        });
+        if let (Some(window), Some(winit_state)) = (window, self.winit_state.as_mut()) {
+            winit_state.handle_platform_output(window, &self.ctx, output.platform_output);
+        }
+        self.pixels_per_point = output.pixels_per_point;
+        self.paint_jobs = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        self.textures_delta.append(output.textures_delta);
+        actions
+    }
+
+    // draws on top of whatever is already in `view`
+    pub fn paint(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size: [u32; 2],
+        scale: f32, // of size over the window, for larger screenshots
+    ) {
+        let screen = egui_wgpu::renderer::ScreenDescriptor {
+            size_in_pixels: size,
+            pixels_per_point: self.pixels_per_point * scale,
+        };
+        let delta = std::mem::take(&mut self.textures_delta);
+        for (id, image) in &delta.set {
+            self.renderer.update_texture(device, queue, *id, image);
+        }
+        self.renderer
+            .update_buffers(device, queue, encoder, &self.paint_jobs, &screen);
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Hud Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.renderer
+                .render(&mut render_pass, &self.paint_jobs, &screen);
+        }
+        for id in &delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
+
+fn panel(ctx: &egui::Context, info: &HudInfo, view: &mut ViewSettings, actions: &mut Vec<HudAction>) {
+    egui::Window::new("Model")
+        .default_pos([10.0, 10.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            let obj = info.obj;
+            let (lo, hi) = obj.bounds();
+            egui::Grid::new("model_stats").num_columns(2).show(ui, |ui| {
+                ui.label("Nodes");
+                ui.label(obj.vertices.len().to_string());
+                ui.end_row();
+                ui.label("Elements");
+                ui.label(obj.element_count().to_string());
+                ui.end_row();
+                ui.label("Min");
+                ui.label(format!("{:.3} {:.3} {:.3}", lo[0], lo[1], lo[2]));
+                ui.end_row();
+                ui.label("Max");
+                ui.label(format!("{:.3} {:.3} {:.3}", hi[0], hi[1], hi[2]));
+                ui.end_row();
+            });
+
+            if obj.elements.iter().any(|e| e.kind == ElementKind::Line2) {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut view.profiles.show, "Beam profiles");
+                    ui.add(egui::Slider::new(&mut view.profiles.scale, 0.1..=20.0).logarithmic(true).text("size"));
+                });
+            }
+            if shell::thicknesses(obj).iter().any(|t| t.is_some()) {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut view.shells.extrude, "Shell thickness");
+                    ui.add(egui::Slider::new(&mut view.shells.scale, 0.1..=20.0).logarithmic(true).text("scale"));
+                });
+            }
+            if !obj.model.materials.is_empty() || !obj.model.sections.is_empty() {
+                ui.collapsing("Properties", |ui| properties_panel(ui, obj));
+            }
+
+            ui.separator();
+            if !obj.elements.is_empty() {
+                egui::ComboBox::from_label("Color by")
+                    .selected_text(view.color_by.name())
+                    .show_ui(ui, |ui| {
+                        for c in ColorBy::ALL {
+                            ui.selectable_value(&mut view.color_by, c, c.name());
+                        }
+                    });
+            }
+            let field_name = |i: Option<usize>| match i {
+                Some(i) => obj.fields[i].name.clone(),
+                None => "None".to_string(),
+            };
+            egui::ComboBox::from_label("Field")
+                .selected_text(field_name(view.field))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut view.field, None, "None");
+                    for (i, f) in obj.fields.iter().enumerate() {
+                        if f.layer.is_none() || f.layer == Some(view.layer) {
+                            ui.selectable_value(&mut view.field, Some(i), field_name(Some(i)));
+                        }
+                    }
+                });
+            if obj.fields.iter().any(|f| f.layer.is_some()) {
+                ui.horizontal(|ui| {
+                    ui.label("Shell results");
+                    for l in Layer::ALL {
+                        ui.radio_value(&mut view.layer, l, l.name());
+                    }
+                });
+            }
+            egui::ComboBox::from_label("Colormap")
+                .selected_text(view.colormap.name())
+                .show_ui(ui, |ui| {
+                    for c in Colormap::ALL {
+                        ui.selectable_value(&mut view.colormap, c, c.name());
+                    }
+                });
+            if let Some(f) = view.field.map(|i| &obj.fields[i]) {
+                let (lo, hi) = f.range();
+                ui.label(format!("Range {:.4} .. {:.4} {}", lo, hi, f.units));
+                if f.location == Location::Element {
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut view.cell_mode, CellMode::Flat, "Flat");
+                        ui.radio_value(&mut view.cell_mode, CellMode::Averaged, "Averaged");
+                    });
+                    if view.cell_mode == CellMode::Averaged {
+                        ui.checkbox(&mut view.average_groups, "Average across groups");
+                    }
+                }
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut view.show_surface, "Surface");
+                ui.checkbox(&mut view.show_edges, "Edges");
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut view.show_lines, "Lines");
+                ui.checkbox(&mut view.show_nodes, "Nodes");
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut view.show_legend, "Legend");
+                ui.checkbox(&mut view.show_triad, "Axes");
+            });
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Anti-aliasing")
+                    .selected_text(format!("{}x", view.msaa))
+                    .show_ui(ui, |ui| {
+                        for n in info.sample_counts {
+                            ui.selectable_value(&mut view.msaa, *n, format!("{}x", n));
+                        }
+                    });
+                ui.checkbox(&mut view.smooth_lines, "Smooth lines");
+            });
+
+            ui.collapsing("Section planes", |ui| {
+                ui.checkbox(&mut view.cap, "Cap cut faces");
+                for (i, plane) in view.planes.iter_mut().enumerate() {
+                    ui.checkbox(&mut plane.enabled, format!("Plane {}", i + 1));
+                    if plane.enabled {
+                        ui.add(egui::Slider::new(&mut plane.offset, -1.0..=1.0).text("offset"));
+                        ui.add(egui::Slider::new(&mut plane.azimuth, -180.0..=180.0).text("azimuth"));
+                        ui.add(egui::Slider::new(&mut plane.elevation, -90.0..=90.0).text("elevation"));
+                    }
+                }
+            });
+
+            if !obj.elements.is_empty() {
+                ui.collapsing("Mesh quality", |ui| quality_panel(ui, obj, view, actions));
+                ui.collapsing("Topology", |ui| {
+                    let t = &mut view.topology;
+                    ui.add(egui::Slider::new(&mut t.tolerance, 1e-9..=1e-2).logarithmic(true).text("tolerance"));
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut t.highlight, "Highlight");
+                        ui.checkbox(&mut t.regions, "Color regions");
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Check").clicked() {
+                            actions.push(HudAction::Diagnose);
+                        }
+                        if ui.button("Merge coincident").clicked() {
+                            actions.push(HudAction::MergeNodes);
+                        }
+                    });
+                });
+            }
+            ui.collapsing("Measure", |ui| {
+                let m = &mut view.measure;
+                egui::ComboBox::from_label("Tool")
+                    .selected_text(m.tool.map_or("None", |t| t.name()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut m.tool, None, "None");
+                        for t in Tool::ALL {
+                            ui.selectable_value(&mut m.tool, Some(t), t.name());
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut m.deformed, "Deformed");
+                    if ui.button("Clear").clicked() {
+                        actions.push(HudAction::ClearPicks);
+                    }
+                });
+                if m.tool.is_some() {
+                    ui.label(if m.tool == Some(Tool::Area) { "click a face" } else { "click nodes" });
+                }
+                if let Some(measurement) = info.measurement {
+                    ui.label(&measurement.summary);
+                }
+            });
+            ui.collapsing("Probe", |ui| {
+                let p = &mut view.probe;
+                ui.checkbox(&mut p.show, "Show");
+                for (label, point) in [("from", &mut p.from), ("to", &mut p.to)] {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        for v in point.iter_mut() {
+                            ui.add(egui::DragValue::new(v).speed(0.01));
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.label("samples");
+                    ui.add(egui::DragValue::new(&mut p.samples).clamp_range(2..=2000));
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("From picks").clicked() {
+                        actions.push(HudAction::ProbeFromPicks);
+                    }
+                    if ui.add_enabled(info.probe.is_some(), egui::Button::new("Export CSV")).clicked() {
+                        actions.push(HudAction::ExportProbe);
+                    }
+                });
+            });
+            ui.collapsing("Export results", |ui| export_panel(ui, obj, view, actions));
+            if !obj.surface.is_empty() {
+                ui.collapsing("Export surface", |ui| {
+                    let e = &mut view.surface_export;
+                    egui::ComboBox::from_label("Format")
+                        .selected_text(e.format.name())
+                        .show_ui(ui, |ui| {
+                            for f in MeshFormat::ALL {
+                                ui.selectable_value(&mut e.format, f, f.name());
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut e.deformed, "Deformed");
+                        ui.add_enabled(e.format.has_colors(), egui::Checkbox::new(&mut e.colors, "Colors"));
+                        if ui.button("Export").clicked() {
+                            actions.push(HudAction::ExportSurface);
+                        }
+                    });
+                });
+            }
+            ui.collapsing("Screenshot", |ui| {
+                let shot = &mut view.screenshot;
+                ui.add(egui::Slider::new(&mut shot.scale, 1..=8).text("x window size"));
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut shot.supersample, "Supersample");
+                    ui.checkbox(&mut shot.transparent, "Transparent");
+                });
+                if ui.button("Save (F12)").clicked() {
+                    actions.push(HudAction::Screenshot);
+                }
+            });
+            if !obj.model.is_empty() {
+                ui.collapsing("Boundary conditions", |ui| {
+                    let model = &obj.model;
+                    ui.checkbox(&mut view.bcs.show, "Show");
+                    ui.add(egui::Slider::new(&mut view.bcs.scale, 0.1..=10.0).logarithmic(true).text("scale"));
+                    ui.label(format!(
+                        "{} supports, {} loads, {} pressures",
+                        model.supports.len(),
+                        model.loads.len(),
+                        model.pressures.len()
+                    ));
+                    if !model.temperatures.is_empty() || !model.fluxes.is_empty() || !model.convections.is_empty() {
+                        ui.label(format!(
+                            "{} temperatures, {} fluxes, {} convections",
+                            model.temperatures.len(),
+                            model.fluxes.len(),
+                            model.convections.len()
+                        ));
+                    }
+                });
+                ui.collapsing("Analysis", |ui| {
+                    egui::ComboBox::from_label("type")
+                        .selected_text(view.analysis.name())
+                        .show_ui(ui, |ui| {
+                            for a in ANALYSES {
+                                ui.selectable_value(&mut view.analysis, a, a.name());
+                            }
+                        });
+                    if ui.button("Solve").clicked() {
+                        actions.push(HudAction::Solve);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut view.modes).clamp_range(1..=50));
+                        ui.checkbox(&mut view.lumped, "Lumped mass");
+                        if ui.button("Modes").clicked() {
+                            actions.push(HudAction::Modes);
+                        }
+                    });
+                });
+            }
+            if !obj.steps.is_empty() {
+                ui.collapsing("Steps", |ui| {
+                    steps_panel(ui, obj, view);
+                });
+            }
+            if !obj.tensors.is_empty() {
+                ui.collapsing("Derived quantities", |ui| {
+                    tensor_panel(ui, obj, view, actions);
+                });
+            }
+            ui.collapsing("Iso-surfaces", |ui| {
+                iso_panel(ui, obj, &mut view.iso, actions);
+            });
+            ui.collapsing("Vector glyphs", |ui| {
+                let glyphs = &mut view.glyphs;
+                egui::ComboBox::from_label("Vector field")
+                    .selected_text(glyphs.field.map_or("None", |i| obj.vectors[i].name.as_str()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut glyphs.field, None, "None");
+                        for (i, f) in obj.vectors.iter().enumerate() {
+                            ui.selectable_value(&mut glyphs.field, Some(i), f.name.as_str());
+                        }
+                    });
+                ui.add(egui::Slider::new(&mut glyphs.scale, 0.05..=20.0).logarithmic(true).text("scale"));
+                ui.checkbox(&mut glyphs.uniform_length, "Same length");
+            });
+            ui.collapsing("Deformation", |ui| {
+                let deform = &mut view.deform;
+                egui::ComboBox::from_label("Displacement")
+                    .selected_text(deform.field.map_or("None", |i| obj.vectors[i].name.as_str()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut deform.field, None, "None");
+                        for (i, f) in obj.vectors.iter().enumerate() {
+                            if f.location == Location::Node {
+                                ui.selectable_value(&mut deform.field, Some(i), f.name.as_str());
+                            }
+                        }
+                    });
+                ui.add(egui::Slider::new(&mut deform.scale, 0.0..=0.5).text("of model size"));
+                ui.checkbox(&mut deform.animate, "Animate");
+            });
+
+            ui.separator();
+            let cam = info.camera;
+            ui.label(format!(
+                "Camera ({:.2}, {:.2}, {:.2})",
+                cam.pos.x, cam.pos.y, cam.pos.z
+            ));
+            ui.label(format!(
+                "Yaw {:.1}°  Pitch {:.1}°  Fov {:.0}°",
+                cgmath::Deg::from(cam.yaw).0,
+                cgmath::Deg::from(cam.pitch).0,
+                cgmath::Deg::from(info.projection.fov).0,
+            ));
+            ui.label(format!("Speed {}", info.controller.speed));
+
+            let ms = info.frame_time.as_secs_f32() * 1000.0;
+            let fps = if ms > 0.0 { 1000.0 / ms } else { 0.0 };
+            ui.label(format!("Frame {:.2} ms ({:.0} fps)", ms, fps));
+            if !info.status.is_empty() {
+                ui.label(info.status);
+            }
+            ui.small("F1 hides this panel");
+        });
+}
+
+fn iso_panel(ui: &mut egui::Ui, obj: &TdObject, iso: &mut IsoSettings, actions: &mut Vec<HudAction>) {
+    let old_field = iso.field;
+    egui::ComboBox::from_label("Iso field")
+        .selected_text(iso.field.map_or("None", |i| obj.fields[i].name.as_str()))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut iso.field, None, "None");
+            for (i, f) in obj.fields.iter().enumerate() {
+                ui.selectable_value(&mut iso.field, Some(i), f.name.as_str());
+            }
+        });
+    let Some(field) = iso.field.map(|i| &obj.fields[i]) else {
+        return;
+    };
+    let (lo, hi) = field.range();
+    if iso.field != old_field {
+        iso.levels = vec![(lo + hi) / 2.0];
+    }
+
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut iso.surfaces, "Surfaces");
+        ui.checkbox(&mut iso.lines, "Lines");
+    });
+    let mut remove = None;
+    for (i, level) in iso.levels.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.add(egui::Slider::new(level, lo..=hi).text(&field.units));
+            if ui.small_button("x").clicked() {
+                remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = remove {
+        iso.levels.remove(i);
+    }
+    ui.horizontal(|ui| {
+        if ui.button("Add level").clicked() {
+            iso.levels.push((lo + hi) / 2.0);
+        }
+        if ui.button("Export surface").clicked() {
+            actions.push(HudAction::ExportIso);
+        }
+    });
+}
+
+fn properties_panel(ui: &mut egui::Ui, obj: &TdObject) {
+    let model = &obj.model;
+    egui::Grid::new("materials").num_columns(6).striped(true).show(ui, |ui| {
+        for h in ["Material", "E", "nu", "density", "conductivity", "expansion"] {
+            ui.strong(h);
+        }
+        ui.end_row();
+        for m in &model.materials {
+            ui.label(&m.name);
+            for v in [m.e, m.nu, m.density, m.conductivity, m.expansion] {
+                ui.label(format!("{:.4e}", v));
+            }
+            ui.end_row();
+        }
+    });
+    ui.separator();
+    egui::Grid::new("sections").num_columns(6).striped(true).show(ui, |ui| {
+        for h in ["Section", "area", "iy", "iz", "j", "thickness"] {
+            ui.strong(h);
+        }
+        ui.end_row();
+        for s in &model.sections {
+            ui.label(&s.name);
+            for v in [s.area, s.iy, s.iz, s.j, s.thickness] {
+                ui.label(format!("{:.4e}", v));
+            }
+            ui.end_row();
+        }
+    });
+    if !model.groups.is_empty() {
+        ui.separator();
+        let mut groups: Vec<_> = model.groups.iter().collect();
+        groups.sort_by_key(|(name, _)| name.as_str());
+        for (name, g) in groups {
+            ui.label(format!("{}: {} / {}", name, g.material, g.section));
+        }
+    }
+}
+
+fn steps_panel(ui: &mut egui::Ui, obj: &TdObject, view: &mut ViewSettings) {
+    let label = |i: usize| format!("{}  ({})", obj.steps[i].name, obj.steps[i].value);
+    egui::ComboBox::from_label("Step")
+        .selected_text(view.step.map_or("None".to_string(), label))
+        .show_ui(ui, |ui| {
+            for i in 0..obj.steps.len() {
+                ui.selectable_value(&mut view.step, Some(i), label(i));
+            }
+        });
+    ui.horizontal(|ui| {
+        let last = obj.steps.len() - 1;
+        if ui.button("<").clicked() {
+            view.step = Some(view.step.map_or(0, |i| if i == 0 { last } else { i - 1 }));
+        }
+        if ui.button(">").clicked() {
+            view.step = Some(view.step.map_or(0, |i| if i == last { 0 } else { i + 1 }));
+        }
+        ui.checkbox(&mut view.play_steps, "Play");
+    });
+}
+
+fn export_panel(ui: &mut egui::Ui, obj: &TdObject, view: &mut ViewSettings, actions: &mut Vec<HudAction>) {
+    let e = &mut view.export;
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut e.location, Location::Node, "Nodes");
+        ui.radio_value(&mut e.location, Location::Element, "Elements");
+    });
+    // node sets for nodes, groups for elements
+    let mut sets: Vec<&str> = match e.location {
+        Location::Node => obj.model.node_sets.keys().map(String::as_str).collect(),
+        Location::Element => obj.categories(ColorBy::Group).into_iter().filter(|g| !g.is_empty()).collect(),
+    };
+    sets.sort();
+    if !sets.contains(&e.set.as_str()) {
+        e.set.clear();
+    }
+    egui::ComboBox::from_label("Set")
+        .selected_text(if e.set.is_empty() { "All" } else { e.set.as_str() })
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut e.set, String::new(), "All");
+            for s in sets {
+                ui.selectable_value(&mut e.set, s.to_string(), s);
+            }
+        });
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut e.format, Format::Csv, "CSV");
+        ui.radio_value(&mut e.format, Format::Json, "JSON");
+        if ui.button("Export").clicked() {
+            actions.push(HudAction::ExportResults);
+        }
+    });
+}
+
+fn quality_panel(ui: &mut egui::Ui, obj: &TdObject, view: &mut ViewSettings, actions: &mut Vec<HudAction>) {
+    let settings = &mut view.quality;
+    let before = settings.metric;
+    egui::ComboBox::from_label("Metric")
+        .selected_text(settings.metric.name())
+        .show_ui(ui, |ui| {
+            for m in Metric::ALL {
+                ui.selectable_value(&mut settings.metric, m, m.name());
+            }
+        });
+    if settings.metric != before {
+        settings.threshold = settings.metric.default_threshold();
+    }
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut settings.highlight, format!("Highlight {}", settings.metric.bad_side()));
+        ui.add(egui::DragValue::new(&mut settings.threshold).speed(0.01));
+    });
+    if ui.button("Show").clicked() {
+        actions.push(HudAction::Quality);
+    }
+    // counted on the field once it is shown, not every frame
+    let shown = view.field.map(|i| &obj.fields[i]).filter(|f| quality::metric_of(f) == Some(settings.metric));
+    if let Some(f) = shown {
+        let bad = quality::bad_count(&f.values, settings.metric, settings.threshold);
+        ui.label(format!("{} of {} elements {} {}", bad, f.values.len(), settings.metric.bad_side(), settings.threshold));
+    }
+}
+
+fn tensor_panel(ui: &mut egui::Ui, obj: &TdObject, view: &mut ViewSettings, actions: &mut Vec<HudAction>) {
+    egui::ComboBox::from_label("Tensor")
+        .selected_text(obj.tensors[view.tensor].name.as_str())
+        .show_ui(ui, |ui| {
+            for (i, t) in obj.tensors.iter().enumerate() {
+                ui.selectable_value(&mut view.tensor, i, t.name.as_str());
+            }
+        });
+    egui::ComboBox::from_label("Quantity")
+        .selected_text(view.quantity.name())
+        .show_ui(ui, |ui| {
+            for q in Quantity::ALL {
+                ui.selectable_value(&mut view.quantity, q, q.name());
+            }
+        });
+    ui.horizontal(|ui| {
+        if ui.button("Show").clicked() {
+            actions.push(HudAction::DeriveField);
+        }
+        if let Quantity::Principal(_) = view.quantity {
+            if ui.button("Directions").clicked() {
+                actions.push(HudAction::DeriveDirections);
+            }
+        }
+    });
+}
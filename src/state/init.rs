@@ -1,12 +1,15 @@
 use winit::window::Window;
 
+use super::pipelines;
+
 pub struct InitWgpu {
     pub instance: wgpu::Instance,
-    pub surface: wgpu::Surface,
+    pub surface: Option<wgpu::Surface>, // none when rendering offscreen
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
+    pub sample_counts: Vec<u32>, // msaa the adapter can do with config.format
 }
 
 impl InitWgpu {
@@ -30,7 +33,7 @@ impl InitWgpu {
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    features: wgpu::Features::empty(),
+                    features: adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
                     limits: wgpu::Limits::default(),
                 },
                 None, // Trace path
@@ -50,15 +53,60 @@ impl InitWgpu {
             view_formats: vec![],
         };
         surface.configure(&device, &config);
+        let sample_counts = pipelines::sample_counts(&adapter, device.features(), format);
      
         Self{
             instance,
-            surface,
+            surface: Some(surface),
             device,
             queue,
             config,
             size,
+            sample_counts,
         }
     }
-}
 
+    // no window: any adapter will do and frames go to textures read back by the caller
+    pub async fn headless(width: u32, height: u32) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+            .ok_or("no graphics adapter for offscreen rendering")?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                    limits: wgpu::Limits::default(),
+                },
+                None,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        let sample_counts = pipelines::sample_counts(&adapter, device.features(), format);
+        Ok(Self {
+            instance,
+            surface: None,
+            device,
+            queue,
+            config,
+            size: winit::dpi::PhysicalSize::new(width, height),
+            sample_counts,
+        })
+    }
+}
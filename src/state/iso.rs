@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use super::elements::{Element, ElementKind};
+use super::fields::{Colormap, Field, Location};
+use super::march::{marching_tets, marching_tris, EdgePoint};
+use super::td_comp::{self, TdObject, Vertex};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IsoSettings {
+    pub field: Option<usize>,
+    pub levels: Vec<f32>, // in field units
+    pub surfaces: bool,
+    pub lines: bool,
+}
+
+impl Default for IsoSettings {
+    fn default() -> Self {
+        Self {
+            field: None,
+            levels: Vec::new(),
+            surfaces: true,
+            lines: true,
+        }
+    }
+}
+
+// iso-surface triangles with vertices shared along tet edges
+pub struct IsoMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub values: Vec<f32>,
+    pub tris: Vec<[u32; 3]>,
+}
+
+impl IsoMesh {
+    pub fn new(obj: &TdObject, field: &Field, levels: &[f32]) -> Self {
+        let tets = obj.tets();
+        let positions = obj.display_positions();
+        let values = obj.nodal_values(field, true);
+        let mut mesh = IsoMesh {
+            positions: Vec::new(),
+            values: Vec::new(),
+            tris: Vec::new(),
+        };
+        let mut shared: HashMap<(u32, u32, usize), u32> = HashMap::new();
+        for (l, level) in levels.iter().enumerate() {
+            for tri in marching_tets(&tets, &values, *level) {
+                let ids = tri.map(|ep| {
+                    let key = (ep.a.min(ep.b), ep.a.max(ep.b), l);
+                    *shared.entry(key).or_insert_with(|| {
+                        mesh.positions.push(ep.lerp3(&positions));
+                        mesh.values.push(*level);
+                        mesh.positions.len() as u32 - 1
+                    })
+                });
+                // degenerate when the level runs through a node
+                if ids[0] != ids[1] && ids[1] != ids[2] && ids[0] != ids[2] {
+                    mesh.tris.push(ids);
+                }
+            }
+        }
+        mesh
+    }
+
+    pub fn vertices(&self, cmap: Colormap, range: (f32, f32)) -> Vec<Vertex> {
+        self.tris
+            .iter()
+            .flatten()
+            .map(|i| {
+                let p = self.positions[*i as usize];
+                let c = cmap.map(self.values[*i as usize], range.0, range.1);
+                Vertex {
+                    position: [p[0], p[1], p[2], 1.0],
+                    color: [c[0], c[1], c[2], 1.0],
+                }
+            })
+            .collect()
+    }
+
+    pub fn save(&self, filename: &str, field: &Field) -> std::io::Result<()> {
+        let elems = self
+            .tris
+            .iter()
+            .map(|t| Element {
+                kind: ElementKind::Tri3,
+                nodes: t.to_vec(),
+                group: String::new(),
+            })
+            .collect();
+        let level = Field {
+            name: field.name.clone(),
+            units: field.units.clone(),
+            location: Location::Node,
+            values: self.values.clone(),
+            layer: None,
+        };
+        td_comp::write_json(filename, self.positions.clone(), elems, vec![level])
+    }
+}
+
+// iso-lines over the outer surface as a line list
+pub fn lines(obj: &TdObject, field: &Field, levels: &[f32], cmap: Colormap) -> Vec<Vertex> {
+    let tris = obj.surface_tris();
+    let positions = obj.display_positions();
+    let values = obj.nodal_values(field, true);
+    let (lo, hi) = field.range();
+    let mut out = Vec::new();
+    for level in levels {
+        let c = cmap.map(*level, lo, hi);
+        for seg in marching_tris(&tris, &values, *level) {
+            out.extend(seg.iter().map(|ep: &EdgePoint| {
+                let p = ep.lerp3(&positions);
+                Vertex {
+                    position: [p[0], p[1], p[2], 1.0],
+                    color: [c[0], c[1], c[2], 1.0],
+                }
+            }));
+        }
+    }
+    out
+}
@@ -0,0 +1,76 @@
+// marching tetrahedra and triangles, shared by section caps and iso-surfaces/lines
+
+// a point on the edge a-b, at a + t * (b - a)
+#[derive(Clone, Copy, Debug)]
+pub struct EdgePoint {
+    pub a: u32,
+    pub b: u32,
+    pub t: f32,
+}
+
+impl EdgePoint {
+    fn new(a: u32, b: u32, values: &[f32], level: f32) -> Self {
+        let (va, vb) = (values[a as usize], values[b as usize]);
+        let t = if vb != va { (level - va) / (vb - va) } else { 0.5 };
+        Self { a, b, t: t.clamp(0.0, 1.0) }
+    }
+
+    pub fn lerp(&self, data: &[f32]) -> f32 {
+        let (va, vb) = (data[self.a as usize], data[self.b as usize]);
+        va + (vb - va) * self.t
+    }
+
+    pub fn lerp3(&self, data: &[[f32; 3]]) -> [f32; 3] {
+        let (pa, pb) = (data[self.a as usize], data[self.b as usize]);
+        [
+            pa[0] + (pb[0] - pa[0]) * self.t,
+            pa[1] + (pb[1] - pa[1]) * self.t,
+            pa[2] + (pb[2] - pa[2]) * self.t,
+        ]
+    }
+}
+
+// triangles of the surface values == level through the given tets
+pub fn marching_tets(tets: &[[u32; 4]], values: &[f32], level: f32) -> Vec<[EdgePoint; 3]> {
+    let mut out = Vec::new();
+    for tet in tets {
+        let (inside, outside): (Vec<u32>, Vec<u32>) =
+            tet.iter().partition(|n| values[**n as usize] >= level);
+        let ep = |a: u32, b: u32| EdgePoint::new(a, b, values, level);
+        match inside.len() {
+            1 => {
+                let a = inside[0];
+                out.push([ep(a, outside[0]), ep(a, outside[1]), ep(a, outside[2])]);
+            }
+            3 => {
+                let a = outside[0];
+                out.push([ep(a, inside[0]), ep(a, inside[1]), ep(a, inside[2])]);
+            }
+            2 => {
+                let (a, b) = (inside[0], inside[1]);
+                let (c, d) = (outside[0], outside[1]);
+                let quad = [ep(a, c), ep(a, d), ep(b, d), ep(b, c)];
+                out.push([quad[0], quad[1], quad[2]]);
+                out.push([quad[0], quad[2], quad[3]]);
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+// segments of the line values == level across the given triangles
+pub fn marching_tris(tris: &[[u32; 3]], values: &[f32], level: f32) -> Vec<[EdgePoint; 2]> {
+    let mut out = Vec::new();
+    for tri in tris {
+        let (inside, outside): (Vec<u32>, Vec<u32>) =
+            tri.iter().partition(|n| values[**n as usize] >= level);
+        let ep = |a: u32, b: u32| EdgePoint::new(a, b, values, level);
+        match (inside.len(), outside.len()) {
+            (1, 2) => out.push([ep(inside[0], outside[0]), ep(inside[0], outside[1])]),
+            (2, 1) => out.push([ep(outside[0], inside[0]), ep(outside[0], inside[1])]),
+            _ => {}
+        }
+    }
+    out
+}
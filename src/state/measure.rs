@@ -0,0 +1,273 @@
+use cgmath::{Matrix4, SquareMatrix, Vector4};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::td_comp::{TdObject, Vertex};
+
+// how close to a node a click has to land, in pixels
+const PICK_RADIUS: f32 = 12.0;
+const LINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tool {
+    Distance, // between two nodes
+    Angle,    // at the second of three nodes
+    Area,     // of a face under the cursor
+    Chain,    // along the mesh edges through the picked nodes
+}
+
+impl Tool {
+    pub const ALL: [Tool; 4] = [Tool::Distance, Tool::Angle, Tool::Area, Tool::Chain];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Distance => "Distance",
+            Tool::Angle => "Angle",
+            Tool::Area => "Area",
+            Tool::Chain => "Chain length",
+        }
+    }
+
+    // picks one tool uses, a new pick past this starts over
+    pub fn node_count(self) -> Option<usize> {
+        match self {
+            Tool::Distance => Some(2),
+            Tool::Angle => Some(3),
+            Tool::Area => Some(0),
+            Tool::Chain => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeasureSettings {
+    pub tool: Option<Tool>,
+    pub deformed: bool, // measure the drawn shape, not the undeformed one
+}
+
+// what was measured: segments to draw, labels at world points and the text
+#[derive(Default)]
+pub struct Measurement {
+    pub lines: Vec<Vertex>,
+    pub labels: Vec<([f32; 3], String)>,
+    pub summary: String,
+}
+
+fn point(obj: &TdObject, n: u32, deformed: bool) -> [f32; 3] {
+    let p = if deformed {
+        obj.display_position(n)
+    } else {
+        obj.vertices[n as usize].position
+    };
+    [p[0], p[1], p[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn midpoint(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|k| (a[k] + b[k]) / 2.0)
+}
+
+// world point to pixels, None behind the camera
+fn to_screen(view_proj: Matrix4<f32>, size: [f32; 2], p: [f32; 3]) -> Option<([f32; 2], f32)> {
+    let c = view_proj * Vector4::new(p[0], p[1], p[2], 1.0);
+    if c.w <= 0.0 {
+        return None;
+    }
+    let (x, y) = (c.x / c.w, c.y / c.w);
+    Some(([(x + 1.0) / 2.0 * size[0], (1.0 - y) / 2.0 * size[1]], c.z / c.w))
+}
+
+// nearest node on screen around the cursor, the front one on a tie
+pub fn pick_node(obj: &TdObject, view_proj: Matrix4<f32>, size: [f32; 2], cursor: [f32; 2], deformed: bool) -> Option<u32> {
+    let mut best: Option<(f32, f32, u32)> = None;
+    for n in 0..obj.vertices.len() as u32 {
+        let Some((s, depth)) = to_screen(view_proj, size, point(obj, n, deformed)) else {
+            continue;
+        };
+        let d = ((s[0] - cursor[0]).powi(2) + (s[1] - cursor[1]).powi(2)).sqrt();
+        if d > PICK_RADIUS {
+            continue;
+        }
+        if best.is_none_or(|(bd, bz, _)| d < bd - 0.5 || (d < bd + 0.5 && depth < bz)) {
+            best = Some((d, depth, n));
+        }
+    }
+    best.map(|(_, _, n)| n)
+}
+
+// element face hit first by the ray through the cursor
+pub fn pick_face(
+    obj: &TdObject,
+    view_proj: Matrix4<f32>,
+    size: [f32; 2],
+    cursor: [f32; 2],
+    deformed: bool,
+) -> Option<(usize, usize)> {
+    let inverse = view_proj.invert()?;
+    let ndc = [cursor[0] / size[0] * 2.0 - 1.0, 1.0 - cursor[1] / size[1] * 2.0];
+    let unproject = |z: f32| {
+        let p = inverse * Vector4::new(ndc[0], ndc[1], z, 1.0);
+        [p.x / p.w, p.y / p.w, p.z / p.w]
+    };
+    let origin = unproject(0.0);
+    let dir = sub(unproject(1.0), origin);
+
+    // moller-trumbore against the surface triangles
+    let mut hit: Option<(f32, usize)> = None;
+    for (i, tri) in obj.surface.chunks_exact(3).enumerate() {
+        let [a, b, c] = [tri[0], tri[1], tri[2]].map(|n| point(obj, n, deformed));
+        let (e1, e2) = (sub(b, a), sub(c, a));
+        let h = cross(dir, e2);
+        let det = dot(e1, h);
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let s = sub(origin, a);
+        let u = dot(s, h) / det;
+        let q = cross(s, e1);
+        let v = dot(dir, q) / det;
+        let t = dot(e2, q) / det;
+        if u < 0.0 || v < 0.0 || u + v > 1.0 || t < 0.0 {
+            continue;
+        }
+        if hit.is_none_or(|(ht, _)| t < ht) {
+            hit = Some((t, i));
+        }
+    }
+    let (_, tri) = hit?;
+    let owner = obj.surface_owner[tri] as usize;
+    let nodes = &obj.surface[tri * 3..tri * 3 + 3];
+    let e = &obj.elements[owner];
+    let face = e
+        .kind
+        .faces()
+        .iter()
+        .position(|f| nodes.iter().all(|n| f.iter().any(|l| e.nodes[*l] == *n)))?;
+    Some((owner, face))
+}
+
+// shortest way along the element edges, by length
+fn edge_path(obj: &TdObject, from: u32, to: u32, deformed: bool) -> Option<Vec<u32>> {
+    let mut adjacent: HashMap<u32, Vec<u32>> = HashMap::new();
+    for e in obj.edges.chunks_exact(2) {
+        adjacent.entry(e[0]).or_default().push(e[1]);
+        adjacent.entry(e[1]).or_default().push(e[0]);
+    }
+    let mut dist: HashMap<u32, f32> = HashMap::from([(from, 0.0)]);
+    let mut prev: HashMap<u32, u32> = HashMap::new();
+    // the bits of a positive f32 sort like the number
+    let mut queue = BinaryHeap::from([Reverse((0.0f32.to_bits(), from))]);
+    while let Some(Reverse((bits, n))) = queue.pop() {
+        if n == to {
+            break;
+        }
+        let d = f32::from_bits(bits);
+        if d > dist[&n] {
+            continue;
+        }
+        for m in adjacent.get(&n).into_iter().flatten() {
+            let next = d + length(sub(point(obj, *m, deformed), point(obj, n, deformed)));
+            if dist.get(m).is_none_or(|old| next < *old) {
+                dist.insert(*m, next);
+                prev.insert(*m, n);
+                queue.push(Reverse((next.to_bits(), *m)));
+            }
+        }
+    }
+    let mut path = vec![to];
+    while *path.last()? != from {
+        path.push(*prev.get(path.last()?)?);
+    }
+    path.reverse();
+    Some(path)
+}
+
+pub fn evaluate(obj: &TdObject, tool: Tool, picks: &[u32], face: Option<(usize, usize)>, deformed: bool) -> Measurement {
+    let p = |n: u32| point(obj, n, deformed);
+    let mut m = Measurement::default();
+    let mut segment = |a: [f32; 3], b: [f32; 3]| {
+        m.lines.extend([a, b].map(|q| Vertex { position: [q[0], q[1], q[2], 1.0], color: LINE_COLOR }));
+    };
+    match tool {
+        Tool::Distance if picks.len() == 2 => {
+            let (a, b) = (p(picks[0]), p(picks[1]));
+            let d = sub(b, a);
+            segment(a, b);
+            m.summary = format!(
+                "distance {} - {}: {:.6} (dx {:.6}, dy {:.6}, dz {:.6})",
+                picks[0], picks[1], length(d), d[0], d[1], d[2]
+            );
+            m.labels.push((midpoint(a, b), format!("{:.4}", length(d))));
+        }
+        Tool::Angle if picks.len() == 3 => {
+            let (a, o, b) = (p(picks[0]), p(picks[1]), p(picks[2]));
+            segment(o, a);
+            segment(o, b);
+            let (u, v) = (sub(a, o), sub(b, o));
+            let angle = (dot(u, v) / (length(u) * length(v))).clamp(-1.0, 1.0).acos().to_degrees();
+            m.summary = format!("angle {} - {} - {}: {:.4} deg", picks[0], picks[1], picks[2], angle);
+            m.labels.push((o, format!("{:.2} deg", angle)));
+        }
+        Tool::Area => {
+            let Some((element, f)) = face else {
+                return m;
+            };
+            let e = &obj.elements[element];
+            let nodes: Vec<[f32; 3]> = e.kind.faces()[f].iter().map(|l| p(e.nodes[*l])).collect();
+            let mut area = [0.0; 3];
+            for i in 1..nodes.len() - 1 {
+                let c = cross(sub(nodes[i], nodes[0]), sub(nodes[i + 1], nodes[0]));
+                area = std::array::from_fn(|k| area[k] + c[k] / 2.0);
+            }
+            for i in 0..nodes.len() {
+                segment(nodes[i], nodes[(i + 1) % nodes.len()]);
+            }
+            let n = nodes.len() as f32;
+            let center = std::array::from_fn(|k| nodes.iter().map(|q| q[k]).sum::<f32>() / n);
+            m.summary = format!("area of element {} face {}: {:.6}", element, f, length(area));
+            m.labels.push((center, format!("{:.4}", length(area))));
+        }
+        Tool::Chain if picks.len() >= 2 => {
+            let mut total = 0.0;
+            let mut straight = false;
+            for w in picks.windows(2) {
+                // straight across where the mesh does not connect the picks
+                let path = edge_path(obj, w[0], w[1], deformed).unwrap_or_else(|| {
+                    straight = true;
+                    w.to_vec()
+                });
+                for s in path.windows(2) {
+                    let (a, b) = (p(s[0]), p(s[1]));
+                    total += length(sub(b, a));
+                    segment(a, b);
+                }
+            }
+            m.summary = format!("chain of {} nodes: {:.6}", picks.len(), total);
+            if straight {
+                m.summary += " (partly straight, no edge path)";
+            }
+            m.labels.push((p(*picks.last().unwrap()), format!("{:.4}", total)));
+        }
+        _ => {
+            m.summary = format!("{} picked", picks.len());
+        }
+    }
+    // the picked nodes themselves stay marked
+    m.labels.extend(picks.iter().map(|n| (p(*n), format!("#{}", n))));
+    m
+}
@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// analysis input read next to the mesh, everything is optional in the file
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Material {
+    pub name: String,
+    pub e: f64, // young's modulus
+    #[serde(default)]
+    pub nu: f64, // poisson's ratio
+    #[serde(default)]
+    pub density: f64,
+    #[serde(default)]
+    pub conductivity: f64,
+    #[serde(default)]
+    pub expansion: f64, // thermal expansion coefficient
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Section {
+    pub name: String,
+    #[serde(default)]
+    pub area: f64,
+    #[serde(default)]
+    pub iy: f64, // second moments about the local y and z axes
+    #[serde(default)]
+    pub iz: f64,
+    #[serde(default)]
+    pub j: f64, // torsion constant
+    #[serde(default)]
+    pub thickness: f64, // plane elements, 1 when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<Profile>,
+}
+
+// cross-section shape for drawing beams, width along the local z axis and
+// height along the local y axis
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum Profile {
+    Rect { width: f64, height: f64 },
+    Circle { diameter: f64 },
+    I { width: f64, height: f64, web: f64, flange: f64 },
+    Tube { diameter: f64, wall: f64 },
+}
+
+// what an element group is made of
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GroupProps {
+    #[serde(default)]
+    pub material: String,
+    #[serde(default)]
+    pub section: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<[f64; 3]>, // local y of the beams, global y by default
+}
+
+// fixed dofs of a node in the order ux uy uz rx ry rz, missing ones are free.
+// fixed dofs are held at `value`, or at 0 past its end
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Support {
+    pub node: u32,
+    pub fixed: Vec<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub value: Vec<f64>,
+}
+
+impl Support {
+    pub fn is_fixed(&self, dof: usize) -> bool {
+        self.fixed.get(dof).copied().unwrap_or(false)
+    }
+
+    pub fn prescribed(&self, dof: usize) -> Option<f64> {
+        self.is_fixed(dof)
+            .then(|| self.value.get(dof).copied().unwrap_or(0.0))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodalLoad {
+    pub node: u32,
+    #[serde(default)]
+    pub force: [f64; 3],
+    #[serde(default)]
+    pub moment: [f64; 3],
+}
+
+// uniform pressure on an element face (an edge of a triangle), positive
+// pushes into the element
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pressure {
+    pub element: u32,
+    pub face: usize,
+    pub value: f64,
+}
+
+// one face of an element, an edge for triangles
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct FaceRef {
+    pub element: u32,
+    pub face: usize,
+}
+
+// thermal boundaries, all by set name
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Temperature {
+    pub set: String, // node set
+    pub value: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Flux {
+    pub set: String, // face set
+    pub value: f64,  // heat per area flowing in
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Convection {
+    pub set: String, // face set
+    pub h: f64,      // film coefficient
+    pub ambient: f64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ModelData {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub materials: Vec<Material>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<Section>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub groups: HashMap<String, GroupProps>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub supports: Vec<Support>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub loads: Vec<NodalLoad>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pressures: Vec<Pressure>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub node_sets: HashMap<String, Vec<u32>>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub face_sets: HashMap<String, Vec<FaceRef>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub temperatures: Vec<Temperature>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fluxes: Vec<Flux>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub convections: Vec<Convection>,
+}
+
+impl ModelData {
+    pub fn is_empty(&self) -> bool {
+        self.supports.is_empty()
+            && self.loads.is_empty()
+            && self.pressures.is_empty()
+            && self.temperatures.is_empty()
+            && self.fluxes.is_empty()
+            && self.convections.is_empty()
+    }
+
+    // materials, sections and who uses them, for the info output
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+        for m in &self.materials {
+            out += &format!(
+                "material {}: E {:e}, nu {}, density {}, conductivity {}, expansion {:e}\n",
+                m.name, m.e, m.nu, m.density, m.conductivity, m.expansion
+            );
+        }
+        for s in &self.sections {
+            out += &format!(
+                "section {}: area {:e}, iy {:e}, iz {:e}, j {:e}, thickness {}\n",
+                s.name, s.area, s.iy, s.iz, s.j, s.thickness
+            );
+        }
+        let mut groups: Vec<_> = self.groups.iter().collect();
+        groups.sort_by_key(|(name, _)| name.as_str());
+        for (name, g) in groups {
+            out += &format!("group {}: material {}, section {}\n", name, g.material, g.section);
+        }
+        out
+    }
+
+    pub fn orientation(&self, group: &str) -> Option<[f64; 3]> {
+        self.groups.get(group).and_then(|g| g.orientation)
+    }
+
+    pub fn node_set(&self, name: &str) -> Result<&[u32], String> {
+        self.node_sets
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or(format!("unknown node set '{}'", name))
+    }
+
+    pub fn face_set(&self, name: &str) -> Result<&[FaceRef], String> {
+        self.face_sets
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or(format!("unknown face set '{}'", name))
+    }
+
+    // group assignment, or the only material/section when there is just one
+    pub fn material(&self, group: &str) -> Result<&Material, String> {
+        let name = self.groups.get(group).map(|g| g.material.as_str());
+        match name {
+            Some(name) => self
+                .materials
+                .iter()
+                .find(|m| m.name == name)
+                .ok_or(format!("unknown material {} in group {}", name, group)),
+            None if self.materials.len() == 1 => Ok(&self.materials[0]),
+            None => Err(format!("no material for group '{}'", group)),
+        }
+    }
+
+    pub fn section(&self, group: &str) -> Result<&Section, String> {
+        let name = self.groups.get(group).map(|g| g.section.as_str());
+        match name {
+            Some(name) => self
+                .sections
+                .iter()
+                .find(|s| s.name == name)
+                .ok_or(format!("unknown section {} in group {}", name, group)),
+            None if self.sections.len() == 1 => Ok(&self.sections[0]),
+            None => Err(format!("no section for group '{}'", group)),
+        }
+    }
+}
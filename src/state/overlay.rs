@@ -0,0 +1,197 @@
+use cgmath::{Matrix4, Vector4};
+use egui::{Align2, Color32, FontId, Pos2, Rect, Shape, Stroke, Vec2};
+
+use super::camera::FpsCamera;
+use super::fields::{self, Colormap, Field};
+
+const LEGEND_TICKS: usize = 5;
+const LEGEND_BAR: Vec2 = Vec2::new(22.0, 240.0);
+const TRIAD_LEN: f32 = 40.0;
+
+fn to_color32(c: [f32; 3]) -> Color32 {
+    Color32::from_rgb((c[0] * 255.0) as u8, (c[1] * 255.0) as u8, (c[2] * 255.0) as u8)
+}
+
+fn painter(ctx: &egui::Context, id: &str) -> egui::Painter {
+    ctx.layer_painter(egui::LayerId::new(egui::Order::Background, egui::Id::new(id)))
+}
+
+// vertical color bar on the right edge, max on top
+pub fn legend(ctx: &egui::Context, field: &Field, cmap: Colormap) {
+    let painter = painter(ctx, "legend");
+    let screen = ctx.screen_rect();
+    let text_color = Color32::WHITE;
+    let font = FontId::proportional(13.0);
+
+    let bar = Rect::from_min_size(
+        Pos2::new(screen.right() - LEGEND_BAR.x - 90.0, screen.center().y - LEGEND_BAR.y / 2.0),
+        LEGEND_BAR,
+    );
+    let mut title = field.name.clone();
+    if let Some(layer) = field.layer {
+        title = format!("{} ({})", title, layer.name().to_lowercase());
+    }
+    if !field.units.is_empty() {
+        title = format!("{} [{}]", title, field.units);
+    }
+    painter.text(
+        bar.left_top() - Vec2::new(0.0, 10.0),
+        Align2::LEFT_BOTTOM,
+        title,
+        font.clone(),
+        text_color,
+    );
+
+    // one quad per colormap segment so non-linear maps look right
+    let steps = 32;
+    let mut mesh = egui::Mesh::default();
+    for i in 0..=steps {
+        let t = i as f32 / steps as f32;
+        let y = bar.bottom() - t * bar.height();
+        let c = to_color32(cmap.sample(t));
+        mesh.colored_vertex(Pos2::new(bar.left(), y), c);
+        mesh.colored_vertex(Pos2::new(bar.right(), y), c);
+        if i > 0 {
+            let k = 2 * i as u32;
+            mesh.add_triangle(k - 2, k - 1, k);
+            mesh.add_triangle(k - 1, k + 1, k);
+        }
+    }
+    painter.add(Shape::mesh(mesh));
+    painter.rect_stroke(bar, 0.0, Stroke::new(1.0, text_color));
+
+    let (lo, hi) = field.range();
+    for i in 0..LEGEND_TICKS {
+        let t = i as f32 / (LEGEND_TICKS - 1) as f32;
+        let y = bar.bottom() - t * bar.height();
+        painter.line_segment(
+            [Pos2::new(bar.right(), y), Pos2::new(bar.right() + 5.0, y)],
+            Stroke::new(1.0, text_color),
+        );
+        painter.text(
+            Pos2::new(bar.right() + 8.0, y),
+            Align2::LEFT_CENTER,
+            format!("{:.4}", lo + t * (hi - lo)),
+            font.clone(),
+            text_color,
+        );
+    }
+}
+
+// swatches and names on the right edge, for a model colored by category
+pub fn categories(ctx: &egui::Context, title: &str, names: &[&str]) {
+    let painter = painter(ctx, "legend");
+    let screen = ctx.screen_rect();
+    let font = FontId::proportional(13.0);
+    let row = 18.0;
+    let top = screen.center().y - row * names.len() as f32 / 2.0;
+    let left = screen.right() - LEGEND_BAR.x - 90.0;
+    painter.text(Pos2::new(left, top - 10.0), Align2::LEFT_BOTTOM, title, font.clone(), Color32::WHITE);
+    for (i, name) in names.iter().enumerate() {
+        let y = top + i as f32 * row;
+        let swatch = Rect::from_min_size(Pos2::new(left, y), Vec2::new(14.0, 14.0));
+        painter.rect_filled(swatch, 0.0, to_color32(fields::category_color(i)));
+        let name = if name.is_empty() { "(none)" } else { name };
+        painter.text(
+            Pos2::new(left + 20.0, y + 7.0),
+            Align2::LEFT_CENTER,
+            name,
+            font.clone(),
+            Color32::WHITE,
+        );
+    }
+}
+
+// xyz axes in the bottom left corner, rotated like the world is
+pub fn triad(ctx: &egui::Context, camera: &FpsCamera) {
+    let painter = painter(ctx, "triad");
+    let screen = ctx.screen_rect();
+    let origin = Pos2::new(screen.left() + 60.0, screen.bottom() - 60.0);
+    let view: Matrix4<f32> = camera.calc_matrix();
+
+    let mut axes = [
+        (Vector4::unit_x(), "X", Color32::from_rgb(230, 60, 60)),
+        (Vector4::unit_y(), "Y", Color32::from_rgb(60, 200, 60)),
+        (Vector4::unit_z(), "Z", Color32::from_rgb(70, 110, 255)),
+    ]
+    .map(|(axis, label, color)| (view * axis, label, color));
+    // farthest first so the nearest axis ends up on top
+    axes.sort_by(|a, b| a.0.z.total_cmp(&b.0.z));
+
+    for (dir, label, color) in axes {
+        let tip = origin + Vec2::new(dir.x, -dir.y) * TRIAD_LEN;
+        painter.line_segment([origin, tip], Stroke::new(2.0, color));
+        painter.text(
+            tip + Vec2::new(dir.x, -dir.y) * 8.0,
+            Align2::CENTER_CENTER,
+            label,
+            FontId::proportional(13.0),
+            color,
+        );
+    }
+}
+
+// text at world points, skipped when behind the camera
+pub fn annotations(ctx: &egui::Context, view_proj: Matrix4<f32>, labels: &[([f32; 3], String)]) {
+    let painter = painter(ctx, "annotations");
+    let screen = ctx.screen_rect();
+    for (p, text) in labels {
+        let c = view_proj * Vector4::new(p[0], p[1], p[2], 1.0);
+        if c.w <= 0.0 {
+            continue;
+        }
+        let pos = Pos2::new(
+            screen.left() + (c.x / c.w + 1.0) / 2.0 * screen.width(),
+            screen.top() + (1.0 - c.y / c.w) / 2.0 * screen.height(),
+        );
+        let galley = painter.layout_no_wrap(text.clone(), FontId::proportional(13.0), Color32::WHITE);
+        let rect = Align2::LEFT_BOTTOM.anchor_rect(Rect::from_min_size(pos + Vec2::new(6.0, -4.0), galley.size()));
+        painter.rect_filled(rect.expand(2.0), 2.0, Color32::from_black_alpha(160));
+        painter.galley(rect.min, galley);
+    }
+}
+
+// x-y line plot filling the width of the ui, gaps where y is NaN
+pub fn plot(ui: &mut egui::Ui, xs: &[f32], ys: &[f32], x_label: &str, y_label: &str) {
+    let font = FontId::proportional(12.0);
+    let text_color = ui.visuals().text_color();
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(ui.available_width().max(320.0), 200.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    // room on the left and bottom for the tick labels
+    let area = Rect::from_min_max(rect.min + Vec2::new(60.0, 8.0), rect.max - Vec2::new(10.0, 28.0));
+    painter.rect_stroke(area, 0.0, Stroke::new(1.0, text_color));
+
+    let finite = ys.iter().cloned().filter(|y| y.is_finite());
+    let (lo, hi) = finite.fold((f32::MAX, f32::MIN), |(lo, hi), y| (lo.min(y), hi.max(y)));
+    let (x0, x1) = (xs.first().cloned().unwrap_or(0.0), xs.last().cloned().unwrap_or(1.0));
+    if lo > hi {
+        painter.text(area.center(), Align2::CENTER_CENTER, "no samples inside the mesh", font, text_color);
+        return;
+    }
+    let span = |a: f32, b: f32| if b > a { b - a } else { 1.0 };
+    let to_screen = |x: f32, y: f32| {
+        Pos2::new(
+            area.left() + (x - x0) / span(x0, x1) * area.width(),
+            area.bottom() - (y - lo) / span(lo, hi) * area.height(),
+        )
+    };
+
+    let mut run = Vec::new();
+    for (x, y) in xs.iter().zip(ys) {
+        if y.is_finite() {
+            run.push(to_screen(*x, *y));
+        } else if !run.is_empty() {
+            painter.add(Shape::line(std::mem::take(&mut run), Stroke::new(1.5, Color32::LIGHT_BLUE)));
+        }
+    }
+    painter.add(Shape::line(run, Stroke::new(1.5, Color32::LIGHT_BLUE)));
+
+    for (v, align, y) in [(hi, Align2::RIGHT_TOP, area.top()), (lo, Align2::RIGHT_BOTTOM, area.bottom())] {
+        painter.text(Pos2::new(area.left() - 4.0, y), align, format!("{:.4}", v), font.clone(), text_color);
+    }
+    for (v, align, x) in [(x0, Align2::LEFT_TOP, area.left()), (x1, Align2::RIGHT_TOP, area.right())] {
+        painter.text(Pos2::new(x, area.bottom() + 4.0), align, format!("{:.4}", v), font.clone(), text_color);
+    }
+    painter.text(Pos2::new(area.center().x, area.bottom() + 4.0), Align2::CENTER_TOP, x_label, font.clone(), text_color);
+    painter.text(Pos2::new(rect.left(), area.center().y), Align2::LEFT_CENTER, y_label, font, text_color);
+}
@@ -0,0 +1,163 @@
+use std::mem;
+
+use super::glyph::{GlyphInstance, GlyphVertex};
+use super::init::InitWgpu;
+use super::profile::LitVertex;
+use super::td_comp::Vertex;
+
+pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+// a line list read two vertices at a time, one segment per instance
+const SEGMENT_ATTRIBUTES: [wgpu::VertexAttribute; 4] =
+    wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4, 2 => Float32x4, 3 => Float32x4];
+
+fn segment_desc() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: 2 * mem::size_of::<Vertex>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &SEGMENT_ATTRIBUTES,
+    }
+}
+
+// sample counts the adapter can render the surface and depth formats with;
+// past 1 and 4 that needs the adapter specific format feature
+pub fn sample_counts(adapter: &wgpu::Adapter, features: wgpu::Features, format: wgpu::TextureFormat) -> Vec<u32> {
+    if !features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+        return vec![1, 4];
+    }
+    let color = adapter.get_texture_format_features(format).flags;
+    let depth = adapter.get_texture_format_features(wgpu::TextureFormat::Depth24Plus).flags;
+    SAMPLE_COUNTS
+        .into_iter()
+        .filter(|n| color.sample_count_supported(*n) && depth.sample_count_supported(*n))
+        .collect()
+}
+
+// every way the scene is drawn, built again when the sample count changes
+pub struct Pipelines {
+    pub strip: wgpu::RenderPipeline,
+    pub node: wgpu::RenderPipeline,
+    pub surface: wgpu::RenderPipeline,
+    pub edge: wgpu::RenderPipeline,
+    pub bare_edge: wgpu::RenderPipeline,
+    pub glyph: wgpu::RenderPipeline,
+    pub lit: wgpu::RenderPipeline,
+    pub line: wgpu::RenderPipeline,      // smoothed line lists
+    pub edge_line: wgpu::RenderPipeline, // smoothed and darker, over a surface
+}
+
+impl Pipelines {
+    pub fn new(init: &InitWgpu, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, samples: u32) -> Self {
+        let model_vertex = wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[Vertex::desc()],
+        };
+        let segment_vertex = wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_line",
+            buffers: &[segment_desc()],
+        };
+        let pipeline = |vertex: wgpu::VertexState, topology, fs_entry, depth_bias, blend| {
+            create_pipeline(init, layout, vertex, topology, fs_entry, depth_bias, samples, blend)
+        };
+        let replace = wgpu::BlendState::REPLACE;
+        Self {
+            strip: pipeline(model_vertex.clone(), wgpu::PrimitiveTopology::LineStrip, "fs_main", 0, replace), // this in particular!!
+            node: pipeline(model_vertex.clone(), wgpu::PrimitiveTopology::PointList, "fs_main", 0, replace),
+            // pushed back a little so the edges win the depth test
+            surface: pipeline(model_vertex.clone(), wgpu::PrimitiveTopology::TriangleList, "fs_main", 2, replace),
+            edge: pipeline(model_vertex.clone(), wgpu::PrimitiveTopology::LineList, "fs_edge", 0, replace),
+            bare_edge: pipeline(model_vertex, wgpu::PrimitiveTopology::LineList, "fs_main", 0, replace),
+            // second pipeline for the instanced arrows
+            glyph: pipeline(
+                wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_glyph",
+                    buffers: &[GlyphVertex::desc(), GlyphInstance::desc()],
+                },
+                wgpu::PrimitiveTopology::TriangleList,
+                "fs_glyph",
+                0,
+                replace,
+            ),
+            // cpu-built meshes with normals, shaded like the glyphs
+            lit: pipeline(
+                wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_lit",
+                    buffers: &[LitVertex::desc()],
+                },
+                wgpu::PrimitiveTopology::TriangleList,
+                "fs_glyph",
+                0,
+                replace,
+            ),
+            // quads with faded sides, blended over what is behind
+            line: pipeline(
+                segment_vertex.clone(),
+                wgpu::PrimitiveTopology::TriangleList,
+                "fs_line",
+                0,
+                wgpu::BlendState::ALPHA_BLENDING,
+            ),
+            edge_line: pipeline(
+                segment_vertex,
+                wgpu::PrimitiveTopology::TriangleList,
+                "fs_line_edge",
+                0,
+                wgpu::BlendState::ALPHA_BLENDING,
+            ),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_pipeline(
+    init: &InitWgpu,
+    layout: &wgpu::PipelineLayout,
+    vertex: wgpu::VertexState,
+    topology: wgpu::PrimitiveTopology,
+    fs_entry: &str,
+    depth_bias: i32,
+    samples: u32,
+    blend: wgpu::BlendState,
+) -> wgpu::RenderPipeline {
+    let module = vertex.module;
+    init.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(layout),
+            vertex,
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: fs_entry,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: init.config.format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology,
+                strip_index_format: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth24Plus,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: depth_bias,
+                    slope_scale: depth_bias as f32,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState {
+                count: samples,
+                ..Default::default()
+            },
+            multiview: None,
+        })
+}
@@ -0,0 +1,235 @@
+use std::fs;
+
+use super::elements::ElementKind;
+use super::fields::{Field, Location};
+use super::td_comp::{TdObject, Vertex};
+
+// how far off a surface element's plane a sample may be, relative to the model radius
+const PLANE_TOLERANCE: f32 = 1e-4;
+// slack on the barycentric test so samples on shared faces are not lost
+const INSIDE_TOLERANCE: f32 = 1e-5;
+const LINE_COLOR: [f32; 4] = [1.0, 0.9, 0.2, 1.0];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProbeSettings {
+    pub show: bool,
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+    pub samples: usize,
+}
+
+impl Default for ProbeSettings {
+    fn default() -> Self {
+        Self { show: false, from: [0.0; 3], to: [1.0, 0.0, 0.0], samples: 100 }
+    }
+}
+
+// a field sampled at evenly spaced points of the line, NaN outside the mesh
+pub struct Probe {
+    pub field: String,
+    pub units: String,
+    pub points: Vec<[f32; 3]>,
+    pub distance: Vec<f32>,
+    pub values: Vec<f32>,
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// weights of the corners at p, None when p is outside the tet
+fn tet_weights(c: [[f32; 3]; 4], p: [f32; 3]) -> Option<[f32; 4]> {
+    let (a, b, d) = (sub(c[1], c[0]), sub(c[2], c[0]), sub(c[3], c[0]));
+    let det = dot(cross(a, b), d);
+    if det.abs() <= f32::MIN_POSITIVE {
+        return None;
+    }
+    let q = sub(p, c[0]);
+    // cramer's rule on [a b d] w = q
+    let w1 = dot(cross(q, b), d) / det;
+    let w2 = dot(cross(a, q), d) / det;
+    let w3 = dot(cross(a, b), q) / det;
+    let w = [1.0 - w1 - w2 - w3, w1, w2, w3];
+    w.iter().all(|v| *v >= -INSIDE_TOLERANCE).then_some(w)
+}
+
+// weights of the corners at p, None when p is off the triangle or its plane
+fn tri_weights(c: [[f32; 3]; 3], p: [f32; 3], tolerance: f32) -> Option<[f32; 3]> {
+    let (a, b) = (sub(c[1], c[0]), sub(c[2], c[0]));
+    let n = cross(a, b);
+    let nn = dot(n, n);
+    if nn <= f32::MIN_POSITIVE {
+        return None;
+    }
+    let q = sub(p, c[0]);
+    if dot(q, n).abs() / nn.sqrt() > tolerance {
+        return None;
+    }
+    let w1 = dot(cross(q, b), n) / nn;
+    let w2 = dot(cross(a, q), n) / nn;
+    let w = [1.0 - w1 - w2, w1, w2];
+    w.iter().all(|v| *v >= -INSIDE_TOLERANCE).then_some(w)
+}
+
+// the element around p and the weights of its nodes: volumes as their tets,
+// surfaces as triangles, so hexes and quads are interpolated piecewise linearly
+fn locate(obj: &TdObject, p: [f32; 3], tolerance: f32) -> Option<(usize, Vec<(u32, f32)>)> {
+    let pos = |n: u32| {
+        let v = obj.vertices[n as usize].position;
+        [v[0], v[1], v[2]]
+    };
+    // volumes first, a shell on a solid's face should not win
+    let volumes = obj.elements.iter().enumerate().filter(|(_, e)| e.kind.is_volume());
+    for (id, e) in volumes {
+        for t in e.tets() {
+            if let Some(w) = tet_weights(t.map(pos), p) {
+                return Some((id, t.into_iter().zip(w).collect()));
+            }
+        }
+    }
+    for (id, e) in obj.elements.iter().enumerate() {
+        let tris: &[[usize; 3]] = match e.kind {
+            ElementKind::Tri3 => &[[0, 1, 2]],
+            ElementKind::Quad4 => &[[0, 1, 2], [0, 2, 3]],
+            _ => continue,
+        };
+        for tri in tris {
+            let nodes = tri.map(|l| e.nodes[l]);
+            if let Some(w) = tri_weights(nodes.map(pos), p, tolerance) {
+                return Some((id, nodes.into_iter().zip(w).collect()));
+            }
+        }
+    }
+    None
+}
+
+pub fn sample(obj: &TdObject, field: &Field, settings: &ProbeSettings) -> Probe {
+    let (_, radius) = obj.center_radius();
+    let tolerance = PLANE_TOLERANCE * radius;
+    let d = sub(settings.to, settings.from);
+    let length = dot(d, d).sqrt();
+    let count = settings.samples.max(2);
+    let mut probe = Probe {
+        field: field.name.clone(),
+        units: field.units.clone(),
+        points: Vec::with_capacity(count),
+        distance: Vec::with_capacity(count),
+        values: Vec::with_capacity(count),
+    };
+    for i in 0..count {
+        let t = i as f32 / (count - 1) as f32;
+        let p = std::array::from_fn(|k| settings.from[k] + d[k] * t);
+        let value = match locate(obj, p, tolerance) {
+            Some((id, _)) if field.location == Location::Element => field.values[id],
+            Some((_, weights)) => weights.iter().map(|(n, w)| field.values[*n as usize] * w).sum(),
+            None => f32::NAN,
+        };
+        probe.points.push(p);
+        probe.distance.push(t * length);
+        probe.values.push(value);
+    }
+    probe
+}
+
+// the probe line itself, for the 3d view
+pub fn line(settings: &ProbeSettings) -> Vec<Vertex> {
+    [settings.from, settings.to]
+        .map(|p| Vertex { position: [p[0], p[1], p[2], 1.0], color: LINE_COLOR })
+        .to_vec()
+}
+
+impl Probe {
+    // samples outside the mesh are left empty
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("distance,x,y,z,{}\n", self.field);
+        for ((d, p), v) in self.distance.iter().zip(&self.points).zip(&self.values) {
+            let v = if v.is_nan() { String::new() } else { v.to_string() };
+            out += &format!("{},{},{},{},{}\n", d, p[0], p[1], p[2], v);
+        }
+        out
+    }
+
+    pub fn save_csv(&self, filename: &str) -> std::io::Result<()> {
+        fs::write(filename, self.to_csv())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // two tets sharing the face 1-2-3
+    fn two_tets() -> TdObject {
+        let doc = json!({
+            "vers": [
+                [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 1.0, 1.0],
+            ],
+            "elems": [
+                { "kind": "tet4", "nodes": [0, 1, 2, 3] },
+                { "kind": "tet4", "nodes": [1, 2, 3, 4] },
+            ],
+        });
+        TdObject::from_json(&doc.to_string())
+    }
+
+    fn linear(p: [f32; 3]) -> f32 {
+        1.0 + 2.0 * p[0] + 3.0 * p[1] - p[2]
+    }
+
+    fn linear_field(obj: &TdObject) -> Field {
+        Field {
+            name: "Linear".into(),
+            units: String::new(),
+            location: Location::Node,
+            values: obj.vertices.iter().map(|v| linear(std::array::from_fn(|k| v.position[k]))).collect(),
+            layer: None,
+        }
+    }
+
+    #[test]
+    fn linear_field_is_exact_across_both_tets() {
+        let obj = two_tets();
+        let field = linear_field(&obj);
+        let settings = ProbeSettings { show: true, from: [0.1, 0.1, 0.1], to: [0.6, 0.6, 0.5], samples: 11 };
+        let probe = sample(&obj, &field, &settings);
+        assert_eq!(probe.values.len(), 11);
+        // the line starts in the first tet and ends in the second
+        let first = locate(&obj, settings.from, 0.0).map(|(id, _)| id);
+        let last = locate(&obj, settings.to, 0.0).map(|(id, _)| id);
+        assert_eq!((first, last), (Some(0), Some(1)));
+        for (p, v) in probe.points.iter().zip(&probe.values) {
+            assert!((v - linear(*p)).abs() < 1e-4, "{:?}: {} != {}", p, v, linear(*p));
+        }
+        let length = probe.distance.last().unwrap();
+        assert!((length - (0.25f32 + 0.25 + 0.16).sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn outside_the_mesh_is_nan_and_an_empty_cell() {
+        let obj = two_tets();
+        let field = linear_field(&obj);
+        let settings = ProbeSettings { show: true, from: [0.1, 0.1, 0.1], to: [2.1, 2.1, 2.1], samples: 3 };
+        let probe = sample(&obj, &field, &settings);
+        assert!((probe.values[0] - linear([0.1, 0.1, 0.1])).abs() < 1e-4);
+        assert!(probe.values[1].is_nan());
+        assert!(probe.values[2].is_nan());
+
+        let csv = probe.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "distance,x,y,z,Linear");
+        assert_eq!(lines.len(), 4);
+        assert!(!lines[1].ends_with(','));
+        assert!(lines[2].ends_with(','));
+        assert!(lines[3].ends_with(','));
+        assert!(lines.iter().all(|l| !l.contains("NaN")));
+    }
+}
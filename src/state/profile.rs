@@ -0,0 +1,250 @@
+use bytemuck::{Pod, Zeroable};
+use std::f32::consts::TAU;
+
+use super::elements::ElementKind;
+use super::fields::{ColorBy, Colormap, Field, Location};
+use super::model::Profile;
+use super::solver;
+use super::td_comp::TdObject;
+
+const CIRCLE_SEGMENTS: usize = 16;
+// radius of lines without a section, relative to the model radius
+const DEFAULT_RADIUS: f32 = 0.005;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProfileSettings {
+    pub show: bool,
+    pub scale: f32, // 1 is the true section size
+}
+
+impl Default for ProfileSettings {
+    fn default() -> Self {
+        Self { show: true, scale: 1.0 }
+    }
+}
+
+// vertex of the lit meshes made on the cpu
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct LitVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl LitVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0=>Float32x3, 1=>Float32x3, 2=>Float32x4];
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LitVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// colors of the meshes made on the cpu: the element's category, its value of
+// an element field, or the colors of its nodes
+pub struct Paint<'a> {
+    categories: Option<Vec<[f32; 4]>>,
+    field: Option<&'a Field>,
+    range: (f32, f32),
+    cmap: Colormap,
+}
+
+impl<'a> Paint<'a> {
+    pub fn new(obj: &TdObject, field: Option<&'a Field>, color_by: ColorBy, cmap: Colormap) -> Self {
+        let field = field.filter(|f| f.location == Location::Element);
+        Self {
+            categories: (color_by != ColorBy::Field).then(|| obj.category_colors(color_by)),
+            field,
+            range: field.map_or((0.0, 1.0), |f| f.range()),
+            cmap,
+        }
+    }
+
+    pub fn color(&self, obj: &TdObject, element: usize, node: u32) -> [f32; 4] {
+        match (&self.categories, self.field) {
+            (Some(c), _) => c[element],
+            (None, Some(f)) => {
+                let c = self.cmap.map(f.values[element], self.range.0, self.range.1);
+                [c[0], c[1], c[2], 1.0]
+            }
+            (None, None) => obj.vertices[node as usize].color,
+        }
+    }
+}
+
+// a cross-section in (width, height) coordinates: counter-clockwise outer
+// loops, clockwise holes, smooth or flat shaded, and the end cap triangles
+struct Outline {
+    loops: Vec<(Vec<[f32; 2]>, bool)>,
+    caps: Vec<[[f32; 2]; 3]>,
+}
+
+fn circle(r: f32) -> Vec<[f32; 2]> {
+    (0..CIRCLE_SEGMENTS)
+        .map(|i| {
+            let (s, c) = (i as f32 / CIRCLE_SEGMENTS as f32 * TAU).sin_cos();
+            [r * c, r * s]
+        })
+        .collect()
+}
+
+fn rect_caps(lo: [f32; 2], hi: [f32; 2]) -> [[[f32; 2]; 3]; 2] {
+    [
+        [lo, [hi[0], lo[1]], hi],
+        [lo, hi, [lo[0], hi[1]]],
+    ]
+}
+
+fn outline(profile: Profile, scale: f32) -> Outline {
+    let f = |v: f64| v as f32 * scale;
+    match profile {
+        Profile::Rect { width, height } => {
+            let (w, h) = (f(width) / 2.0, f(height) / 2.0);
+            Outline {
+                loops: vec![(vec![[-w, -h], [w, -h], [w, h], [-w, h]], false)],
+                caps: rect_caps([-w, -h], [w, h]).to_vec(),
+            }
+        }
+        Profile::Circle { diameter } => {
+            let pts = circle(f(diameter) / 2.0);
+            let caps = (0..pts.len()).map(|i| [[0.0, 0.0], pts[i], pts[(i + 1) % pts.len()]]).collect();
+            Outline { loops: vec![(pts, true)], caps }
+        }
+        Profile::I { width, height, web, flange } => {
+            let (b, h, tw, tf) = (f(width) / 2.0, f(height) / 2.0, f(web) / 2.0, f(flange));
+            let pts = vec![
+                [-b, -h],
+                [b, -h],
+                [b, -h + tf],
+                [tw, -h + tf],
+                [tw, h - tf],
+                [b, h - tf],
+                [b, h],
+                [-b, h],
+                [-b, h - tf],
+                [-tw, h - tf],
+                [-tw, -h + tf],
+                [-b, -h + tf],
+            ];
+            let mut caps = rect_caps([-b, -h], [b, -h + tf]).to_vec();
+            caps.extend(rect_caps([-tw, -h + tf], [tw, h - tf]));
+            caps.extend(rect_caps([-b, h - tf], [b, h]));
+            Outline { loops: vec![(pts, false)], caps }
+        }
+        Profile::Tube { diameter, wall } => {
+            let r = f(diameter) / 2.0;
+            let outer = circle(r);
+            let inner = circle((r - f(wall)).max(0.0));
+            let n = outer.len();
+            let mut caps = Vec::new();
+            for i in 0..n {
+                let j = (i + 1) % n;
+                caps.push([outer[i], outer[j], inner[j]]);
+                caps.push([outer[i], inner[j], inner[i]]);
+            }
+            let hole = inner.into_iter().rev().collect();
+            Outline { loops: vec![(outer, true), (hole, true)], caps }
+        }
+    }
+}
+
+// outward normal of the edge a -> b of a counter-clockwise loop
+fn edge_normal(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    let (ds, dt) = (b[0] - a[0], b[1] - a[1]);
+    let l = (ds * ds + dt * dt).sqrt().max(f32::MIN_POSITIVE);
+    [dt / l, -ds / l]
+}
+
+type Frame = [[f32; 3]; 3];
+
+fn place(origin: [f32; 3], axes: &Frame, p: [f32; 2]) -> [f32; 3] {
+    std::array::from_fn(|k| origin[k] + axes[2][k] * p[0] + axes[1][k] * p[1])
+}
+
+fn turn(axes: &Frame, n: [f32; 2]) -> [f32; 3] {
+    std::array::from_fn(|k| axes[2][k] * n[0] + axes[1][k] * n[1])
+}
+
+// the outline swept from a to b, walls then the two end caps
+fn extrude(out: &mut Vec<LitVertex>, shape: &Outline, ends: [[f32; 3]; 2], axes: &Frame, colors: [[f32; 4]; 2]) {
+    let vertex = |end: usize, p: [f32; 2], normal: [f32; 3]| LitVertex {
+        position: place(ends[end], axes, p),
+        normal,
+        color: colors[end],
+    };
+    for (pts, smooth) in &shape.loops {
+        let n = pts.len();
+        let normals: Vec<[f32; 2]> = (0..n).map(|i| edge_normal(pts[i], pts[(i + 1) % n])).collect();
+        for i in 0..n {
+            let j = (i + 1) % n;
+            let (ni, nj) = if *smooth {
+                let avg = |k: usize| {
+                    let (a, b) = (normals[(k + n - 1) % n], normals[k]);
+                    [a[0] + b[0], a[1] + b[1]]
+                };
+                (turn(axes, avg(i)), turn(axes, avg(j)))
+            } else {
+                (turn(axes, normals[i]), turn(axes, normals[i]))
+            };
+            out.extend([
+                vertex(0, pts[i], ni),
+                vertex(0, pts[j], nj),
+                vertex(1, pts[j], nj),
+                vertex(0, pts[i], ni),
+                vertex(1, pts[j], nj),
+                vertex(1, pts[i], ni),
+            ]);
+        }
+    }
+    for (end, sign) in [(0, -1.0), (1, 1.0)] {
+        let normal = axes[0].map(|v| v * sign);
+        for tri in &shape.caps {
+            out.extend(tri.iter().map(|p| vertex(end, *p, normal)));
+        }
+    }
+}
+
+// line elements as solid beams: their section profile, a round bar of the
+// section area, or a thin tube when they have no section
+pub fn beams(
+    obj: &TdObject,
+    field: Option<&Field>,
+    color_by: ColorBy,
+    cmap: Colormap,
+    settings: &ProfileSettings,
+) -> Vec<LitVertex> {
+    let model = &obj.model;
+    let (_, radius) = obj.center_radius();
+    let paint = Paint::new(obj, field, color_by, cmap);
+    let mut out = Vec::new();
+    for (id, e) in obj.elements.iter().enumerate() {
+        if e.kind != ElementKind::Line2 {
+            continue;
+        }
+        let section = model.section(&e.group).ok();
+        let profile = section
+            .and_then(|s| {
+                s.profile.or((s.area > 0.0).then(|| Profile::Circle {
+                    diameter: (4.0 * s.area / std::f64::consts::PI).sqrt(),
+                }))
+            })
+            .unwrap_or(Profile::Circle {
+                diameter: (2.0 * DEFAULT_RADIUS * radius) as f64,
+            });
+        let shape = outline(profile, settings.scale);
+        let ends = [obj.display_position(e.nodes[0]), obj.display_position(e.nodes[1])].map(|p| [p[0], p[1], p[2]]);
+        let d: [f64; 3] = std::array::from_fn(|k| (ends[1][k] - ends[0][k]) as f64);
+        let len = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+        if len == 0.0 {
+            continue;
+        }
+        let axes = solver::local_axes(d.map(|v| v / len), model.orientation(&e.group)).map(|a| a.map(|v| v as f32));
+        let colors = [paint.color(obj, id, e.nodes[0]), paint.color(obj, id, e.nodes[1])];
+        extrude(&mut out, &shape, ends, &axes, colors);
+    }
+    out
+}
@@ -0,0 +1,396 @@
+use super::elements::{Element, ElementKind};
+use super::fields::{Colormap, Field, Location};
+use super::td_comp::TdObject;
+
+const HISTOGRAM_BINS: usize = 10;
+const HISTOGRAM_WIDTH: usize = 30;
+// elements that pass the threshold, so the bad ones stand out
+const PASS_COLOR: [f32; 4] = [0.75, 0.75, 0.75, 1.0];
+
+// per element measures of the mesh, NaN where one does not apply
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    AspectRatio,   // longest over shortest edge
+    Skewness,      // equiangular, 0 is ideal and 1 degenerate
+    JacobianRatio, // smallest over largest corner jacobian, negative when inverted
+    MinAngle,
+    MaxAngle,
+    Warpage, // angle between the halves of a quad face
+    Volume,  // signed, negative when the nodes are ordered inside out
+}
+
+impl Metric {
+    pub const ALL: [Metric; 7] = [
+        Metric::AspectRatio,
+        Metric::Skewness,
+        Metric::JacobianRatio,
+        Metric::MinAngle,
+        Metric::MaxAngle,
+        Metric::Warpage,
+        Metric::Volume,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::AspectRatio => "Aspect ratio",
+            Metric::Skewness => "Skewness",
+            Metric::JacobianRatio => "Jacobian ratio",
+            Metric::MinAngle => "Min angle",
+            Metric::MaxAngle => "Max angle",
+            Metric::Warpage => "Warpage",
+            Metric::Volume => "Signed volume",
+        }
+    }
+
+    fn units(self) -> &'static str {
+        match self {
+            Metric::MinAngle | Metric::MaxAngle | Metric::Warpage => "deg",
+            _ => "",
+        }
+    }
+
+    // where the common rules of thumb start calling an element bad
+    pub fn default_threshold(self) -> f32 {
+        match self {
+            Metric::AspectRatio => 5.0,
+            Metric::Skewness => 0.75,
+            Metric::JacobianRatio => 0.5,
+            Metric::MinAngle => 20.0,
+            Metric::MaxAngle => 150.0,
+            Metric::Warpage => 10.0,
+            Metric::Volume => 0.0,
+        }
+    }
+
+    // small is bad for these, large for the rest
+    fn low_is_bad(self) -> bool {
+        matches!(self, Metric::JacobianRatio | Metric::MinAngle | Metric::Volume)
+    }
+
+    pub fn is_bad(self, value: f32, threshold: f32) -> bool {
+        if self.low_is_bad() {
+            value < threshold
+        } else {
+            value > threshold
+        }
+    }
+
+    // which side of the threshold is bad, for the panel and the info output
+    pub fn bad_side(self) -> &'static str {
+        if self.low_is_bad() {
+            "below"
+        } else {
+            "above"
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QualitySettings {
+    pub metric: Metric,
+    pub highlight: bool,
+    pub threshold: f32,
+}
+
+impl Default for QualitySettings {
+    fn default() -> Self {
+        Self {
+            metric: Metric::AspectRatio,
+            highlight: false,
+            threshold: Metric::AspectRatio.default_threshold(),
+        }
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn angle(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let c = dot(a, b) / (length(a) * length(b));
+    c.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+// corner angles of a face polygon
+fn face_angles(p: &[[f32; 3]]) -> Vec<f32> {
+    let n = p.len();
+    (0..n)
+        .map(|i| angle(sub(p[(i + n - 1) % n], p[i]), sub(p[(i + 1) % n], p[i])))
+        .collect()
+}
+
+// the fold between the two triangles of a quad, the worse of both diagonals
+fn quad_warpage(p: &[[f32; 3]]) -> f32 {
+    let normal = |a: usize, b: usize, c: usize| cross(sub(p[b], p[a]), sub(p[c], p[a]));
+    let one = angle(normal(0, 1, 2), normal(0, 2, 3));
+    let other = angle(normal(0, 1, 3), normal(1, 2, 3));
+    one.max(other)
+}
+
+// corner jacobians: the edge frame at each node, on the element normal for quads
+fn corner_jacobians(kind: ElementKind, p: &[[f32; 3]]) -> Vec<f32> {
+    match kind {
+        ElementKind::Quad4 => {
+            let normal = cross(sub(p[2], p[0]), sub(p[3], p[1]));
+            (0..4)
+                .map(|i| dot(cross(sub(p[(i + 1) % 4], p[i]), sub(p[(i + 3) % 4], p[i])), normal))
+                .collect()
+        }
+        ElementKind::Hex8 => (0..8)
+            .map(|i| {
+                let (ring, level) = (i % 4, i - i % 4);
+                let next = p[level + (ring + 1) % 4];
+                let prev = p[level + (ring + 3) % 4];
+                // the top face is walked the other way seen from its neighbour below
+                let (a, b, c) = if level == 0 {
+                    (next, prev, p[i + 4])
+                } else {
+                    (prev, next, p[i - 4])
+                };
+                dot(cross(sub(a, p[i]), sub(b, p[i])), sub(c, p[i]))
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn evaluate(obj: &TdObject, e: &Element, metric: Metric) -> f32 {
+    if e.kind == ElementKind::Line2 {
+        return f32::NAN;
+    }
+    let p: Vec<[f32; 3]> = e.nodes.iter().map(|n| obj.vertices[*n as usize].position).map(|v| [v[0], v[1], v[2]]).collect();
+    let faces: Vec<Vec<[f32; 3]>> = if e.kind.is_volume() {
+        e.kind.faces().iter().map(|f| f.iter().map(|l| p[*l]).collect()).collect()
+    } else {
+        vec![p.clone()]
+    };
+    let angles = || faces.iter().flat_map(|f| face_angles(f));
+    match metric {
+        Metric::AspectRatio => {
+            let lengths = e.kind.edges().iter().map(|[a, b]| length(sub(p[*b], p[*a])));
+            let (lo, hi) = lengths.fold((f32::MAX, 0.0f32), |(lo, hi), l| (lo.min(l), hi.max(l)));
+            if lo > 0.0 {
+                hi / lo
+            } else {
+                f32::INFINITY
+            }
+        }
+        Metric::Skewness => faces
+            .iter()
+            .map(|f| {
+                let ideal = if f.len() == 3 { 60.0 } else { 90.0 };
+                let a = face_angles(f);
+                let lo = a.iter().cloned().fold(f32::MAX, f32::min);
+                let hi = a.iter().cloned().fold(0.0, f32::max);
+                ((hi - ideal) / (180.0 - ideal)).max((ideal - lo) / ideal)
+            })
+            .fold(0.0, f32::max),
+        Metric::JacobianRatio => {
+            let j = corner_jacobians(e.kind, &p);
+            if j.is_empty() {
+                return 1.0;
+            }
+            let lo = j.iter().cloned().fold(f32::MAX, f32::min);
+            let hi = j.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+            if hi > 0.0 {
+                lo / hi
+            } else {
+                0.0
+            }
+        }
+        Metric::MinAngle => angles().fold(f32::MAX, f32::min),
+        Metric::MaxAngle => angles().fold(0.0, f32::max),
+        Metric::Warpage => faces.iter().filter(|f| f.len() == 4).map(|f| quad_warpage(f)).fold(0.0, f32::max),
+        Metric::Volume => {
+            if !e.kind.is_volume() {
+                return f32::NAN;
+            }
+            e.tets()
+                .iter()
+                .map(|t| {
+                    let q = t.map(|n| obj.vertices[n as usize].position).map(|v| [v[0], v[1], v[2]]);
+                    dot(cross(sub(q[1], q[0]), sub(q[2], q[0])), sub(q[3], q[0])) / 6.0
+                })
+                .sum()
+        }
+    }
+}
+
+// one metric over all elements, as an element field
+pub fn field(obj: &TdObject, metric: Metric) -> Field {
+    Field {
+        name: metric.name().to_string(),
+        units: metric.units().to_string(),
+        location: Location::Element,
+        values: obj.elements.iter().map(|e| evaluate(obj, e, metric)).collect(),
+        layer: None,
+    }
+}
+
+// the metric a field was made from, when it is one
+pub fn metric_of(field: &Field) -> Option<Metric> {
+    Metric::ALL.into_iter().find(|m| field.location == Location::Element && field.name == m.name())
+}
+
+pub fn bad_count(values: &[f32], metric: Metric, threshold: f32) -> usize {
+    values.iter().filter(|v| metric.is_bad(**v, threshold)).count()
+}
+
+// element colors from the field, the passing elements greyed out
+pub fn colors(field: &Field, metric: Metric, threshold: f32, cmap: Colormap) -> Vec<[f32; 4]> {
+    let (lo, hi) = field.range();
+    field
+        .values
+        .iter()
+        .map(|v| {
+            if !metric.is_bad(*v, threshold) {
+                return PASS_COLOR;
+            }
+            let c = cmap.map(*v, lo, hi);
+            [c[0], c[1], c[2], 1.0]
+        })
+        .collect()
+}
+
+// counts in equal bins over the finite values
+fn histogram(values: &[f32]) -> Option<(f32, f32, [usize; HISTOGRAM_BINS])> {
+    let finite: Vec<f32> = values.iter().cloned().filter(|v| v.is_finite()).collect();
+    let lo = finite.iter().cloned().reduce(f32::min)?;
+    let hi = finite.iter().cloned().fold(lo, f32::max);
+    let mut bins = [0; HISTOGRAM_BINS];
+    for v in finite {
+        let t = if hi > lo { (v - lo) / (hi - lo) } else { 0.0 };
+        bins[((t * HISTOGRAM_BINS as f32) as usize).min(HISTOGRAM_BINS - 1)] += 1;
+    }
+    Some((lo, hi, bins))
+}
+
+// every metric with its range, bad count and a text histogram
+pub fn summary(obj: &TdObject) -> String {
+    let mut out = String::new();
+    for metric in Metric::ALL {
+        let values = field(obj, metric).values;
+        let Some((lo, hi, bins)) = histogram(&values) else {
+            continue;
+        };
+        let threshold = metric.default_threshold();
+        let infinite = values.iter().filter(|v| v.is_infinite()).count();
+        out += &format!(
+            "quality {}: {} .. {}{}, {} {} {}{}\n",
+            metric.name().to_lowercase(),
+            lo,
+            hi,
+            if metric.units().is_empty() { String::new() } else { format!(" {}", metric.units()) },
+            bad_count(&values, metric, threshold),
+            metric.bad_side(),
+            threshold,
+            if infinite > 0 { format!(", {} degenerate", infinite) } else { String::new() },
+        );
+        if hi == lo {
+            continue;
+        }
+        let most = bins.iter().cloned().max().unwrap_or(0).max(1);
+        let width = (hi - lo) / HISTOGRAM_BINS as f32;
+        for (i, n) in bins.iter().enumerate() {
+            let from = lo + i as f32 * width;
+            out += &format!(
+                "  {:>10.4} .. {:<10.4} {:<w$} {}\n",
+                from,
+                from + width,
+                "#".repeat(n * HISTOGRAM_WIDTH / most),
+                n,
+                w = HISTOGRAM_WIDTH
+            );
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // an equilateral tri, a unit square, an inverted tet and a tri with two
+    // nodes on the same spot
+    fn shapes() -> TdObject {
+        let h = 3.0f32.sqrt() / 2.0;
+        let doc = json!({
+            "vers": [
+                [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.5, h, 0.0],
+                [2.0, 0.0, 0.0], [3.0, 0.0, 0.0], [3.0, 1.0, 0.0], [2.0, 1.0, 0.0],
+                [0.0, 0.0, 5.0], [1.0, 0.0, 5.0], [0.0, 1.0, 5.0], [0.0, 0.0, 6.0],
+                [5.0, 5.0, 5.0], [5.0, 5.0, 5.0], [6.0, 5.0, 5.0],
+            ],
+            "elems": [
+                { "kind": "tri3", "nodes": [0, 1, 2] },
+                { "kind": "quad4", "nodes": [3, 4, 5, 6] },
+                { "kind": "tet4", "nodes": [7, 9, 8, 10] },
+                { "kind": "tri3", "nodes": [11, 12, 13] },
+            ],
+        });
+        TdObject::from_json(&doc.to_string())
+    }
+
+    fn value(obj: &TdObject, element: usize, metric: Metric) -> f32 {
+        evaluate(obj, &obj.elements[element], metric)
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn equilateral_triangle() {
+        let obj = shapes();
+        assert!(close(value(&obj, 0, Metric::AspectRatio), 1.0));
+        assert!(close(value(&obj, 0, Metric::Skewness), 0.0));
+        assert!(close(value(&obj, 0, Metric::MinAngle), 60.0));
+        assert!(close(value(&obj, 0, Metric::MaxAngle), 60.0));
+        assert!(value(&obj, 0, Metric::Volume).is_nan());
+    }
+
+    #[test]
+    fn unit_square() {
+        let obj = shapes();
+        assert!(close(value(&obj, 1, Metric::JacobianRatio), 1.0));
+        assert!(close(value(&obj, 1, Metric::Warpage), 0.0));
+        assert!(close(value(&obj, 1, Metric::Skewness), 0.0));
+    }
+
+    #[test]
+    fn inverted_tet_has_negative_volume() {
+        let obj = shapes();
+        assert!(close(value(&obj, 2, Metric::Volume), -1.0 / 6.0));
+        assert!(Metric::Volume.is_bad(value(&obj, 2, Metric::Volume), Metric::Volume.default_threshold()));
+    }
+
+    #[test]
+    fn degenerate_edge_has_infinite_aspect_ratio() {
+        let obj = shapes();
+        assert_eq!(value(&obj, 3, Metric::AspectRatio), f32::INFINITY);
+    }
+
+    #[test]
+    fn histogram_bins() {
+        let values: Vec<f32> = (0..10).map(|i| i as f32).chain([f32::NAN, f32::INFINITY]).collect();
+        let (lo, hi, bins) = histogram(&values).unwrap();
+        assert_eq!((lo, hi), (0.0, 9.0));
+        assert_eq!(bins, [1; HISTOGRAM_BINS]);
+        let (_, _, bins) = histogram(&[2.0, 2.0, 2.0]).unwrap();
+        assert_eq!(bins[0], 3);
+        assert!(histogram(&[f32::NAN]).is_none());
+    }
+}
@@ -0,0 +1,149 @@
+use std::f32::consts::TAU;
+
+use super::deform;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sequence {
+    Steps, // each time step or mode in turn
+    Mode,  // one back and forth of the deformation, per step when there are steps
+    Orbit, // the camera once around the model
+}
+
+impl Sequence {
+    pub fn parse(name: &str) -> Option<Sequence> {
+        match name {
+            "steps" => Some(Sequence::Steps),
+            "mode" | "modes" => Some(Sequence::Mode),
+            "orbit" => Some(Sequence::Orbit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordSettings {
+    pub sequence: Sequence,
+    pub dir: String, // frames go in here as frame_00000.png, ...
+    pub size: [u32; 2],
+    pub fps: u32,
+    pub seconds: f32, // length of an orbit
+}
+
+impl Default for RecordSettings {
+    fn default() -> Self {
+        Self { sequence: Sequence::Orbit, dir: "frames".to_string(), size: [1280, 720], fps: 30, seconds: 6.0 }
+    }
+}
+
+// what one frame shows, all from the frame number so a sequence renders the
+// same every time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub step: Option<usize>,
+    pub time: f32, // seconds into the deformation swing
+    pub angle: f32, // of the orbit, radians
+}
+
+fn frame_count(seconds: f32, fps: u32) -> usize {
+    ((seconds * fps as f32).round() as usize).max(1)
+}
+
+pub fn frames(settings: &RecordSettings, steps: usize) -> Vec<Frame> {
+    let dt = 1.0 / settings.fps.max(1) as f32;
+    let still = Frame { step: None, time: 0.0, angle: 0.0 };
+    match settings.sequence {
+        Sequence::Steps => {
+            let per_step = frame_count(deform::STEP_INTERVAL, settings.fps);
+            (0..steps * per_step).map(|i| Frame { step: Some(i / per_step), ..still }).collect()
+        }
+        Sequence::Mode => {
+            let per_swing = frame_count(deform::PERIOD, settings.fps);
+            let swings = steps.max(1);
+            (0..swings * per_swing)
+                .map(|i| Frame {
+                    step: (steps > 0).then_some(i / per_swing),
+                    time: (i % per_swing) as f32 * dt,
+                    ..still
+                })
+                .collect()
+        }
+        Sequence::Orbit => {
+            let count = frame_count(settings.seconds, settings.fps);
+            (0..count).map(|i| Frame { angle: TAU * i as f32 / count as f32, ..still }).collect()
+        }
+    }
+}
+
+pub fn filename(dir: &str, frame: usize) -> String {
+    format!("{}/frame_{:05}.png", dir.trim_end_matches('/'), frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(sequence: Sequence, fps: u32, seconds: f32) -> RecordSettings {
+        RecordSettings { sequence, fps, seconds, ..Default::default() }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn only_fps_seconds_and_sequence_matter() {
+        for sequence in [Sequence::Steps, Sequence::Mode, Sequence::Orbit] {
+            let a = settings(sequence, 24, 2.0);
+            let b = RecordSettings { dir: "elsewhere".to_string(), size: [64, 48], ..a.clone() };
+            assert_eq!(frames(&a, 3), frames(&a, 3));
+            assert_eq!(frames(&a, 3), frames(&b, 3));
+        }
+    }
+
+    #[test]
+    fn steps_hold_each_step_for_the_step_interval() {
+        let per_step = (deform::STEP_INTERVAL * 30.0).round() as usize;
+        let f = frames(&settings(Sequence::Steps, 30, 6.0), 3);
+        assert_eq!(f.len(), 3 * per_step);
+        assert_eq!(f[0], Frame { step: Some(0), time: 0.0, angle: 0.0 });
+        assert_eq!(f[per_step - 1].step, Some(0));
+        assert_eq!(f[per_step].step, Some(1));
+        assert_eq!(f.last().unwrap().step, Some(2));
+        assert!(f.iter().all(|f| f.time == 0.0 && f.angle == 0.0));
+
+        let single = frames(&settings(Sequence::Steps, 30, 6.0), 1);
+        assert_eq!(single.len(), per_step);
+        assert!(single.iter().all(|f| f.step == Some(0)));
+        assert!(frames(&settings(Sequence::Steps, 30, 6.0), 0).is_empty());
+    }
+
+    #[test]
+    fn mode_swings_once_per_step() {
+        let per_swing = (deform::PERIOD * 30.0).round() as usize;
+        let f = frames(&settings(Sequence::Mode, 30, 6.0), 0);
+        assert_eq!(f.len(), per_swing);
+        assert_eq!(f[0], Frame { step: None, time: 0.0, angle: 0.0 });
+        assert!(close(f.last().unwrap().time, (per_swing - 1) as f32 / 30.0));
+        assert!(f.iter().all(|f| f.step.is_none() && f.angle == 0.0));
+
+        let f = frames(&settings(Sequence::Mode, 30, 6.0), 2);
+        assert_eq!(f.len(), 2 * per_swing);
+        assert_eq!(f[per_swing], Frame { step: Some(1), time: 0.0, angle: 0.0 });
+        assert_eq!(f.last().unwrap().step, Some(1));
+
+        let single = frames(&settings(Sequence::Mode, 30, 6.0), 1);
+        assert_eq!(single.len(), per_swing);
+        assert!(single.iter().all(|f| f.step == Some(0)));
+    }
+
+    #[test]
+    fn orbit_goes_once_around_without_repeating_the_start() {
+        let f = frames(&settings(Sequence::Orbit, 30, 6.0), 3);
+        assert_eq!(f.len(), 180);
+        assert_eq!(f[0], Frame { step: None, time: 0.0, angle: 0.0 });
+        assert!(close(f.last().unwrap().angle, TAU * 179.0 / 180.0));
+        assert!(f.iter().all(|f| f.step.is_none() && f.time == 0.0));
+        // too short for a whole frame still gives one
+        assert_eq!(frames(&settings(Sequence::Orbit, 30, 0.001), 0).len(), 1);
+    }
+}
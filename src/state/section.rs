@@ -0,0 +1,116 @@
+use bytemuck::{Pod, Zeroable};
+
+use super::fields::{Colormap, Field};
+use super::march::marching_tets;
+use super::td_comp::{TdObject, Vertex};
+
+pub const MAX_CLIP_PLANES: usize = 4;
+const CAP_COLOR: [f32; 3] = [0.75, 0.75, 0.75];
+
+// the normal points into the half that gets cut away
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipPlane {
+    pub enabled: bool,
+    pub azimuth: f32,   // degrees around y
+    pub elevation: f32, // degrees from the xz plane
+    pub offset: f32,    // -1..1 of the model radius from its center
+}
+
+impl ClipPlane {
+    fn new(azimuth: f32, elevation: f32) -> Self {
+        Self {
+            enabled: false,
+            azimuth,
+            elevation,
+            offset: 0.0,
+        }
+    }
+
+    pub fn normal(&self) -> [f32; 3] {
+        let (sin_a, cos_a) = self.azimuth.to_radians().sin_cos();
+        let (sin_e, cos_e) = self.elevation.to_radians().sin_cos();
+        [cos_e * cos_a, sin_e, cos_e * sin_a]
+    }
+
+    // (n, d) with n.p + d > 0 on the removed side
+    pub fn equation(&self, center: [f32; 3], radius: f32) -> [f32; 4] {
+        let n = self.normal();
+        let p = [
+            center[0] + n[0] * self.offset * radius,
+            center[1] + n[1] * self.offset * radius,
+            center[2] + n[2] * self.offset * radius,
+        ];
+        [n[0], n[1], n[2], -(n[0] * p[0] + n[1] * p[1] + n[2] * p[2])]
+    }
+}
+
+// one plane per axis plus a spare facing -x
+pub fn default_planes() -> [ClipPlane; MAX_CLIP_PLANES] {
+    [
+        ClipPlane::new(0.0, 0.0),
+        ClipPlane::new(0.0, 90.0),
+        ClipPlane::new(90.0, 0.0),
+        ClipPlane::new(180.0, 0.0),
+    ]
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct ClipUniform {
+    planes: [[f32; 4]; MAX_CLIP_PLANES],
+    params: [f32; 4], // x: active plane count, y: tolerance
+}
+
+impl ClipUniform {
+    pub fn new(planes: &[ClipPlane], center: [f32; 3], radius: f32) -> Self {
+        let mut uniform = Self::zeroed();
+        let mut count = 0;
+        for p in planes.iter().filter(|p| p.enabled) {
+            uniform.planes[count] = p.equation(center, radius);
+            count += 1;
+        }
+        // caps lie exactly on their plane and must survive it
+        uniform.params = [count as f32, radius * 1e-4, 0.0, 0.0];
+        uniform
+    }
+}
+
+// cut faces of the volume elements, as a plain triangle list
+pub fn caps(
+    obj: &TdObject,
+    planes: &[ClipPlane],
+    field: Option<&Field>,
+    cmap: Colormap,
+) -> Vec<Vertex> {
+    let (center, radius) = obj.center_radius();
+    let tets = obj.tets();
+    if tets.is_empty() {
+        return Vec::new();
+    }
+    let positions = obj.display_positions();
+    let range = field.map(|f| f.range());
+    let values = field.map(|f| obj.nodal_values(f, true));
+
+    let mut out = Vec::new();
+    for plane in planes.iter().filter(|p| p.enabled) {
+        let eq = plane.equation(center, radius);
+        let dist: Vec<f32> = positions
+            .iter()
+            .map(|p| eq[0] * p[0] + eq[1] * p[1] + eq[2] * p[2] + eq[3])
+            .collect();
+        for tri in marching_tets(&tets, &dist, 0.0) {
+            for ep in tri {
+                let p = ep.lerp3(&positions);
+                let c = match (&values, range) {
+                    (Some(values), Some((lo, hi))) => cmap.map(ep.lerp(values), lo, hi),
+                    _ => CAP_COLOR,
+                };
+                out.push(Vertex {
+                    position: [p[0], p[1], p[2], 1.0],
+                    color: [c[0], c[1], c[2], 1.0],
+                });
+            }
+        }
+    }
+    out
+}
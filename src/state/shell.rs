@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use super::elements::ElementKind;
+use super::fields::{ColorBy, Colormap, Field};
+use super::profile::{LitVertex, Paint};
+use super::td_comp::TdObject;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShellSettings {
+    pub extrude: bool,
+    pub scale: f32, // 1 is the true thickness
+}
+
+impl Default for ShellSettings {
+    fn default() -> Self {
+        Self { extrude: false, scale: 1.0 }
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn unit(a: [f32; 3]) -> Option<[f32; 3]> {
+    let l = dot(a, a).sqrt();
+    (l > 0.0).then(|| a.map(|v| v / l))
+}
+
+fn offset(p: [f32; 3], n: [f32; 3], d: f32) -> [f32; 3] {
+    std::array::from_fn(|k| p[k] + n[k] * d)
+}
+
+// thickness of the section of each surface element, none when it has no section
+pub fn thicknesses(obj: &TdObject) -> Vec<Option<f32>> {
+    obj.elements
+        .iter()
+        .map(|e| match e.kind {
+            ElementKind::Tri3 | ElementKind::Quad4 => obj
+                .model
+                .section(&e.group)
+                .ok()
+                .map(|s| s.thickness as f32)
+                .filter(|t| *t > 0.0),
+            _ => None,
+        })
+        .collect()
+}
+
+// surface elements with a thickness as solid slabs: top and bottom offset
+// along the averaged node normals, walls on the free edges
+pub fn slabs(
+    obj: &TdObject,
+    field: Option<&Field>,
+    color_by: ColorBy,
+    cmap: Colormap,
+    settings: &ShellSettings,
+) -> Vec<LitVertex> {
+    let shells: Vec<(usize, f32)> = thicknesses(obj)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(id, t)| Some((id, t? * settings.scale)))
+        .collect();
+    let position = |n: u32| {
+        let p = obj.display_position(n);
+        [p[0], p[1], p[2]]
+    };
+    let normal = |id: usize| {
+        let p: Vec<[f32; 3]> = obj.elements[id].nodes.iter().map(|n| position(*n)).collect();
+        // the cross of the diagonals for quads, same as the edges for tris
+        let (a, b) = match p.len() {
+            4 => (sub(p[2], p[0]), sub(p[3], p[1])),
+            _ => (sub(p[1], p[0]), sub(p[2], p[0])),
+        };
+        unit(cross(a, b))
+    };
+
+    let mut node_normals: HashMap<u32, [f32; 3]> = HashMap::new();
+    let mut edge_count: HashMap<[u32; 2], usize> = HashMap::new();
+    for (id, _) in &shells {
+        let e = &obj.elements[*id];
+        let Some(n) = normal(*id) else {
+            continue;
+        };
+        for node in &e.nodes {
+            let sum = node_normals.entry(*node).or_insert([0.0; 3]);
+            for k in 0..3 {
+                sum[k] += n[k];
+            }
+        }
+        for [a, b] in e.kind.edges() {
+            let (a, b) = (e.nodes[*a], e.nodes[*b]);
+            *edge_count.entry([a.min(b), a.max(b)]).or_insert(0) += 1;
+        }
+    }
+
+    let paint = Paint::new(obj, field, color_by, cmap);
+    let mut out = Vec::new();
+    for (id, t) in shells {
+        let e = &obj.elements[id];
+        let Some(face_normal) = normal(id) else {
+            continue;
+        };
+        let corner = |node: u32, side: f32| {
+            let n = node_normals.get(&node).and_then(|n| unit(*n)).unwrap_or(face_normal);
+            let n = if dot(n, face_normal) < 0.0 { n.map(|v| -v) } else { n };
+            LitVertex {
+                position: offset(position(node), n, side * t / 2.0),
+                normal: n.map(|v| v * side),
+                color: paint.color(obj, id, node),
+            }
+        };
+        // quads as two triangles, the bottom wound the other way
+        let tris: &[[usize; 3]] = match e.kind {
+            ElementKind::Quad4 => &[[0, 1, 2], [0, 2, 3]],
+            _ => &[[0, 1, 2]],
+        };
+        for tri in tris {
+            out.extend(tri.iter().map(|l| corner(e.nodes[*l], 1.0)));
+            out.extend(tri.iter().rev().map(|l| corner(e.nodes[*l], -1.0)));
+        }
+
+        let mut center = [0.0; 3];
+        for node in &e.nodes {
+            center = offset(center, position(*node), 1.0 / e.nodes.len() as f32);
+        }
+        for [a, b] in e.kind.edges() {
+            let (a, b) = (e.nodes[*a], e.nodes[*b]);
+            if edge_count.get(&[a.min(b), a.max(b)]) != Some(&1) {
+                continue;
+            }
+            let Some(mut wall) = unit(cross(sub(position(b), position(a)), face_normal)) else {
+                continue;
+            };
+            if dot(wall, sub(position(a), center)) < 0.0 {
+                wall = wall.map(|v| -v);
+            }
+            let [a0, a1, b0, b1] = [corner(a, -1.0), corner(a, 1.0), corner(b, -1.0), corner(b, 1.0)]
+                .map(|v| LitVertex { normal: wall, ..v });
+            out.extend([a0, b0, b1, a0, b1, a1]);
+        }
+    }
+    out
+}
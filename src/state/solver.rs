@@ -0,0 +1,519 @@
+use super::elements::ElementKind;
+use super::fields::{Field, Location, Step, VectorField};
+use super::model::ModelData;
+use super::td_comp::TdObject;
+use super::tensor::{Quantity, TensorField};
+
+use self::linalg::Dense;
+use self::sparse::Assembly;
+
+mod elastic;
+mod heat;
+mod linalg;
+mod modal;
+mod sparse;
+mod truss;
+
+pub use self::truss::local_axes;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Analysis {
+    Truss, // pinned bars, 3 dofs per node
+    Frame, // euler-bernoulli beams, 6 dofs per node
+    PlaneStress, // cst triangles in the xy plane, 2 dofs per node
+    PlaneStrain,
+    Solid, // linear tets, 3 dofs per node
+    Heat,  // steady conduction on tets or else triangles, 1 dof per node
+}
+
+impl Analysis {
+    pub fn dofs_per_node(self) -> usize {
+        match self {
+            Analysis::Truss | Analysis::Solid => 3,
+            Analysis::Frame => 6,
+            Analysis::PlaneStress | Analysis::PlaneStrain => 2,
+            Analysis::Heat => 1,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Analysis::Truss => "Truss",
+            Analysis::Frame => "Frame",
+            Analysis::PlaneStress => "Plane stress",
+            Analysis::PlaneStrain => "Plane strain",
+            Analysis::Solid => "Solid",
+            Analysis::Heat => "Heat conduction",
+        }
+    }
+}
+
+pub const ANALYSES: [Analysis; 6] = [
+    Analysis::Truss,
+    Analysis::Frame,
+    Analysis::PlaneStress,
+    Analysis::PlaneStrain,
+    Analysis::Solid,
+    Analysis::Heat,
+];
+
+// what a solve adds to the model, replacing results of the same name
+pub struct Results {
+    pub fields: Vec<Field>,
+    pub vectors: Vec<VectorField>,
+    pub tensors: Vec<TensorField>,
+    pub steps: Vec<Step>,
+    pub summary: String,
+}
+
+const DOF_NAMES: [&str; 6] = ["ux", "uy", "uz", "rx", "ry", "rz"];
+
+// K u = f with some dofs prescribed, returns u and the reactions K u - f.
+// dofs nothing is attached to (no stiffness, e.g. out of plane) are held at 0
+fn solve_system(
+    k: &Dense,
+    f: &[f64],
+    prescribed: &[Option<f64>],
+    dofs_per_node: usize,
+) -> Result<(Vec<f64>, Vec<f64>), String> {
+    let n = k.n;
+    let mut u: Vec<f64> = prescribed.iter().map(|p| p.unwrap_or(0.0)).collect();
+    let free: Vec<usize> = (0..n)
+        .filter(|i| prescribed[*i].is_none() && k.get(*i, *i) != 0.0)
+        .collect();
+    let mut kff = Dense::new(free.len());
+    let mut ff = vec![0.0; free.len()];
+    for (a, i) in free.iter().enumerate() {
+        ff[a] = f[*i];
+        for j in 0..n {
+            if prescribed[j].is_some() {
+                ff[a] -= k.get(*i, j) * u[j];
+            }
+        }
+        for (b, j) in free.iter().enumerate() {
+            kff.add(a, b, k.get(*i, *j));
+        }
+    }
+    let uf = linalg::cholesky_solve(&kff, &ff).map_err(|a| {
+        let dof = free[a];
+        format!(
+            "stiffness is singular at node {} {} (mechanism or missing supports)",
+            dof / dofs_per_node,
+            DOF_NAMES[dof % dofs_per_node]
+        )
+    })?;
+    for (a, i) in free.iter().enumerate() {
+        u[*i] = uf[a];
+    }
+    let ku = k.mul(&u);
+    let reactions = ku.iter().zip(f).map(|(a, b)| a - b).collect();
+    Ok((u, reactions))
+}
+
+fn node_vectors(name: &str, data: &[f64], dofs_per_node: usize, offset: usize) -> VectorField {
+    VectorField {
+        name: name.to_string(),
+        units: String::new(),
+        location: Location::Node,
+        values: data
+            .chunks_exact(dofs_per_node)
+            .map(|d| std::array::from_fn(|i| d.get(offset + i).copied().unwrap_or(0.0) as f32))
+            .collect(),
+    }
+}
+
+// element kind each structural analysis works on, heat picks its own
+fn element_kind(analysis: Analysis) -> ElementKind {
+    match analysis {
+        Analysis::Truss | Analysis::Frame => ElementKind::Line2,
+        Analysis::PlaneStress | Analysis::PlaneStrain => ElementKind::Tri3,
+        Analysis::Solid | Analysis::Heat => ElementKind::Tet4,
+    }
+}
+
+fn elements_of(obj: &TdObject, analysis: Analysis) -> Result<Vec<usize>, String> {
+    let kind = element_kind(analysis);
+    let used: Vec<usize> = (0..obj.elements.len())
+        .filter(|i| obj.elements[*i].kind == kind)
+        .collect();
+    if used.is_empty() {
+        return Err(format!("the model has no {} elements", kind.name()));
+    }
+    Ok(used)
+}
+
+fn element_dofs(nodes: &[u32], dofs_per_node: usize) -> Vec<usize> {
+    nodes
+        .iter()
+        .flat_map(|a| (0..dofs_per_node).map(move |d| *a as usize * dofs_per_node + d))
+        .collect()
+}
+
+fn rows<const N: usize>(m: [[f64; N]; N]) -> Vec<Vec<f64>> {
+    m.iter().map(|r| r.to_vec()).collect()
+}
+
+// global dofs and stiffness of one element
+fn element_stiffness(
+    obj: &TdObject,
+    analysis: Analysis,
+    id: usize,
+    pos: &[[f64; 3]],
+) -> Result<(Vec<usize>, Vec<Vec<f64>>), String> {
+    let model = &obj.model;
+    let e = &obj.elements[id];
+    let mat = model.material(&e.group)?;
+    let p = |i: usize| pos[e.nodes[i] as usize];
+    let ke = match analysis {
+        Analysis::Truss | Analysis::Frame => {
+            let sec = model.section(&e.group)?;
+            let (dir, len) = truss::axis(p(0), p(1));
+            if len == 0.0 {
+                return Err(format!("element {} has zero length", id));
+            }
+            if analysis == Analysis::Truss {
+                rows(truss::bar_stiffness(dir, len, mat.e * sec.area))
+            } else {
+                let t = truss::frame_transform(truss::local_axes(dir, model.orientation(&e.group)));
+                rows(truss::to_global(&truss::frame_local(len, mat, sec), &t))
+            }
+        }
+        Analysis::PlaneStress | Analysis::PlaneStrain => {
+            let (b, area) = elastic::cst_b([p(0), p(1), p(2)]);
+            if area == 0.0 {
+                return Err(format!("element {} has zero area", id));
+            }
+            let d = elastic::plane_d(mat, analysis == Analysis::PlaneStrain);
+            rows(elastic::stiffness(&b, &d, area * thickness(model, &e.group)))
+        }
+        Analysis::Solid | Analysis::Heat => {
+            let (b, volume) = elastic::tet_b([p(0), p(1), p(2), p(3)]);
+            if volume == 0.0 {
+                return Err(format!("element {} has zero volume", id));
+            }
+            rows(elastic::stiffness(&b, &elastic::solid_d(mat), volume.abs()))
+        }
+    };
+    Ok((element_dofs(&e.nodes, analysis.dofs_per_node()), ke))
+}
+
+pub fn modes(obj: &TdObject, analysis: Analysis, count: usize, lumped: bool) -> Result<Results, String> {
+    if analysis == Analysis::Heat {
+        return Err("modes need a structural analysis".to_string());
+    }
+    modal::solve(obj, analysis, count, lumped)
+}
+
+pub fn solve(obj: &TdObject, analysis: Analysis) -> Result<Results, String> {
+    match analysis {
+        Analysis::Truss | Analysis::Frame => solve_lines(obj, analysis),
+        Analysis::Heat => heat::solve(obj),
+        _ => solve_continuum(obj, analysis),
+    }
+}
+
+// static solve of the line2 elements as bars or frames
+fn solve_lines(obj: &TdObject, analysis: Analysis) -> Result<Results, String> {
+    let model = &obj.model;
+    let nd = analysis.dofs_per_node();
+    let n = obj.vertices.len() * nd;
+    let pos: Vec<[f64; 3]> = obj
+        .positions()
+        .iter()
+        .map(|p| p.map(|v| v as f64))
+        .collect();
+    let lines = elements_of(obj, analysis)?;
+
+    let mut k = Dense::new(n);
+    for &id in &lines {
+        let (dofs, ke) = element_stiffness(obj, analysis, id, &pos)?;
+        for (i, row) in ke.iter().enumerate() {
+            for (j, v) in row.iter().enumerate() {
+                k.add(dofs[i], dofs[j], *v);
+            }
+        }
+    }
+
+    let mut f = vec![0.0; n];
+    for load in &model.loads {
+        let base = load.node as usize * nd;
+        if base >= n {
+            return Err(format!("load on missing node {}", load.node));
+        }
+        for d in 0..3 {
+            f[base + d] += load.force[d];
+            if nd == 6 {
+                f[base + 3 + d] += load.moment[d];
+            }
+        }
+    }
+    let mut prescribed = vec![None; n];
+    for s in &model.supports {
+        let base = s.node as usize * nd;
+        if base >= n {
+            return Err(format!("support on missing node {}", s.node));
+        }
+        // a later support on the node only adds to what is fixed
+        for d in 0..nd {
+            if let Some(value) = s.prescribed(d) {
+                prescribed[base + d] = Some(value);
+            }
+        }
+    }
+
+    let (u, reactions) = solve_system(&k, &f, &prescribed, nd)?;
+
+    // element results, zero on elements that are not lines
+    let mut axial = vec![0.0; obj.elements.len()];
+    let mut moment = vec![0.0; obj.elements.len()];
+    for &id in &lines {
+        let e = &obj.elements[id];
+        let (mat, sec) = (model.material(&e.group)?, model.section(&e.group)?);
+        let (a, b) = (e.nodes[0] as usize, e.nodes[1] as usize);
+        let (dir, len) = truss::axis(pos[a], pos[b]);
+        match analysis {
+            Analysis::Truss => {
+                let u1 = [u[a * 3], u[a * 3 + 1], u[a * 3 + 2]];
+                let u2 = [u[b * 3], u[b * 3 + 1], u[b * 3 + 2]];
+                axial[id] = truss::bar_force(dir, len, mat.e * sec.area, u1, u2) as f32;
+            }
+            _ => {
+                let t = truss::frame_transform(truss::local_axes(dir, model.orientation(&e.group)));
+                let ue: [f64; 12] = std::array::from_fn(|i| {
+                    if i < 6 { u[a * 6 + i] } else { u[b * 6 + i - 6] }
+                });
+                let fl = truss::frame_end_forces(&truss::frame_local(len, mat, sec), &t, &ue);
+                axial[id] = fl[6] as f32;
+                let m1 = (fl[4] * fl[4] + fl[5] * fl[5]).sqrt();
+                let m2 = (fl[10] * fl[10] + fl[11] * fl[11]).sqrt();
+                moment[id] = m1.max(m2) as f32;
+            }
+        }
+    }
+
+    let displacement = node_vectors("Displacement", &u, nd, 0);
+    let max_disp = displacement
+        .magnitudes()
+        .into_iter()
+        .fold(0.0, f32::max);
+    let mut fields = vec![
+        Field {
+            name: "Displacement magnitude".to_string(),
+            units: String::new(),
+            location: Location::Node,
+            values: displacement.magnitudes(),
+            layer: None,
+        },
+        Field {
+            name: "Axial force".to_string(),
+            units: String::new(),
+            location: Location::Element,
+            values: axial,
+            layer: None,
+        },
+    ];
+    let mut vectors = vec![displacement, node_vectors("Reaction force", &reactions, nd, 0)];
+    if analysis == Analysis::Frame {
+        fields.push(Field {
+            name: "Bending moment".to_string(),
+            units: String::new(),
+            location: Location::Element,
+            values: moment,
+            layer: None,
+        });
+        vectors.push(node_vectors("Rotation", &u, nd, 3));
+    }
+    Ok(Results {
+        fields,
+        vectors,
+        tensors: Vec::new(),
+        steps: Vec::new(),
+        summary: format!(
+            "{} line elements, {} dofs, max displacement {:.4e}",
+            lines.len(),
+            n,
+            max_disp
+        ),
+    })
+}
+
+fn nodal_loads(model: &ModelData, nd: usize, n: usize) -> Result<Vec<f64>, String> {
+    let mut f = vec![0.0; n];
+    for load in &model.loads {
+        let base = load.node as usize * nd;
+        if base >= n {
+            return Err(format!("load on missing node {}", load.node));
+        }
+        for d in 0..nd.min(3) {
+            f[base + d] += load.force[d];
+        }
+    }
+    Ok(f)
+}
+
+// of plane elements, from the group section
+fn thickness(model: &ModelData, group: &str) -> f64 {
+    match model.section(group) {
+        Ok(s) if s.thickness > 0.0 => s.thickness,
+        _ => 1.0,
+    }
+}
+
+// outward normal times area of an element face (an edge of a triangle
+// in the xy plane), and the face nodes
+fn face_load(obj: &TdObject, id: usize, face: usize) -> Option<([f64; 3], Vec<u32>)> {
+    let e = obj.elements.get(id)?;
+    if !matches!(e.kind, ElementKind::Tri3 | ElementKind::Tet4) {
+        return None;
+    }
+    let nodes = obj.face_nodes(id, face)?;
+    let pos = |i: u32| -> [f64; 3] { std::array::from_fn(|a| obj.vertices[i as usize].position[a] as f64) };
+    let p: Vec<[f64; 3]> = nodes.iter().map(|i| pos(*i)).collect();
+    let mut n = if nodes.len() == 2 {
+        [p[1][1] - p[0][1], p[0][0] - p[1][0], 0.0]
+    } else {
+        let (a, b) = (truss::sub(p[1], p[0]), truss::sub(p[2], p[0]));
+        truss::cross(a, b).map(|v| v / 2.0)
+    };
+    // flip to point away from the rest of the element
+    let c = obj.centroid(e).map(|v| v as f64);
+    if truss::dot(n, truss::sub(p[0], c)) < 0.0 {
+        n = n.map(|v| -v);
+    }
+    Some((n, nodes))
+}
+
+// static solve of the triangles (plane problems) or tets (solid) with
+// sparse assembly and conjugate gradients
+fn solve_continuum(obj: &TdObject, analysis: Analysis) -> Result<Results, String> {
+    let model = &obj.model;
+    let nd = analysis.dofs_per_node();
+    let n = obj.vertices.len() * nd;
+    let kind = element_kind(analysis);
+    let pos: Vec<[f64; 3]> = obj
+        .positions()
+        .iter()
+        .map(|p| p.map(|v| v as f64))
+        .collect();
+    let used = elements_of(obj, analysis)?;
+
+    let mut k = Assembly::new(n);
+    for &id in &used {
+        let (dofs, ke) = element_stiffness(obj, analysis, id, &pos)?;
+        for (i, row) in ke.iter().enumerate() {
+            for (j, v) in row.iter().enumerate() {
+                k.add(dofs[i], dofs[j], *v);
+            }
+        }
+    }
+    let k = k.to_csr();
+
+    let mut f = nodal_loads(model, nd, n)?;
+    for pressure in &model.pressures {
+        let (area, nodes) = face_load(obj, pressure.element as usize, pressure.face)
+            .ok_or(format!("no face {} on element {}", pressure.face, pressure.element))?;
+        let t = if nd == 2 { thickness(model, &obj.elements[pressure.element as usize].group) } else { 1.0 };
+        for node in &nodes {
+            for d in 0..nd {
+                f[*node as usize * nd + d] -= pressure.value * area[d] * t / nodes.len() as f64;
+            }
+        }
+    }
+    let mut known = vec![None; n];
+    for s in &model.supports {
+        let base = s.node as usize * nd;
+        if base >= n {
+            return Err(format!("support on missing node {}", s.node));
+        }
+        for (d, known) in known[base..base + nd].iter_mut().enumerate() {
+            if let Some(value) = s.prescribed(d) {
+                *known = Some(value);
+            }
+        }
+    }
+    // nodes of other elements, or none, are held where they are
+    for (known, d) in known.iter_mut().zip(k.diagonal()) {
+        if d == 0.0 {
+            known.get_or_insert(0.0);
+        }
+    }
+
+    let (kc, rhs) = k.constrain(&f, &known);
+    let (u, iterations) = sparse::conjugate_gradient(&kc, &rhs, 10 * n + 100).map_err(|r| {
+        format!(
+            "conjugate gradients stalled at residual {:.2e} (mechanism or missing supports)",
+            r
+        )
+    })?;
+    let reactions: Vec<f64> = k.mul(&u).iter().zip(&f).map(|(a, b)| a - b).collect();
+
+    // element strain and stress, tensor shear for the strain
+    let mut strain = vec![[0.0f32; 6]; obj.elements.len()];
+    let mut stress = vec![[0.0f32; 6]; obj.elements.len()];
+    for &id in &used {
+        let e = &obj.elements[id];
+        let mat = model.material(&e.group)?;
+        let p = |i: usize| pos[e.nodes[i] as usize];
+        let ue = |i: usize| u[e.nodes[i / nd] as usize * nd + i % nd];
+        let (eps, sig) = if nd == 2 {
+            let (b, _) = elastic::cst_b([p(0), p(1), p(2)]);
+            let eps = elastic::mul(&b, &std::array::from_fn(ue));
+            let sig = elastic::mul(&elastic::plane_d(mat, analysis == Analysis::PlaneStrain), &eps);
+            let (ezz, szz) = if analysis == Analysis::PlaneStrain {
+                (0.0, mat.nu * (sig[0] + sig[1]))
+            } else {
+                (-mat.nu / (1.0 - mat.nu) * (eps[0] + eps[1]), 0.0)
+            };
+            (
+                [eps[0], eps[1], ezz, eps[2], 0.0, 0.0],
+                [sig[0], sig[1], szz, sig[2], 0.0, 0.0],
+            )
+        } else {
+            let (b, _) = elastic::tet_b([p(0), p(1), p(2), p(3)]);
+            let eps = elastic::mul(&b, &std::array::from_fn(ue));
+            (eps, elastic::mul(&elastic::solid_d(mat), &eps))
+        };
+        strain[id] = std::array::from_fn(|i| (if i < 3 { eps[i] } else { eps[i] / 2.0 }) as f32);
+        stress[id] = sig.map(|v| v as f32);
+    }
+
+    let tensor = |name: &str, values| TensorField {
+        name: name.to_string(),
+        units: String::new(),
+        location: Location::Element,
+        values,
+    };
+    let stress = tensor("Stress", stress);
+    let von_mises = stress.derive(Quantity::VonMises);
+    let max_stress = von_mises.values.iter().copied().fold(0.0, f32::max);
+    let displacement = node_vectors("Displacement", &u, nd, 0);
+    let max_disp = displacement
+        .magnitudes()
+        .into_iter()
+        .fold(0.0, f32::max);
+    Ok(Results {
+        fields: vec![
+            Field {
+                name: "Displacement magnitude".to_string(),
+                units: String::new(),
+                location: Location::Node,
+                values: displacement.magnitudes(),
+                layer: None,
+            },
+            von_mises,
+        ],
+        vectors: vec![displacement, node_vectors("Reaction force", &reactions, nd, 0)],
+        tensors: vec![tensor("Strain", strain), stress],
+        steps: Vec::new(),
+        summary: format!(
+            "{} {} elements, {} dofs, {} nonzeros, {} cg iterations, max displacement {:.4e}, max von Mises {:.4e}",
+            used.len(),
+            kind.name(),
+            n,
+            k.nnz(),
+            iterations,
+            max_disp,
+            max_stress
+        ),
+    })
+}
@@ -0,0 +1,238 @@
+// linear elastic cst triangles and 4 node tetrahedra, the gradients are
+// shared with the heat solver
+
+use super::super::model::Material;
+use super::truss::Vec3;
+
+// material stiffness in voigt order xx yy xy (engineering shear)
+pub fn plane_d(mat: &Material, plane_strain: bool) -> [[f64; 3]; 3] {
+    let (e, nu) = (mat.e, mat.nu);
+    if plane_strain {
+        let c = e / ((1.0 + nu) * (1.0 - 2.0 * nu));
+        [
+            [c * (1.0 - nu), c * nu, 0.0],
+            [c * nu, c * (1.0 - nu), 0.0],
+            [0.0, 0.0, c * (1.0 - 2.0 * nu) / 2.0],
+        ]
+    } else {
+        let c = e / (1.0 - nu * nu);
+        [
+            [c, c * nu, 0.0],
+            [c * nu, c, 0.0],
+            [0.0, 0.0, c * (1.0 - nu) / 2.0],
+        ]
+    }
+}
+
+// voigt order xx yy zz xy yz xz, same as the tensor fields
+pub fn solid_d(mat: &Material) -> [[f64; 6]; 6] {
+    let (e, nu) = (mat.e, mat.nu);
+    let c = e / ((1.0 + nu) * (1.0 - 2.0 * nu));
+    let mut d = [[0.0; 6]; 6];
+    for i in 0..3 {
+        for (j, v) in d[i].iter_mut().take(3).enumerate() {
+            *v = c * if i == j { 1.0 - nu } else { nu };
+        }
+        d[i + 3][i + 3] = c * (1.0 - 2.0 * nu) / 2.0;
+    }
+    d
+}
+
+// shape function gradients of a triangle in the xy plane and its area
+pub fn tri_gradients(p: [Vec3; 3]) -> ([[f64; 2]; 3], f64) {
+    let det = (p[1][0] - p[0][0]) * (p[2][1] - p[0][1]) - (p[2][0] - p[0][0]) * (p[1][1] - p[0][1]);
+    let grads = std::array::from_fn(|i| {
+        let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+        [(p[j][1] - p[k][1]) / det, (p[k][0] - p[j][0]) / det]
+    });
+    (grads, det.abs() / 2.0)
+}
+
+// strain-displacement matrix of a triangle in the xy plane, dofs ux uy
+// per node, and its area
+pub fn cst_b(p: [Vec3; 3]) -> ([[f64; 6]; 3], f64) {
+    let (grads, area) = tri_gradients(p);
+    let mut b = [[0.0; 6]; 3];
+    for (i, [dx, dy]) in grads.into_iter().enumerate() {
+        b[0][2 * i] = dx;
+        b[1][2 * i + 1] = dy;
+        b[2][2 * i] = dy;
+        b[2][2 * i + 1] = dx;
+    }
+    (b, area)
+}
+
+// shape function gradients of a tet and its signed volume
+pub fn tet_gradients(p: [Vec3; 4]) -> ([[f64; 3]; 4], f64) {
+    // x - p0 = m xi with the edges from node 0 as columns
+    let m: [Vec3; 3] = std::array::from_fn(|r| std::array::from_fn(|c| p[c + 1][r] - p[0][r]));
+    let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    // rows of m^-1 are the gradients of xi
+    let inv = |r: usize, c: usize| {
+        let (r1, r2) = ((c + 1) % 3, (c + 2) % 3);
+        let (c1, c2) = ((r + 1) % 3, (r + 2) % 3);
+        (m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]) / det
+    };
+    let mut grads = [[0.0; 3]; 4];
+    for i in 0..3 {
+        grads[i + 1] = [inv(i, 0), inv(i, 1), inv(i, 2)];
+    }
+    grads[0] = std::array::from_fn(|a| -(grads[1][a] + grads[2][a] + grads[3][a]));
+    (grads, det / 6.0)
+}
+
+// strain-displacement matrix of a tet, dofs ux uy uz per node, and its
+// signed volume
+pub fn tet_b(p: [Vec3; 4]) -> ([[f64; 12]; 6], f64) {
+    let (grads, volume) = tet_gradients(p);
+    let mut b = [[0.0; 12]; 6];
+    for (i, [dx, dy, dz]) in grads.into_iter().enumerate() {
+        let c = 3 * i;
+        b[0][c] = dx;
+        b[1][c + 1] = dy;
+        b[2][c + 2] = dz;
+        b[3][c] = dy;
+        b[3][c + 1] = dx;
+        b[4][c + 1] = dz;
+        b[4][c + 2] = dy;
+        b[5][c] = dz;
+        b[5][c + 2] = dx;
+    }
+    (b, volume)
+}
+
+// scale * B^T D B
+pub fn stiffness<const S: usize, const N: usize>(
+    b: &[[f64; N]; S],
+    d: &[[f64; S]; S],
+    scale: f64,
+) -> [[f64; N]; N] {
+    let mut db = [[0.0; N]; S];
+    for i in 0..S {
+        for j in 0..N {
+            db[i][j] = (0..S).map(|p| d[i][p] * b[p][j]).sum();
+        }
+    }
+    let mut k = [[0.0; N]; N];
+    for i in 0..N {
+        for j in 0..N {
+            k[i][j] = scale * (0..S).map(|p| b[p][i] * db[p][j]).sum::<f64>();
+        }
+    }
+    k
+}
+
+pub fn mul<const R: usize, const C: usize>(m: &[[f64; C]; R], x: &[f64; C]) -> [f64; R] {
+    std::array::from_fn(|i| (0..C).map(|j| m[i][j] * x[j]).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::linalg::{cholesky_solve, Dense};
+    use super::*;
+
+    fn material() -> Material {
+        Material { name: "m".to_string(), e: 1000.0, nu: 0.3, density: 0.0, conductivity: 0.0, expansion: 0.0 }
+    }
+
+    // displacement gradient and offset of the linear field every patch must reproduce
+    const GRAD: [[f64; 3]; 3] = [[1e-3, 2e-3, -1e-3], [-5e-4, 3e-3, 2e-3], [1.5e-3, -2e-3, 4e-3]];
+    const OFFSET: [f64; 3] = [0.1, -0.2, 0.05];
+
+    fn exact(p: Vec3, dofs: usize) -> Vec<f64> {
+        (0..dofs).map(|i| OFFSET[i] + (0..dofs).map(|j| GRAD[i][j] * p[j]).sum::<f64>()).collect()
+    }
+
+    // the boundary held on the linear field, the free dofs of the one
+    // inner node solved for
+    fn solve_patch(k: &Dense, u: &[f64], free: &[usize]) -> Vec<f64> {
+        let mut kff = Dense::new(free.len());
+        let mut f = vec![0.0; free.len()];
+        for (a, i) in free.iter().enumerate() {
+            for j in (0..k.n).filter(|j| !free.contains(j)) {
+                f[a] -= k.get(*i, j) * u[j];
+            }
+            for (b, j) in free.iter().enumerate() {
+                kff.add(a, b, k.get(*i, *j));
+            }
+        }
+        cholesky_solve(&kff, &f).unwrap()
+    }
+
+    fn assert_close(a: &[f64], b: &[f64]) {
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-10, "{:?} vs {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn cst_patch_test() {
+        let p = [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [2.0, 1.0, 0.0], [0.0, 1.0, 0.0], [0.7, 0.4, 0.0]];
+        let tris = [[0, 1, 4], [1, 2, 4], [2, 3, 4], [3, 0, 4]];
+        let exact_u: Vec<f64> = p.iter().flat_map(|x| exact(*x, 2)).collect();
+        let strain = [GRAD[0][0], GRAD[1][1], GRAD[0][1] + GRAD[1][0]];
+        for plane_strain in [false, true] {
+            let d = plane_d(&material(), plane_strain);
+            let mut k = Dense::new(10);
+            for t in tris {
+                let (b, area) = cst_b(t.map(|i| p[i]));
+                let ke = stiffness(&b, &d, area * 0.5);
+                for (i, row) in ke.iter().enumerate() {
+                    for (j, v) in row.iter().enumerate() {
+                        k.add(2 * t[i / 2] + i % 2, 2 * t[j / 2] + j % 2, *v);
+                    }
+                }
+            }
+            let inner = solve_patch(&k, &exact_u, &[8, 9]);
+            assert_close(&inner, &exact_u[8..]);
+            for t in tris {
+                let (b, _) = cst_b(t.map(|i| p[i]));
+                let u: [f64; 6] = std::array::from_fn(|i| exact_u[2 * t[i / 2] + i % 2]);
+                assert_close(&mul(&b, &u), &strain);
+            }
+        }
+    }
+
+    #[test]
+    fn tet_patch_test() {
+        // corners of the unit cube by bits x y z, then a node inside
+        let mut p: Vec<Vec3> = (0..8).map(|c| std::array::from_fn(|a| ((c >> a) & 1) as f64)).collect();
+        p.push([0.4, 0.55, 0.45]);
+        // each face of the cube split in two, every triangle joined to the inner node
+        let mut tets = Vec::new();
+        for a in 0..3 {
+            let (b, c) = ((a + 1) % 3, (a + 2) % 3);
+            for s in 0..2 {
+                let q = [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(x, y)| s << a | x << b | y << c);
+                tets.push([q[0], q[1], q[2], 8]);
+                tets.push([q[0], q[2], q[3], 8]);
+            }
+        }
+        let exact_u: Vec<f64> = p.iter().flat_map(|x| exact(*x, 3)).collect();
+        let d = solid_d(&material());
+        let mut k = Dense::new(27);
+        let mut total = 0.0;
+        for t in &tets {
+            let (b, volume) = tet_b(t.map(|i| p[i]));
+            total += volume.abs();
+            let ke = stiffness(&b, &d, volume.abs());
+            for (i, row) in ke.iter().enumerate() {
+                for (j, v) in row.iter().enumerate() {
+                    k.add(3 * t[i / 3] + i % 3, 3 * t[j / 3] + j % 3, *v);
+                }
+            }
+        }
+        assert!((total - 1.0).abs() < 1e-12);
+        let inner = solve_patch(&k, &exact_u, &[24, 25, 26]);
+        assert_close(&inner, &exact_u[24..]);
+        let g = GRAD;
+        let strain = [g[0][0], g[1][1], g[2][2], g[0][1] + g[1][0], g[1][2] + g[2][1], g[0][2] + g[2][0]];
+        for t in &tets {
+            let (b, _) = tet_b(t.map(|i| p[i]));
+            let u: [f64; 12] = std::array::from_fn(|i| exact_u[3 * t[i / 3] + i % 3]);
+            assert_close(&mul(&b, &u), &strain);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+// steady conduction on triangles (per unit thickness times the section
+// thickness) or tets, one temperature per node
+
+use super::super::elements::ElementKind;
+use super::super::fields::{Field, Location, VectorField};
+use super::super::td_comp::TdObject;
+use super::sparse::{self, Assembly};
+use super::{elastic, face_load, thickness, Results};
+
+// consistent face matrix of a linear edge or triangle, times its area
+fn face_matrix(n: usize) -> Vec<Vec<f64>> {
+    let (diag, off) = if n == 2 { (2.0 / 6.0, 1.0 / 6.0) } else { (2.0 / 12.0, 1.0 / 12.0) };
+    (0..n)
+        .map(|i| (0..n).map(|j| if i == j { diag } else { off }).collect())
+        .collect()
+}
+
+pub fn solve(obj: &TdObject) -> Result<Results, String> {
+    let model = &obj.model;
+    let n = obj.vertices.len();
+    // tets when there are any, otherwise a plane model of triangles
+    let kind = if obj.elements.iter().any(|e| e.kind == ElementKind::Tet4) {
+        ElementKind::Tet4
+    } else {
+        ElementKind::Tri3
+    };
+    let pos: Vec<[f64; 3]> = obj
+        .positions()
+        .iter()
+        .map(|p| p.map(|v| v as f64))
+        .collect();
+    let used: Vec<usize> = (0..obj.elements.len())
+        .filter(|i| obj.elements[*i].kind == kind)
+        .collect();
+    if used.is_empty() {
+        return Err("the model has no tri3 or tet4 elements".to_string());
+    }
+
+    // gradients of every used element, kept for the fluxes
+    let mut grads: Vec<Vec<[f64; 3]>> = vec![Vec::new(); obj.elements.len()];
+    let mut k = Assembly::new(n);
+    for &id in &used {
+        let e = &obj.elements[id];
+        let mat = model.material(&e.group)?;
+        if mat.conductivity <= 0.0 {
+            return Err(format!("material {} has no conductivity", mat.name));
+        }
+        let p = |i: usize| pos[e.nodes[i] as usize];
+        let (g, size) = if kind == ElementKind::Tri3 {
+            let (g, area) = elastic::tri_gradients([p(0), p(1), p(2)]);
+            (g.map(|[x, y]| [x, y, 0.0]).to_vec(), area * thickness(model, &e.group))
+        } else {
+            let (g, volume) = elastic::tet_gradients([p(0), p(1), p(2), p(3)]);
+            (g.to_vec(), volume.abs())
+        };
+        if size == 0.0 || !size.is_finite() {
+            return Err(format!("element {} is degenerate", id));
+        }
+        for (i, gi) in g.iter().enumerate() {
+            for (j, gj) in g.iter().enumerate() {
+                let dot: f64 = (0..3).map(|a| gi[a] * gj[a]).sum();
+                k.add(e.nodes[i] as usize, e.nodes[j] as usize, mat.conductivity * size * dot);
+            }
+        }
+        grads[id] = g;
+    }
+
+    let mut f = vec![0.0; n];
+    let faces = |set: &str| -> Result<Vec<(f64, Vec<u32>)>, String> {
+        model
+            .face_set(set)?
+            .iter()
+            .map(|r| {
+                let (normal, nodes) = face_load(obj, r.element as usize, r.face)
+                    .ok_or(format!("no face {} on element {}", r.face, r.element))?;
+                let mut area = normal.iter().map(|v| v * v).sum::<f64>().sqrt();
+                if nodes.len() == 2 {
+                    area *= thickness(model, &obj.elements[r.element as usize].group);
+                }
+                Ok((area, nodes))
+            })
+            .collect()
+    };
+    for flux in &model.fluxes {
+        for (area, nodes) in faces(&flux.set)? {
+            for node in &nodes {
+                f[*node as usize] += flux.value * area / nodes.len() as f64;
+            }
+        }
+    }
+    for conv in &model.convections {
+        for (area, nodes) in faces(&conv.set)? {
+            let m = face_matrix(nodes.len());
+            for (i, a) in nodes.iter().enumerate() {
+                f[*a as usize] += conv.h * conv.ambient * area / nodes.len() as f64;
+                for (j, b) in nodes.iter().enumerate() {
+                    k.add(*a as usize, *b as usize, conv.h * area * m[i][j]);
+                }
+            }
+        }
+    }
+    let k = k.to_csr();
+
+    let mut known = vec![None; n];
+    for t in &model.temperatures {
+        for node in model.node_set(&t.set)? {
+            *known
+                .get_mut(*node as usize)
+                .ok_or(format!("node set {} has missing node {}", t.set, node))? = Some(t.value);
+        }
+    }
+    if known.iter().all(Option::is_none) && model.convections.is_empty() {
+        return Err("no fixed temperatures or convection, the temperature is not determined".to_string());
+    }
+    for (known, d) in known.iter_mut().zip(k.diagonal()) {
+        if d == 0.0 {
+            known.get_or_insert(0.0);
+        }
+    }
+
+    let (kc, rhs) = k.constrain(&f, &known);
+    let (t, iterations) = sparse::conjugate_gradient(&kc, &rhs, 10 * n + 100)
+        .map_err(|r| format!("conjugate gradients stalled at residual {:.2e}", r))?;
+    let residual = k.mul(&t);
+    let fixed_heat: f64 = (0..n)
+        .filter(|i| known[*i].is_some())
+        .map(|i| residual[i] - f[i])
+        .sum();
+
+    // q = -k grad T per element
+    let mut flux = vec![[0.0f32; 3]; obj.elements.len()];
+    for &id in &used {
+        let e = &obj.elements[id];
+        let conductivity = model.material(&e.group)?.conductivity;
+        flux[id] = std::array::from_fn(|a| {
+            let g: f64 = e.nodes.iter().zip(&grads[id]).map(|(i, g)| g[a] * t[*i as usize]).sum();
+            (-conductivity * g) as f32
+        });
+    }
+    let flux = VectorField {
+        name: "Heat flux".to_string(),
+        units: String::new(),
+        location: Location::Element,
+        values: flux,
+    };
+    let (lo, hi) = t.iter().fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+    Ok(Results {
+        fields: vec![
+            Field {
+                name: "Temperature".to_string(),
+                units: String::new(),
+                location: Location::Node,
+                values: t.iter().map(|v| *v as f32).collect(),
+                layer: None,
+            },
+            Field {
+                name: "Heat flux magnitude".to_string(),
+                units: String::new(),
+                location: Location::Element,
+                values: flux.magnitudes(),
+                layer: None,
+            },
+        ],
+        vectors: vec![flux],
+        tensors: Vec::new(),
+        steps: Vec::new(),
+        summary: format!(
+            "{} {} elements, {} cg iterations, temperature {:.4} to {:.4}, net heat supplied at fixed temperatures {:.4e}",
+            used.len(),
+            kind.name(),
+            iterations,
+            lo,
+            hi,
+            fixed_heat
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const LENGTH: usize = 4;
+    const CONDUCTIVITY: f64 = 2.0;
+
+    // a bar along x one unit high, LENGTH cells of two triangles, bottom
+    // nodes 0..=LENGTH and the top row after them
+    fn bar(extra: Value) -> TdObject {
+        let top = LENGTH + 1;
+        let vers: Vec<[f64; 3]> = (0..2).flat_map(|y| (0..top).map(move |x| [x as f64, y as f64, 0.0])).collect();
+        let elems: Vec<Value> = (0..LENGTH)
+            .flat_map(|i| {
+                [[i, i + 1, top + i + 1], [i, top + i + 1, top + i]].map(|n| json!({ "kind": "tri3", "nodes": n }))
+            })
+            .collect();
+        let mut doc = json!({
+            "vers": vers,
+            "elems": elems,
+            "materials": [{ "name": "m", "e": 1.0, "conductivity": CONDUCTIVITY }],
+            "node_sets": { "left": [0, top], "right": [LENGTH, top + LENGTH] },
+            // edge 1 of the last cell's first triangle is the right end
+            "face_sets": { "end": [{ "element": 2 * LENGTH - 2, "face": 1 }] },
+        });
+        for (key, value) in extra.as_object().unwrap() {
+            doc[key] = value.clone();
+        }
+        TdObject::from_json(&doc.to_string())
+    }
+
+    fn temperatures(obj: &TdObject) -> Vec<f32> {
+        let results = solve(obj).unwrap();
+        results.fields.into_iter().find(|f| f.name == "Temperature").unwrap().values
+    }
+
+    fn assert_linear(obj: &TdObject, t: &[f32], at: impl Fn(f64) -> f64) {
+        for (v, p) in t.iter().zip(obj.positions()) {
+            let expected = at(p[0] as f64);
+            assert!((*v as f64 - expected).abs() < 1e-4, "{} at x {} vs {}", v, p[0], expected);
+        }
+    }
+
+    #[test]
+    fn fixed_ends_give_a_linear_profile() {
+        let obj = bar(json!({
+            "temperatures": [{ "set": "left", "value": 100.0 }, { "set": "right", "value": 20.0 }],
+        }));
+        let t = temperatures(&obj);
+        assert_linear(&obj, &t, |x| 100.0 - 80.0 * x / LENGTH as f64);
+    }
+
+    #[test]
+    fn convection_at_the_end() {
+        let (h, ambient, fixed) = (3.0, 10.0, 100.0);
+        let obj = bar(json!({
+            "temperatures": [{ "set": "left", "value": fixed }],
+            "convections": [{ "set": "end", "h": h, "ambient": ambient }],
+        }));
+        let t = temperatures(&obj);
+        // conduction along the bar in series with the film at its end
+        let q = (fixed - ambient) / (LENGTH as f64 / CONDUCTIVITY + 1.0 / h);
+        assert_linear(&obj, &t, |x| fixed - q * x / CONDUCTIVITY);
+    }
+}
@@ -0,0 +1,185 @@
+// small dense kit for the line element solvers and the projected eigen
+// problems
+
+const JACOBI_SWEEPS: usize = 64;
+
+// square matrix, row major
+pub struct Dense {
+    pub n: usize,
+    pub a: Vec<f64>,
+}
+
+impl Dense {
+    pub fn new(n: usize) -> Self {
+        Self { n, a: vec![0.0; n * n] }
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        self.a[i * self.n + j]
+    }
+
+    pub fn add(&mut self, i: usize, j: usize, v: f64) {
+        self.a[i * self.n + j] += v;
+    }
+
+    pub fn mul(&self, x: &[f64]) -> Vec<f64> {
+        self.a
+            .chunks_exact(self.n)
+            .map(|row| row.iter().zip(x).map(|(a, b)| a * b).sum())
+            .collect()
+    }
+}
+
+// lower triangle l with l l^T = k, Err(i) when the pivot of row i vanishes
+pub fn cholesky(k: &Dense) -> Result<Dense, usize> {
+    let n = k.n;
+    let mut l = k.a.clone();
+    let scale = (0..n).map(|i| k.get(i, i).abs()).fold(0.0, f64::max);
+    for j in 0..n {
+        let mut d = l[j * n + j];
+        for p in 0..j {
+            d -= l[j * n + p] * l[j * n + p];
+        }
+        if d <= 1e-12 * scale {
+            return Err(j);
+        }
+        let d = d.sqrt();
+        l[j * n + j] = d;
+        for i in j + 1..n {
+            let mut s = l[i * n + j];
+            for p in 0..j {
+                s -= l[i * n + p] * l[j * n + p];
+            }
+            l[i * n + j] = s / d;
+        }
+        for i in 0..j {
+            l[i * n + j] = 0.0;
+        }
+    }
+    Ok(Dense { n, a: l })
+}
+
+// solves l y = f
+pub fn forward(l: &Dense, f: &[f64]) -> Vec<f64> {
+    let mut y = f.to_vec();
+    for i in 0..l.n {
+        for p in 0..i {
+            y[i] -= l.get(i, p) * y[p];
+        }
+        y[i] /= l.get(i, i);
+    }
+    y
+}
+
+// solves l^T x = y
+pub fn backward(l: &Dense, y: &[f64]) -> Vec<f64> {
+    let mut x = y.to_vec();
+    for i in (0..l.n).rev() {
+        for p in i + 1..l.n {
+            x[i] -= l.get(p, i) * x[p];
+        }
+        x[i] /= l.get(i, i);
+    }
+    x
+}
+
+// solves a symmetric positive definite system in place of a copy,
+// Err(i) when the pivot of row i vanishes
+pub fn cholesky_solve(k: &Dense, f: &[f64]) -> Result<Vec<f64>, usize> {
+    let l = cholesky(k)?;
+    Ok(backward(&l, &forward(&l, f)))
+}
+
+// eigenvalues (ascending) and eigenvectors (columns) of a symmetric
+// matrix by cyclic jacobi, for the small projected problems
+pub fn symmetric_eigen(a: &Dense) -> (Vec<f64>, Dense) {
+    let n = a.n;
+    let mut a = Dense { n, a: a.a.clone() };
+    let mut v = Dense::new(n);
+    for i in 0..n {
+        v.a[i * n + i] = 1.0;
+    }
+    for _ in 0..JACOBI_SWEEPS {
+        let mut off = 0.0;
+        let mut diag = 0.0;
+        for i in 0..n {
+            diag += a.get(i, i).abs();
+            for j in i + 1..n {
+                off += a.get(i, j).abs();
+            }
+        }
+        if off <= 1e-14 * diag.max(1e-300) {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                let apq = a.get(p, q);
+                if apq == 0.0 {
+                    continue;
+                }
+                let theta = (a.get(q, q) - a.get(p, p)) / (2.0 * apq);
+                let tan = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (tan * tan + 1.0).sqrt();
+                let s = tan * c;
+                for k in 0..n {
+                    let (akp, akq) = (a.get(k, p), a.get(k, q));
+                    a.a[k * n + p] = c * akp - s * akq;
+                    a.a[k * n + q] = s * akp + c * akq;
+                }
+                for k in 0..n {
+                    let (apk, aqk) = (a.get(p, k), a.get(q, k));
+                    a.a[p * n + k] = c * apk - s * aqk;
+                    a.a[q * n + k] = s * apk + c * aqk;
+                }
+                for k in 0..n {
+                    let (vkp, vkq) = (v.get(k, p), v.get(k, q));
+                    v.a[k * n + p] = c * vkp - s * vkq;
+                    v.a[k * n + q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|i, j| a.get(*i, *i).total_cmp(&a.get(*j, *j)));
+    let values = order.iter().map(|i| a.get(*i, *i)).collect();
+    let mut vectors = Dense::new(n);
+    for (c, i) in order.iter().enumerate() {
+        for k in 0..n {
+            vectors.a[k * n + c] = v.get(k, *i);
+        }
+    }
+    (values, vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dense(rows: &[&[f64]]) -> Dense {
+        Dense { n: rows.len(), a: rows.concat() }
+    }
+
+    #[test]
+    fn cholesky_solves_spd_system() {
+        let k = dense(&[&[4.0, 2.0, 0.0], &[2.0, 5.0, 3.0], &[0.0, 3.0, 6.0]]);
+        let x = cholesky_solve(&k, &[2.0, 3.0, 9.0]).unwrap();
+        for (a, b) in x.iter().zip([1.0, -1.0, 2.0]) {
+            assert!((a - b).abs() < 1e-12, "{:?}", x);
+        }
+        let l = cholesky(&k).unwrap();
+        for i in 0..3 {
+            for j in 0..3 {
+                let llt: f64 = (0..3).map(|p| l.get(i, p) * l.get(j, p)).sum();
+                assert!((llt - k.get(i, j)).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn cholesky_reports_the_row_that_is_not_positive_definite() {
+        let k = dense(&[&[1.0, 2.0], &[2.0, 1.0]]);
+        assert_eq!(cholesky_solve(&k, &[1.0, 1.0]).err(), Some(1));
+        let singular = dense(&[&[1.0, 0.0], &[0.0, 0.0]]);
+        assert_eq!(cholesky(&singular).err(), Some(1));
+    }
+}
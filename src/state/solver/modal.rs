@@ -0,0 +1,363 @@
+// natural frequencies and mode shapes by subspace iteration on K x = w^2 M x
+
+use std::f64::consts::PI;
+
+use super::super::fields::{Field, Location, Step};
+use super::super::td_comp::TdObject;
+use super::linalg::{self, Dense};
+use super::sparse::{self, Assembly, Csr};
+use super::{element_kind, element_stiffness, elements_of, node_vectors, rows, truss, Analysis, Results};
+
+const MAX_ITERATIONS: usize = 100;
+const TOLERANCE: f64 = 1e-8;
+
+// consistent mass of a linear element with `nodes` nodes spread over
+// `dofs` translations, `total` is its whole mass
+fn simplex_mass(nodes: usize, dofs: usize, total: f64, lumped: bool) -> Vec<Vec<f64>> {
+    // consistent mass of a linear simplex with k nodes is m/(k(k+1)) (1 + delta)
+    let (diag, off) = if lumped {
+        (total / nodes as f64, 0.0)
+    } else {
+        let c = total / (nodes * (nodes + 1)) as f64;
+        (2.0 * c, c)
+    };
+    let n = nodes * dofs;
+    (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| match (i % dofs == j % dofs, i == j) {
+                    (_, true) => diag,
+                    (true, false) => off,
+                    _ => 0.0,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// 12x12 frame mass in local axes, translations only when lumped
+fn frame_mass(len: f64, rho: f64, area: f64, polar: f64, lumped: bool) -> [[f64; 12]; 12] {
+    let m = rho * area * len;
+    let mut k = [[0.0; 12]; 12];
+    if lumped {
+        for d in [0, 1, 2, 6, 7, 8] {
+            k[d][d] = m / 2.0;
+        }
+        return k;
+    }
+    let l = len;
+    let mut set = |i: usize, j: usize, v: f64| {
+        k[i][j] = v;
+        k[j][i] = v;
+    };
+    set(0, 0, m / 3.0);
+    set(6, 6, m / 3.0);
+    set(0, 6, m / 6.0);
+    let mt = rho * polar * len;
+    set(3, 3, mt / 3.0);
+    set(9, 9, mt / 3.0);
+    set(3, 9, mt / 6.0);
+    let c = m / 420.0;
+    // v, rz
+    set(1, 1, 156.0 * c);
+    set(7, 7, 156.0 * c);
+    set(1, 7, 54.0 * c);
+    set(1, 5, 22.0 * l * c);
+    set(7, 11, -22.0 * l * c);
+    set(1, 11, -13.0 * l * c);
+    set(5, 7, 13.0 * l * c);
+    set(5, 5, 4.0 * l * l * c);
+    set(11, 11, 4.0 * l * l * c);
+    set(5, 11, -3.0 * l * l * c);
+    // w, ry
+    set(2, 2, 156.0 * c);
+    set(8, 8, 156.0 * c);
+    set(2, 8, 54.0 * c);
+    set(2, 4, -22.0 * l * c);
+    set(8, 10, 22.0 * l * c);
+    set(2, 10, 13.0 * l * c);
+    set(4, 8, -13.0 * l * c);
+    set(4, 4, 4.0 * l * l * c);
+    set(10, 10, 4.0 * l * l * c);
+    set(4, 10, -3.0 * l * l * c);
+    k
+}
+
+// mass of one element in the same dofs as its stiffness
+fn element_mass(obj: &TdObject, analysis: Analysis, id: usize, pos: &[[f64; 3]], lumped: bool) -> Result<Vec<Vec<f64>>, String> {
+    let model = &obj.model;
+    let e = &obj.elements[id];
+    let mat = model.material(&e.group)?;
+    if mat.density <= 0.0 {
+        return Err(format!("material {} has no density", mat.name));
+    }
+    let p = |i: usize| pos[e.nodes[i] as usize];
+    Ok(match analysis {
+        Analysis::Truss => {
+            let (_, len) = truss::axis(p(0), p(1));
+            simplex_mass(2, 3, mat.density * model.section(&e.group)?.area * len, lumped)
+        }
+        Analysis::Frame => {
+            let sec = model.section(&e.group)?;
+            let (dir, len) = truss::axis(p(0), p(1));
+            let t = truss::frame_transform(truss::local_axes(dir, model.orientation(&e.group)));
+            let me = frame_mass(len, mat.density, sec.area, sec.iy + sec.iz, lumped);
+            rows(truss::to_global(&me, &t))
+        }
+        Analysis::PlaneStress | Analysis::PlaneStrain => {
+            let (_, area) = super::elastic::tri_gradients([p(0), p(1), p(2)]);
+            let mass = mat.density * area * super::thickness(model, &e.group);
+            simplex_mass(3, 2, mass, lumped)
+        }
+        Analysis::Solid | Analysis::Heat => {
+            let (_, volume) = super::elastic::tet_gradients([p(0), p(1), p(2), p(3)]);
+            simplex_mass(4, 3, mat.density * volume.abs(), lumped)
+        }
+    })
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+// x^T a y for every pair of columns
+fn project(x: &[Vec<f64>], ay: &[Vec<f64>]) -> Dense {
+    let q = x.len();
+    let mut out = Dense::new(q);
+    for (i, xi) in x.iter().enumerate() {
+        for (j, col) in ay.iter().enumerate() {
+            out.add(i, j, dot(xi, col));
+        }
+    }
+    // symmetric up to round-off
+    for i in 0..q {
+        for j in i + 1..q {
+            let v = (out.get(i, j) + out.get(j, i)) / 2.0;
+            out.a[i * q + j] = v;
+            out.a[j * q + i] = v;
+        }
+    }
+    out
+}
+
+// lowest eigenpairs of k x = lambda m x, k with identity rows for the held
+// dofs and m zero there
+fn subspace(k: &Csr, m: &Csr, count: usize, free: usize) -> Result<(Vec<f64>, Vec<Vec<f64>>), String> {
+    if count > free {
+        return Err(format!("{} modes asked for but only {} free dofs", count, free));
+    }
+    let n = k.n();
+    let q = (2 * count).min(count + 8).min(free);
+    // deterministic start: the mass diagonal, then scattered unit loads
+    let diag = m.diagonal();
+    let massive: Vec<usize> = (0..n).filter(|i| diag[*i] > 0.0).collect();
+    let mut x: Vec<Vec<f64>> = (0..q)
+        .map(|c| {
+            if c == 0 {
+                diag.clone()
+            } else {
+                let mut v = vec![0.0; n];
+                let mut seed = (c as u64).wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                for _ in 0..3 {
+                    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    v[massive[(seed >> 33) as usize % massive.len()]] += 1.0;
+                }
+                v
+            }
+        })
+        .collect();
+    let mut values = vec![0.0; q];
+    for _ in 0..MAX_ITERATIONS {
+        let y: Vec<Vec<f64>> = x.iter().map(|v| m.mul(v)).collect();
+        let xbar = y
+            .iter()
+            .map(|f| sparse::conjugate_gradient(k, f, 10 * n + 100).map(|(u, _)| u))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|r| format!("conjugate gradients stalled at residual {:.2e} (free body or missing supports)", r))?;
+        let kr = project(&xbar, &y);
+        let mbar: Vec<Vec<f64>> = xbar.iter().map(|v| m.mul(v)).collect();
+        let mr = project(&xbar, &mbar);
+        // kr z = lambda mr z through the cholesky factor of mr
+        let l = linalg::cholesky(&mr).map_err(|_| "mode shapes became dependent, try fewer modes".to_string())?;
+        // columns of w = l^-1 kr, then the rows of c = l^-1 w^T
+        let w: Vec<Vec<f64>> = (0..q)
+            .map(|j| linalg::forward(&l, &(0..q).map(|i| kr.get(i, j)).collect::<Vec<_>>()))
+            .collect();
+        let c = Dense {
+            n: q,
+            a: (0..q)
+                .flat_map(|i| linalg::forward(&l, &w.iter().map(|col| col[i]).collect::<Vec<_>>()))
+                .collect(),
+        };
+        let (next, z) = linalg::symmetric_eigen(&c);
+        x = (0..q)
+            .map(|j| {
+                let zj: Vec<f64> = (0..q).map(|i| z.get(i, j)).collect();
+                let coef = linalg::backward(&l, &zj);
+                let mut v = vec![0.0; n];
+                for (col, a) in xbar.iter().zip(&coef) {
+                    for (vi, ci) in v.iter_mut().zip(col) {
+                        *vi += a * ci;
+                    }
+                }
+                v
+            })
+            .collect();
+        let converged = (0..count).all(|i| (next[i] - values[i]).abs() <= TOLERANCE * next[i].abs());
+        values = next;
+        if converged {
+            break;
+        }
+    }
+    x.truncate(count);
+    values.truncate(count);
+    Ok((values, x))
+}
+
+// the lowest `count` modes of the structural `analysis`, one step each
+pub fn solve(obj: &TdObject, analysis: Analysis, count: usize, lumped: bool) -> Result<Results, String> {
+    let model = &obj.model;
+    let nd = analysis.dofs_per_node();
+    let n = obj.vertices.len() * nd;
+    let pos: Vec<[f64; 3]> = obj
+        .positions()
+        .iter()
+        .map(|p| p.map(|v| v as f64))
+        .collect();
+    let used = elements_of(obj, analysis)?;
+
+    let (mut k, mut m) = (Assembly::new(n), Assembly::new(n));
+    for &id in &used {
+        let (dofs, ke) = element_stiffness(obj, analysis, id, &pos)?;
+        let me = element_mass(obj, analysis, id, &pos, lumped)?;
+        for i in 0..dofs.len() {
+            for j in 0..dofs.len() {
+                k.add(dofs[i], dofs[j], ke[i][j]);
+                if me[i][j] != 0.0 {
+                    m.add(dofs[i], dofs[j], me[i][j]);
+                }
+            }
+        }
+    }
+    let k = k.to_csr();
+    let m = m.to_csr();
+
+    // supports hold their dofs, prescribed values don't matter for modes
+    let mut known = vec![None; n];
+    for s in &model.supports {
+        let base = s.node as usize * nd;
+        if base >= n {
+            return Err(format!("support on missing node {}", s.node));
+        }
+        for (d, known) in known[base..base + nd].iter_mut().enumerate() {
+            if s.is_fixed(d) {
+                *known = Some(0.0);
+            }
+        }
+    }
+    for (known, d) in known.iter_mut().zip(k.diagonal()) {
+        if d == 0.0 {
+            known.get_or_insert(0.0);
+        }
+    }
+    let (kc, _) = k.constrain(&vec![0.0; n], &known);
+    let mc = m.without(&known);
+    let free = mc.diagonal().iter().filter(|d| **d > 0.0).count();
+    if free == 0 {
+        return Err("no free dofs with mass".to_string());
+    }
+
+    let (values, shapes) = subspace(&kc, &mc, count, free)?;
+    let mut steps = Vec::new();
+    let mut frequencies = Vec::new();
+    for (i, (lambda, shape)) in values.iter().zip(&shapes).enumerate() {
+        let hz = lambda.max(0.0).sqrt() / (2.0 * PI);
+        frequencies.push(format!("{:.4}", hz));
+        let mut shape = node_vectors("Displacement", shape, nd, 0);
+        // largest translation 1, sign of the largest component positive
+        let big = shape
+            .values
+            .iter()
+            .flatten()
+            .fold(0.0f32, |a, v| if v.abs() > a.abs() { *v } else { a });
+        if big != 0.0 {
+            for v in shape.values.iter_mut().flatten() {
+                *v /= big;
+            }
+        }
+        steps.push(Step {
+            name: format!("Mode {}", i + 1),
+            value: hz as f32,
+            fields: vec![Field {
+                name: "Displacement magnitude".to_string(),
+                units: String::new(),
+                location: Location::Node,
+                values: shape.magnitudes(),
+                layer: None,
+            }],
+            vectors: vec![shape],
+        });
+    }
+    Ok(Results {
+        fields: Vec::new(),
+        vectors: Vec::new(),
+        tensors: Vec::new(),
+        steps,
+        summary: format!(
+            "{} modes of {} {} elements ({} mass): {} Hz",
+            count,
+            used.len(),
+            element_kind(analysis).name(),
+            if lumped { "lumped" } else { "consistent" },
+            frequencies.join(", ")
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STIFFNESS: f64 = 100.0;
+    const MASS: f64 = 2.0;
+
+    // ground - spring - mass - spring - mass, equal springs and masses
+    fn two_masses() -> (Csr, Csr) {
+        let (mut k, mut m) = (Assembly::new(2), Assembly::new(2));
+        for (i, j, v) in [(0, 0, 2.0), (1, 1, 1.0), (0, 1, -1.0), (1, 0, -1.0)] {
+            k.add(i, j, v * STIFFNESS);
+        }
+        m.add(0, 0, MASS);
+        m.add(1, 1, MASS);
+        (k.to_csr(), m.to_csr())
+    }
+
+    #[test]
+    fn two_dof_frequencies() {
+        let (k, m) = two_masses();
+        let (values, _) = subspace(&k, &m, 2, 2).unwrap();
+        let root = 5.0f64.sqrt();
+        for (value, expected) in values.iter().zip([(3.0 - root) / 2.0, (3.0 + root) / 2.0]) {
+            let expected = expected * STIFFNESS / MASS;
+            assert!((value - expected).abs() < 1e-8 * expected, "{:?}", values);
+        }
+    }
+
+    #[test]
+    fn modes_are_mass_orthonormal() {
+        let (k, m) = two_masses();
+        let (_, modes) = subspace(&k, &m, 2, 2).unwrap();
+        for (i, a) in modes.iter().enumerate() {
+            for (j, b) in modes.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((dot(a, &m.mul(b)) - expected).abs() < 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    fn more_modes_than_free_dofs_is_an_error() {
+        let (k, m) = two_masses();
+        assert!(subspace(&k, &m, 3, 2).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+// sparse kit for the continuum solvers
+
+use std::collections::BTreeMap;
+
+const CG_TOLERANCE: f64 = 1e-10;
+
+// rows as ordered maps while assembling
+pub struct Assembly {
+    rows: Vec<BTreeMap<usize, f64>>,
+}
+
+impl Assembly {
+    pub fn new(n: usize) -> Self {
+        Self {
+            rows: vec![BTreeMap::new(); n],
+        }
+    }
+
+    pub fn add(&mut self, i: usize, j: usize, v: f64) {
+        *self.rows[i].entry(j).or_insert(0.0) += v;
+    }
+
+    pub fn to_csr(&self) -> Csr {
+        let mut csr = Csr {
+            offsets: vec![0],
+            cols: Vec::new(),
+            vals: Vec::new(),
+        };
+        for row in &self.rows {
+            for (j, v) in row {
+                csr.cols.push(*j);
+                csr.vals.push(*v);
+            }
+            csr.offsets.push(csr.cols.len());
+        }
+        csr
+    }
+}
+
+// compressed sparse rows
+pub struct Csr {
+    offsets: Vec<usize>,
+    cols: Vec<usize>,
+    vals: Vec<f64>,
+}
+
+impl Csr {
+    pub fn n(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn nnz(&self) -> usize {
+        self.vals.len()
+    }
+
+    fn row(&self, i: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let r = self.offsets[i]..self.offsets[i + 1];
+        self.cols[r.clone()].iter().copied().zip(self.vals[r].iter().copied())
+    }
+
+    pub fn diagonal(&self) -> Vec<f64> {
+        (0..self.n())
+            .map(|i| self.row(i).find(|(j, _)| *j == i).map_or(0.0, |(_, v)| v))
+            .collect()
+    }
+
+    pub fn mul(&self, x: &[f64]) -> Vec<f64> {
+        (0..self.n()).map(|i| self.row(i).map(|(j, v)| v * x[j]).sum()).collect()
+    }
+
+    // moves the known dofs to the right hand side and leaves an identity
+    // row and column for them, so the system stays symmetric
+    pub fn constrain(&self, f: &[f64], known: &[Option<f64>]) -> (Csr, Vec<f64>) {
+        let mut rhs = f.to_vec();
+        let mut out = Csr {
+            offsets: vec![0],
+            cols: Vec::with_capacity(self.nnz()),
+            vals: Vec::with_capacity(self.nnz()),
+        };
+        for i in 0..self.n() {
+            if let Some(value) = known[i] {
+                rhs[i] = value;
+                out.cols.push(i);
+                out.vals.push(1.0);
+            } else {
+                for (j, v) in self.row(i) {
+                    match known[j] {
+                        Some(value) => rhs[i] -= v * value,
+                        None => {
+                            out.cols.push(j);
+                            out.vals.push(v);
+                        }
+                    }
+                }
+            }
+            out.offsets.push(out.cols.len());
+        }
+        (out, rhs)
+    }
+
+    // rows and columns of the known dofs dropped to zero
+    pub fn without(&self, known: &[Option<f64>]) -> Csr {
+        let mut out = Csr {
+            offsets: vec![0],
+            cols: Vec::with_capacity(self.nnz()),
+            vals: Vec::with_capacity(self.nnz()),
+        };
+        for i in 0..self.n() {
+            if known[i].is_none() {
+                for (j, v) in self.row(i).filter(|(j, _)| known[*j].is_none()) {
+                    out.cols.push(j);
+                    out.vals.push(v);
+                }
+            }
+            out.offsets.push(out.cols.len());
+        }
+        out
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+// jacobi preconditioned conjugate gradients,
+// Err(residual) when it stalls before the tolerance
+pub fn conjugate_gradient(k: &Csr, f: &[f64], max_iter: usize) -> Result<(Vec<f64>, usize), f64> {
+    let n = k.n();
+    let inv: Vec<f64> = k.diagonal().iter().map(|d| if *d > 0.0 { 1.0 / d } else { 1.0 }).collect();
+    let norm = dot(f, f).sqrt();
+    let mut x = vec![0.0; n];
+    if norm == 0.0 {
+        return Ok((x, 0));
+    }
+    let mut r = f.to_vec();
+    let mut z: Vec<f64> = r.iter().zip(&inv).map(|(a, b)| a * b).collect();
+    let mut p = z.clone();
+    let mut rz = dot(&r, &z);
+    for iter in 0..max_iter {
+        let kp = k.mul(&p);
+        let pkp = dot(&p, &kp);
+        if pkp <= 0.0 {
+            return Err(dot(&r, &r).sqrt() / norm);
+        }
+        let alpha = rz / pkp;
+        for i in 0..n {
+            x[i] += alpha * p[i];
+            r[i] -= alpha * kp[i];
+        }
+        if dot(&r, &r).sqrt() <= CG_TOLERANCE * norm {
+            return Ok((x, iter + 1));
+        }
+        for i in 0..n {
+            z[i] = r[i] * inv[i];
+        }
+        let next = dot(&r, &z);
+        let beta = next / rz;
+        rz = next;
+        for i in 0..n {
+            p[i] = z[i] + beta * p[i];
+        }
+    }
+    Err(dot(&r, &r).sqrt() / norm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::linalg::{cholesky_solve, Dense};
+    use super::*;
+
+    // a chain of springs, held to the ground at the first node
+    fn chain(n: usize) -> (Assembly, Dense) {
+        let mut a = Assembly::new(n);
+        let mut d = Dense::new(n);
+        let mut add = |i: usize, j: usize, v: f64| {
+            a.add(i, j, v);
+            d.add(i, j, v);
+        };
+        add(0, 0, 3.0);
+        for i in 0..n - 1 {
+            let k = 1.0 + i as f64;
+            add(i, i, k);
+            add(i + 1, i + 1, k);
+            add(i, i + 1, -k);
+            add(i + 1, i, -k);
+        }
+        (a, d)
+    }
+
+    #[test]
+    fn conjugate_gradient_matches_cholesky() {
+        let n = 12;
+        let (a, d) = chain(n);
+        let k = a.to_csr();
+        assert_eq!(k.nnz(), 3 * n - 2);
+        let f: Vec<f64> = (0..n).map(|i| (i as f64 * 0.7).sin()).collect();
+        let (u, _) = conjugate_gradient(&k, &f, 10 * n).unwrap();
+        let expected = cholesky_solve(&d, &f).unwrap();
+        for (x, y) in u.iter().zip(&expected) {
+            assert!((x - y).abs() < 1e-8 * y.abs().max(1.0), "{:?} vs {:?}", u, expected);
+        }
+
+        // the last node held at 2, same as the dense system without it
+        let mut known = vec![None; n];
+        known[n - 1] = Some(2.0);
+        let (kc, rhs) = k.constrain(&f, &known);
+        let (u, _) = conjugate_gradient(&kc, &rhs, 10 * n).unwrap();
+        let mut reduced = Dense::new(n - 1);
+        let mut g = f[..n - 1].to_vec();
+        for (i, g) in g.iter_mut().enumerate() {
+            *g -= d.get(i, n - 1) * 2.0;
+            for j in 0..n - 1 {
+                reduced.add(i, j, d.get(i, j));
+            }
+        }
+        let expected = cholesky_solve(&reduced, &g).unwrap();
+        assert!((u[n - 1] - 2.0).abs() < 1e-12);
+        for (x, y) in u.iter().zip(&expected) {
+            assert!((x - y).abs() < 1e-8 * y.abs().max(1.0));
+        }
+    }
+
+    #[test]
+    fn conjugate_gradient_reports_the_residual_when_it_stalls() {
+        let n = 12;
+        let k = chain(n).0.to_csr();
+        let f = vec![1.0; n];
+        let residual = conjugate_gradient(&k, &f, 2).unwrap_err();
+        assert!(residual > CG_TOLERANCE);
+        // a free body has no positive curvature along the rigid motion
+        let mut a = Assembly::new(2);
+        for (i, j, v) in [(0, 0, 1.0), (1, 1, 1.0), (0, 1, -1.0), (1, 0, -1.0)] {
+            a.add(i, j, v);
+        }
+        assert!(conjugate_gradient(&a.to_csr(), &[1.0, 1.0], 10).is_err());
+    }
+}
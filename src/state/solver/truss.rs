@@ -0,0 +1,229 @@
+// 3d truss bars and euler-bernoulli frame elements
+
+use super::super::model::{Material, Section};
+
+pub type Vec3 = [f64; 3];
+
+pub fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: Vec3) -> Vec3 {
+    let l = dot(a, a).sqrt();
+    [a[0] / l, a[1] / l, a[2] / l]
+}
+
+// unit direction and length of the bar p1 -> p2
+pub fn axis(p1: Vec3, p2: Vec3) -> (Vec3, f64) {
+    let d = sub(p2, p1);
+    let len = dot(d, d).sqrt();
+    (normalize(d), len)
+}
+
+// local x along the member, local y towards `up` (global y unless the
+// member is nearly vertical, then global x), z = x cross y.
+// an `up` along the member is ignored
+pub fn local_axes(x: Vec3, up: Option<Vec3>) -> [Vec3; 3] {
+    let up = up
+        .filter(|u| dot(*u, *u) > 0.0 && dot(normalize(*u), x).abs() < 0.99)
+        .unwrap_or(if dot(x, [0.0, 1.0, 0.0]).abs() > 0.99 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        });
+    let d = dot(up, x);
+    let y = normalize([up[0] - d * x[0], up[1] - d * x[1], up[2] - d * x[2]]);
+    [x, y, cross(x, y)]
+}
+
+// 6x6 global stiffness of a bar, dofs ux uy uz of both ends
+pub fn bar_stiffness(e: Vec3, len: f64, ea: f64) -> [[f64; 6]; 6] {
+    let k = ea / len;
+    let mut out = [[0.0; 6]; 6];
+    for i in 0..3 {
+        for j in 0..3 {
+            let v = k * e[i] * e[j];
+            out[i][j] = v;
+            out[i + 3][j + 3] = v;
+            out[i][j + 3] = -v;
+            out[i + 3][j] = -v;
+        }
+    }
+    out
+}
+
+// tension positive
+pub fn bar_force(e: Vec3, len: f64, ea: f64, u1: Vec3, u2: Vec3) -> f64 {
+    ea / len * dot(e, sub(u2, u1))
+}
+
+// 12x12 stiffness in local axes, dofs u v w rx ry rz of both ends
+pub fn frame_local(len: f64, mat: &Material, sec: &Section) -> [[f64; 12]; 12] {
+    let e = mat.e;
+    let g = e / (2.0 * (1.0 + mat.nu));
+    let (l, l2, l3) = (len, len * len, len * len * len);
+    let a = e * sec.area / l;
+    let t = g * sec.j / l;
+    let (z12, z6, z4, z2) = (12.0 * e * sec.iz / l3, 6.0 * e * sec.iz / l2, 4.0 * e * sec.iz / l, 2.0 * e * sec.iz / l);
+    let (y12, y6, y4, y2) = (12.0 * e * sec.iy / l3, 6.0 * e * sec.iy / l2, 4.0 * e * sec.iy / l, 2.0 * e * sec.iy / l);
+
+    let mut k = [[0.0; 12]; 12];
+    let mut set = |i: usize, j: usize, v: f64| {
+        k[i][j] = v;
+        k[j][i] = v;
+    };
+    set(0, 0, a);
+    set(6, 6, a);
+    set(0, 6, -a);
+    set(3, 3, t);
+    set(9, 9, t);
+    set(3, 9, -t);
+    // bending in the local xy plane (v, rz)
+    set(1, 1, z12);
+    set(7, 7, z12);
+    set(1, 7, -z12);
+    set(1, 5, z6);
+    set(1, 11, z6);
+    set(5, 7, -z6);
+    set(7, 11, -z6);
+    set(5, 5, z4);
+    set(11, 11, z4);
+    set(5, 11, z2);
+    // bending in the local xz plane (w, ry)
+    set(2, 2, y12);
+    set(8, 8, y12);
+    set(2, 8, -y12);
+    set(2, 4, -y6);
+    set(2, 10, -y6);
+    set(4, 8, y6);
+    set(8, 10, y6);
+    set(4, 4, y4);
+    set(10, 10, y4);
+    set(4, 10, y2);
+    k
+}
+
+// global to local for the 12 dofs, the axes repeated on the diagonal
+pub fn frame_transform(axes: [Vec3; 3]) -> [[f64; 12]; 12] {
+    let mut t = [[0.0; 12]; 12];
+    for b in 0..4 {
+        for (i, axis) in axes.iter().enumerate() {
+            for j in 0..3 {
+                t[b * 3 + i][b * 3 + j] = axis[j];
+            }
+        }
+    }
+    t
+}
+
+// T^T k T
+pub fn to_global(k: &[[f64; 12]; 12], t: &[[f64; 12]; 12]) -> [[f64; 12]; 12] {
+    let mut kt = [[0.0; 12]; 12];
+    for i in 0..12 {
+        for j in 0..12 {
+            kt[i][j] = (0..12).map(|p| k[i][p] * t[p][j]).sum();
+        }
+    }
+    let mut out = [[0.0; 12]; 12];
+    for i in 0..12 {
+        for j in 0..12 {
+            out[i][j] = (0..12).map(|p| t[p][i] * kt[p][j]).sum();
+        }
+    }
+    out
+}
+
+// end forces in local axes from global end displacements
+pub fn frame_end_forces(k: &[[f64; 12]; 12], t: &[[f64; 12]; 12], u: &[f64; 12]) -> [f64; 12] {
+    let ul: Vec<f64> = (0..12).map(|i| (0..12).map(|j| t[i][j] * u[j]).sum()).collect();
+    std::array::from_fn(|i| (0..12).map(|j| k[i][j] * ul[j]).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::linalg::{cholesky_solve, Dense};
+    use super::*;
+
+    // u for the free dofs of k u = f, everything else held at zero
+    fn solve_free(k: &Dense, f: &[f64], free: &[usize]) -> Vec<f64> {
+        let mut kff = Dense::new(free.len());
+        for (a, i) in free.iter().enumerate() {
+            for (b, j) in free.iter().enumerate() {
+                kff.add(a, b, k.get(*i, *j));
+            }
+        }
+        let ff: Vec<f64> = free.iter().map(|i| f[*i]).collect();
+        cholesky_solve(&kff, &ff).unwrap()
+    }
+
+    #[test]
+    fn cantilever_tip_deflection() {
+        let mat = Material { name: "m".to_string(), e: 1000.0, nu: 0.3, density: 0.0, conductivity: 0.0, expansion: 0.0 };
+        let sec = Section { name: "s".to_string(), area: 1.0, iy: 3.0, iz: 2.0, j: 1.0, ..Section::default() };
+        let (len, load, parts) = (3.0, 5.0, 3);
+        let t = frame_transform(local_axes([1.0, 0.0, 0.0], None));
+        let ke = to_global(&frame_local(len / parts as f64, &mat, &sec), &t);
+        let n = 6 * (parts + 1);
+        let mut k = Dense::new(n);
+        for e in 0..parts {
+            for (i, row) in ke.iter().enumerate() {
+                for (j, v) in row.iter().enumerate() {
+                    k.add(6 * e + i, 6 * e + j, *v);
+                }
+            }
+        }
+        // tip load down y, then along z
+        let free: Vec<usize> = (6..n).collect();
+        let tip = n - 6;
+        for (dof, inertia) in [(1, sec.iz), (2, sec.iy)] {
+            let mut f = vec![0.0; n];
+            f[tip + dof] = -load;
+            let u = solve_free(&k, &f, &free);
+            let expected = -load * len.powi(3) / (3.0 * mat.e * inertia);
+            let got = u[tip + dof - 6];
+            assert!((got - expected).abs() < 1e-9 * expected.abs(), "{} vs {}", got, expected);
+        }
+    }
+
+    #[test]
+    fn two_bar_truss_axial_forces() {
+        // bars from the supports at (0,0) and (2,0) up to (1,1), loaded down at the top
+        let p = [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [1.0, 1.0, 0.0]];
+        let (ea, load) = (100.0, 10.0);
+        let bars = [(0, 2), (1, 2)];
+        let mut k = Dense::new(9);
+        for (a, b) in bars {
+            let (e, len) = axis(p[a], p[b]);
+            let ke = bar_stiffness(e, len, ea);
+            let dofs: Vec<usize> = (0..3).map(|d| 3 * a + d).chain((0..3).map(|d| 3 * b + d)).collect();
+            for (i, row) in ke.iter().enumerate() {
+                for (j, v) in row.iter().enumerate() {
+                    k.add(dofs[i], dofs[j], *v);
+                }
+            }
+        }
+        let mut f = vec![0.0; 9];
+        f[7] = -load;
+        let free = solve_free(&k, &f, &[6, 7]);
+        let top = [free[0], free[1], 0.0];
+        // each bar carries half the load along its 45 degree slope, in compression
+        let expected = -load / 2.0 / (std::f64::consts::PI / 4.0).sin();
+        for (a, b) in bars {
+            let (e, len) = axis(p[a], p[b]);
+            let force = bar_force(e, len, ea, [0.0; 3], top);
+            assert!((force - expected).abs() < 1e-9, "{} vs {}", force, expected);
+        }
+    }
+}
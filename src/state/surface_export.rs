@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+
+use super::deform::DeformSettings;
+use super::gltf;
+use super::td_comp::TdObject;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshFormat {
+    StlAscii,
+    StlBinary,
+    Obj,
+    Ply,
+    Gltf,
+    Glb,
+}
+
+impl MeshFormat {
+    pub const ALL: [MeshFormat; 6] = [
+        MeshFormat::StlBinary,
+        MeshFormat::StlAscii,
+        MeshFormat::Obj,
+        MeshFormat::Ply,
+        MeshFormat::Gltf,
+        MeshFormat::Glb,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MeshFormat::StlAscii => "STL (ascii)",
+            MeshFormat::StlBinary => "STL (binary)",
+            MeshFormat::Obj => "OBJ",
+            MeshFormat::Ply => "PLY",
+            MeshFormat::Gltf => "glTF",
+            MeshFormat::Glb => "glTF (binary)",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            MeshFormat::StlAscii | MeshFormat::StlBinary => "stl",
+            MeshFormat::Obj => "obj",
+            MeshFormat::Ply => "ply",
+            MeshFormat::Gltf => "gltf",
+            MeshFormat::Glb => "glb",
+        }
+    }
+
+    // binary for .stl, ascii is asked for separately
+    pub fn from_filename(filename: &str, ascii: bool) -> Option<MeshFormat> {
+        match filename.rsplit('.').next()?.to_lowercase().as_str() {
+            "stl" if ascii => Some(MeshFormat::StlAscii),
+            "stl" => Some(MeshFormat::StlBinary),
+            "obj" => Some(MeshFormat::Obj),
+            "ply" => Some(MeshFormat::Ply),
+            "gltf" => Some(MeshFormat::Gltf),
+            "glb" => Some(MeshFormat::Glb),
+            _ => None,
+        }
+    }
+
+    // stl has nowhere to put them
+    pub fn has_colors(self) -> bool {
+        !matches!(self, MeshFormat::StlAscii | MeshFormat::StlBinary)
+    }
+
+    // deformation goes in as morph targets that can play, not baked in
+    pub fn is_gltf(self) -> bool {
+        matches!(self, MeshFormat::Gltf | MeshFormat::Glb)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfaceExportSettings {
+    pub format: MeshFormat,
+    pub deformed: bool, // baked in, or morph targets for gltf
+    pub colors: bool, // the current field colors as vertex colors
+}
+
+impl Default for SurfaceExportSettings {
+    fn default() -> Self {
+        Self { format: MeshFormat::StlBinary, deformed: false, colors: true }
+    }
+}
+
+// the outer surface with only the nodes it uses, renumbered from 0
+pub struct SurfaceMesh {
+    pub nodes: Vec<u32>, // model node of each vertex
+    pub positions: Vec<[f32; 3]>,
+    pub colors: Vec<[f32; 3]>,
+    pub tris: Vec<[u32; 3]>,
+}
+
+impl SurfaceMesh {
+    pub fn new(obj: &TdObject, deformed: bool) -> Self {
+        let mut index: HashMap<u32, u32> = HashMap::new();
+        let mut mesh = SurfaceMesh { nodes: Vec::new(), positions: Vec::new(), colors: Vec::new(), tris: Vec::new() };
+        for tri in obj.surface_tris() {
+            let tri = tri.map(|n| {
+                *index.entry(n).or_insert_with(|| {
+                    let p = if deformed {
+                        obj.display_position(n)
+                    } else {
+                        obj.vertices[n as usize].position
+                    };
+                    let c = obj.vertices[n as usize].color;
+                    mesh.nodes.push(n);
+                    mesh.positions.push([p[0], p[1], p[2]]);
+                    mesh.colors.push([c[0], c[1], c[2]]);
+                    (mesh.positions.len() - 1) as u32
+                })
+            });
+            mesh.tris.push(tri);
+        }
+        mesh
+    }
+
+    fn normal(&self, tri: [u32; 3]) -> [f32; 3] {
+        let [a, b, c] = tri.map(|n| self.positions[n as usize]);
+        let (u, v) = ([b[0] - a[0], b[1] - a[1], b[2] - a[2]], [c[0] - a[0], c[1] - a[1], c[2] - a[2]]);
+        let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+        let l = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        if l > 0.0 {
+            n.map(|v| v / l)
+        } else {
+            [0.0; 3]
+        }
+    }
+
+    pub fn stl_ascii(&self, name: &str) -> String {
+        let mut out = format!("solid {}\n", name);
+        for tri in &self.tris {
+            let n = self.normal(*tri);
+            let _ = writeln!(out, "  facet normal {:e} {:e} {:e}\n    outer loop", n[0], n[1], n[2]);
+            for v in tri {
+                let p = self.positions[*v as usize];
+                let _ = writeln!(out, "      vertex {:e} {:e} {:e}", p[0], p[1], p[2]);
+            }
+            out += "    endloop\n  endfacet\n";
+        }
+        out + &format!("endsolid {}\n", name)
+    }
+
+    // 80 byte header, triangle count, then normal, corners and a zero attribute per triangle
+    pub fn stl_binary(&self, name: &str) -> Vec<u8> {
+        let mut out = Vec::with_capacity(84 + 50 * self.tris.len());
+        let mut header = [0u8; 80];
+        let text = format!("femjsonwinit {}", name);
+        let len = text.len().min(80);
+        header[..len].copy_from_slice(&text.as_bytes()[..len]);
+        out.extend_from_slice(&header);
+        out.extend_from_slice(&(self.tris.len() as u32).to_le_bytes());
+        for tri in &self.tris {
+            let corners = tri.map(|v| self.positions[v as usize]);
+            for v in std::iter::once(self.normal(*tri)).chain(corners).flatten() {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            out.extend_from_slice(&0u16.to_le_bytes());
+        }
+        out
+    }
+
+    // colors as the widely read "v x y z r g b" extension
+    pub fn obj(&self, colors: bool) -> String {
+        let mut out = String::from("# exported by femjsonwinit\n");
+        for (p, c) in self.positions.iter().zip(&self.colors) {
+            if colors {
+                let _ = writeln!(out, "v {} {} {} {} {} {}", p[0], p[1], p[2], c[0], c[1], c[2]);
+            } else {
+                let _ = writeln!(out, "v {} {} {}", p[0], p[1], p[2]);
+            }
+        }
+        for t in &self.tris {
+            let _ = writeln!(out, "f {} {} {}", t[0] + 1, t[1] + 1, t[2] + 1);
+        }
+        out
+    }
+
+    pub fn ply(&self, colors: bool) -> String {
+        let mut out = format!(
+            "ply\nformat ascii 1.0\ncomment exported by femjsonwinit\nelement vertex {}\nproperty float x\nproperty float y\nproperty float z\n",
+            self.positions.len()
+        );
+        if colors {
+            out += "property uchar red\nproperty uchar green\nproperty uchar blue\n";
+        }
+        let _ = write!(out, "element face {}\nproperty list uchar int vertex_indices\nend_header\n", self.tris.len());
+        for (p, c) in self.positions.iter().zip(&self.colors) {
+            let _ = write!(out, "{} {} {}", p[0], p[1], p[2]);
+            if colors {
+                let [r, g, b] = c.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8);
+                let _ = write!(out, " {} {} {}", r, g, b);
+            }
+            out += "\n";
+        }
+        for t in &self.tris {
+            let _ = writeln!(out, "3 {} {} {}", t[0], t[1], t[2]);
+        }
+        out
+    }
+}
+
+// gltf takes its deformation from the settings, the others the one drawn
+pub fn save(obj: &TdObject, filename: &str, settings: &SurfaceExportSettings, deform: &DeformSettings) -> Result<usize, String> {
+    let mesh = SurfaceMesh::new(obj, settings.deformed && !settings.format.is_gltf());
+    if mesh.tris.is_empty() {
+        return Err("the model has no surface".to_string());
+    }
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+    let name = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let colors = settings.colors && settings.format.has_colors();
+    let written = match settings.format {
+        MeshFormat::StlAscii => fs::write(filename, mesh.stl_ascii(name)),
+        MeshFormat::StlBinary => fs::write(filename, mesh.stl_binary(name)),
+        MeshFormat::Obj => fs::write(filename, mesh.obj(colors)),
+        MeshFormat::Ply => fs::write(filename, mesh.ply(colors)),
+        MeshFormat::Gltf => fs::write(filename, gltf::gltf(obj, colors, settings.deformed.then_some(deform))),
+        MeshFormat::Glb => fs::write(filename, gltf::glb(obj, colors, settings.deformed.then_some(deform))),
+    };
+    written.map_err(|e| e.to_string())?;
+    Ok(mesh.tris.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // one tet, with a node before it no element uses
+    fn tet() -> SurfaceMesh {
+        let doc = json!({
+            "vers": [[9.0, 9.0, 9.0], [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            "elems": [{ "kind": "tet4", "nodes": [1, 2, 3, 4] }],
+        });
+        SurfaceMesh::new(&TdObject::from_json(&doc.to_string()), false)
+    }
+
+    #[test]
+    fn binary_stl_size_and_count() {
+        let mesh = tet();
+        assert_eq!(mesh.tris.len(), 4);
+        let stl = mesh.stl_binary("tet");
+        assert_eq!(stl.len(), 84 + 50 * 4);
+        assert!(stl.starts_with(b"femjsonwinit tet"));
+        assert_eq!(u32::from_le_bytes(stl[80..84].try_into().unwrap()), 4);
+    }
+
+    #[test]
+    fn ply_header_matches_the_body() {
+        let mesh = tet();
+        for colors in [false, true] {
+            let ply = mesh.ply(colors);
+            let (header, body) = ply.split_once("end_header\n").unwrap();
+            assert!(header.contains("element vertex 4\n"));
+            assert!(header.contains("element face 4\n"));
+            assert_eq!(header.contains("property uchar red"), colors);
+            let lines: Vec<&str> = body.lines().collect();
+            assert_eq!(lines.len(), 4 + 4);
+            let width = if colors { 6 } else { 3 };
+            assert!(lines[..4].iter().all(|l| l.split(' ').count() == width));
+            assert!(lines[4..].iter().all(|l| l.starts_with("3 ") && l.split(' ').count() == 4));
+        }
+    }
+
+    #[test]
+    fn obj_faces_are_one_based() {
+        let obj = tet().obj(false);
+        assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 4);
+        let indices: Vec<u32> = obj
+            .lines()
+            .filter_map(|l| l.strip_prefix("f "))
+            .flat_map(|l| l.split(' ').map(|i| i.parse().unwrap()).collect::<Vec<u32>>())
+            .collect();
+        assert_eq!(indices.len(), 4 * 3);
+        assert_eq!(indices.iter().min(), Some(&1));
+        assert_eq!(indices.iter().max(), Some(&4));
+    }
+}
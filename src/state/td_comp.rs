@@ -2,45 +2,420 @@ use bytemuck::{Pod, Zeroable};
 use serde::{Deserialize, Serialize};
 use std::fs;
 
+use super::elements::{self, Element, ElementKind};
+use super::model::ModelData;
+use super::quality;
+use super::topology::{self, TopologySettings};
+use super::fields::{self, CellMode, ColorBy, Colormap, Field, Location, Step, VectorField};
+use super::tensor::TensorField;
+use std::collections::HashMap;
+
+const DEFAULT_COLOR: [f32; 3] = [0.0, 0.0, 1.0];
+
 #[derive(Serialize, Deserialize)]
 pub struct TdObject {
     pub vertices: Vec<Vertex>, // a vector of a Vertex struct (see line 29 or just go to type def in vscode)
-    pub indices : Vec<u16>      // indices of the vertices
+    pub indices : Vec<u16>,     // indices of the vertices
+    pub elements : Vec<Element>, // typed elements, may be empty
+    pub surface : Vec<u32>,     // outer faces of the elements as a triangle list
+    pub surface_owner : Vec<u32>, // element of each surface triangle
+    pub edges : Vec<u32>,       // element edges as a line list
+    pub fields : Vec<Field>,    // nodal results, may be empty
+    pub vectors : Vec<VectorField>, // vector results, may be empty
+    pub tensors : Vec<TensorField>, // stress / strain tensors, may be empty
+    pub steps : Vec<Step>,      // time steps or mode shapes, may be empty
+    #[serde(skip)]
+    pub deformation : Vec<[f32; 3]>, // scaled displacement shown on the mesh, may be empty
+    pub model : ModelData       // materials, supports, loads for the solvers
 }
 #[derive(Serialize, Deserialize)]
 struct JsonIn {
     vers : Vec<[f32;3]>,
-    inds : Vec<u16>
+    #[serde(default)]
+    inds : Vec<u16>,
+    #[serde(default)]
+    elems : Vec<Element>,
+    #[serde(default)]
+    fields : Vec<Field>,
+    #[serde(default)]
+    vectors : Vec<VectorField>,
+    #[serde(default)]
+    tensors : Vec<TensorField>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    steps : Vec<Step>,
+    #[serde(flatten)]
+    model : ModelData
 }
 
 impl TdObject {
     pub fn new(filename : &str) -> Self {
         let  file_data =fs::read_to_string(filename) 
             .expect("FILE READ ERROR ");
-        
-        let json_data:JsonIn = serde_json::from_str(&file_data).unwrap();
+        Self::from_json(&file_data)
+    }
+
+    // the same model from the text of a file
+    pub fn from_json(file_data: &str) -> Self {
+        let json_data:JsonIn = serde_json::from_str(file_data).unwrap();
         let indices: Vec<u16> = json_data.inds;
         let vertices = create_vertices(json_data.vers);
-        TdObject { vertices, indices }
+        let elements = elements::validate(json_data.elems, vertices.len());
+        let (surface, surface_owner) = elements::surface_indices(&elements);
+        let edges = elements::edge_indices(&elements);
+        let counts = (vertices.len(), elements.len());
+        let fields = json_data
+            .fields
+            .into_iter()
+            .filter(|f| has_len("field", &f.name, f.values.len(), f.location, counts))
+            .collect();
+        let vectors = json_data
+            .vectors
+            .into_iter()
+            .filter(|f| has_len("vector field", &f.name, f.values.len(), f.location, counts))
+            .collect();
+        let tensors = json_data
+            .tensors
+            .into_iter()
+            .filter(|f| has_len("tensor field", &f.name, f.values.len(), f.location, counts))
+            .collect();
+        let steps = json_data
+            .steps
+            .into_iter()
+            .map(|mut s| {
+                s.fields.retain(|f| has_len("field", &f.name, f.values.len(), f.location, counts));
+                s.vectors.retain(|f| has_len("vector field", &f.name, f.values.len(), f.location, counts));
+                s
+            })
+            .collect();
+        let model = json_data.model;
+        TdObject {
+            vertices, indices, elements, surface, surface_owner, edges, fields, vectors, tensors, steps,
+            deformation: Vec::new(),
+            model,
+        }
+    }
+
+    pub fn positions(&self) -> Vec<[f32; 3]> {
+        self.vertices
+            .iter()
+            .map(|v| [v.position[0], v.position[1], v.position[2]])
+            .collect()
+    }
+
+    // where node n is drawn, moved by the deformation when there is one
+    pub fn display_position(&self, n: u32) -> [f32; 4] {
+        let mut p = self.vertices[n as usize].position;
+        if let Some(d) = self.deformation.get(n as usize) {
+            for k in 0..3 {
+                p[k] += d[k];
+            }
+        }
+        p
     }
+
+    // every node where it is drawn
+    pub fn display_positions(&self) -> Vec<[f32; 3]> {
+        (0..self.vertices.len() as u32)
+            .map(|n| {
+                let p = self.display_position(n);
+                [p[0], p[1], p[2]]
+            })
+            .collect()
+    }
+
+    // the vertices as drawn
+    pub fn display_vertices(&self) -> Vec<Vertex> {
+        if self.deformation.is_empty() {
+            return self.vertices.clone();
+        }
+        (0..self.vertices.len() as u32)
+            .map(|n| Vertex {
+                position: self.display_position(n),
+                color: self.vertices[n as usize].color,
+            })
+            .collect()
+    }
+
+    // average of the element's nodes
+    pub fn centroid(&self, e: &Element) -> [f32; 3] {
+        let mut c = [0.0; 3];
+        for n in &e.nodes {
+            let p = self.vertices[*n as usize].position;
+            for k in 0..3 {
+                c[k] += p[k] / e.nodes.len() as f32;
+            }
+        }
+        c
+    }
+
+    // average of the element's nodes where they are drawn
+    pub fn display_centroid(&self, e: &Element) -> [f32; 3] {
+        let mut c = [0.0; 3];
+        for n in &e.nodes {
+            let p = self.display_position(*n);
+            for k in 0..3 {
+                c[k] += p[k] / e.nodes.len() as f32;
+            }
+        }
+        c
+    }
+
+    // nodes of one face of an element, an edge for surface elements
+    pub fn face_nodes(&self, element: usize, face: usize) -> Option<Vec<u32>> {
+        let e = self.elements.get(element)?;
+        let local: &[usize] = if e.kind.is_volume() {
+            e.kind.faces().get(face)?
+        } else {
+            e.kind.edges().get(face)?
+        };
+        Some(local.iter().map(|l| e.nodes[*l]).collect())
+    }
+
+    // all volume elements split into linear tets
+    pub fn tets(&self) -> Vec<[u32; 4]> {
+        self.elements.iter().flat_map(|e| e.tets()).collect()
+    }
+
+    pub fn surface_tris(&self) -> Vec<[u32; 3]> {
+        self.surface.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect()
+    }
+
+    // typed elements, or the line segments of the strip for old files
+    pub fn element_count(&self) -> usize {
+        if self.elements.is_empty() {
+            self.indices.len().saturating_sub(1)
+        } else {
+            self.elements.len()
+        }
+    }
+
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        let mut lo = [f32::MAX; 3];
+        let mut hi = [f32::MIN; 3];
+        for v in &self.vertices {
+            for k in 0..3 {
+                lo[k] = lo[k].min(v.position[k]);
+                hi[k] = hi[k].max(v.position[k]);
+            }
+        }
+        (lo, hi)
+    }
+
+    // center of the bounding box and half its diagonal
+    pub fn center_radius(&self) -> ([f32; 3], f32) {
+        let (lo, hi) = self.bounds();
+        let center = [(lo[0] + hi[0]) / 2.0, (lo[1] + hi[1]) / 2.0, (lo[2] + hi[2]) / 2.0];
+        let d = [hi[0] - lo[0], hi[1] - lo[1], hi[2] - lo[2]];
+        let radius = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt() / 2.0;
+        (center, radius.max(f32::EPSILON))
+    }
+
+    // element values averaged over the elements around each node, split by
+    // group when `across_groups` is off (a node then gets one value per group)
+    fn averaged(&self, field: &Field, across_groups: bool) -> HashMap<(u32, &str), f32> {
+        let mut sums: HashMap<(u32, &str), (f32, u32)> = HashMap::new();
+        for (e, val) in self.elements.iter().zip(&field.values) {
+            let group = if across_groups { "" } else { e.group.as_str() };
+            for n in &e.nodes {
+                let s = sums.entry((*n, group)).or_insert((0.0, 0));
+                s.0 += val;
+                s.1 += 1;
+            }
+        }
+        sums.into_iter().map(|(k, (sum, n))| (k, sum / n as f32)).collect()
+    }
+
+    // one value per node whatever the field location, nodes outside every
+    // element get the field minimum
+    pub fn nodal_values(&self, field: &Field, across_groups: bool) -> Vec<f32> {
+        match field.location {
+            Location::Node => field.values.clone(),
+            Location::Element => {
+                let avg = self.averaged(field, across_groups);
+                let lo = field.range().0;
+                let mut out = vec![lo; self.vertices.len()];
+                for ((n, _), v) in avg {
+                    out[n as usize] = v;
+                }
+                out
+            }
+        }
+    }
+
+    // un-shared surface triangles so element fields can jump between
+    // neighbours, either flat or averaged per group
+    pub fn cell_vertices(&self, field: &Field, mode: CellMode, across_groups: bool, cmap: Colormap) -> Vec<Vertex> {
+        let (lo, hi) = field.range();
+        let avg = match mode {
+            CellMode::Flat => HashMap::new(),
+            CellMode::Averaged => self.averaged(field, across_groups),
+        };
+        let mut out = Vec::with_capacity(self.surface.len());
+        for (tri, owner) in self.surface.chunks_exact(3).zip(&self.surface_owner) {
+            let e = &self.elements[*owner as usize];
+            let group = if across_groups { "" } else { e.group.as_str() };
+            for n in tri {
+                let val = match mode {
+                    CellMode::Flat => field.values[*owner as usize],
+                    CellMode::Averaged => avg[&(*n, group)],
+                };
+                let c = cmap.map(val, lo, hi);
+                out.push(Vertex { position: self.display_position(*n), color: [c[0], c[1], c[2], 1.0] });
+            }
+        }
+        out
+    }
+
+    // name of the group, material or section of an element, empty for none
+    pub fn category<'a>(&'a self, e: &'a Element, by: ColorBy) -> &'a str {
+        match by {
+            ColorBy::Field | ColorBy::Group => &e.group,
+            ColorBy::Material => self.model.material(&e.group).map_or("", |m| m.name.as_str()),
+            ColorBy::Section => self.model.section(&e.group).map_or("", |s| s.name.as_str()),
+        }
+    }
+
+    // sorted names in use, the legend order and the color index
+    pub fn categories(&self, by: ColorBy) -> Vec<&str> {
+        let mut names: Vec<&str> = self.elements.iter().map(|e| self.category(e, by)).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // color of every element by category
+    pub fn category_colors(&self, by: ColorBy) -> Vec<[f32; 4]> {
+        let names = self.categories(by);
+        self.elements
+            .iter()
+            .map(|e| {
+                let i = names.binary_search(&self.category(e, by)).unwrap_or(0);
+                let c = fields::category_color(i);
+                [c[0], c[1], c[2], 1.0]
+            })
+            .collect()
+    }
+
+    // surface triangles and line elements colored by category
+    pub fn category_vertices(&self, by: ColorBy) -> (Vec<Vertex>, Vec<Vertex>) {
+        self.element_vertices(&self.category_colors(by))
+    }
+
+    // surface triangles and line elements with one color per element
+    pub fn element_vertices(&self, colors: &[[f32; 4]]) -> (Vec<Vertex>, Vec<Vertex>) {
+        let mut tris = Vec::with_capacity(self.surface.len());
+        for (tri, owner) in self.surface.chunks_exact(3).zip(&self.surface_owner) {
+            let c = colors[*owner as usize];
+            tris.extend(tri.iter().map(|n| Vertex { position: self.display_position(*n), color: c }));
+        }
+        let mut lines = Vec::new();
+        for (e, c) in self.elements.iter().zip(colors).filter(|(e, _)| e.kind == ElementKind::Line2) {
+            lines.extend(e.nodes.iter().map(|n| Vertex { position: self.display_position(*n), color: *c }));
+        }
+        (tris, lines)
+    }
+
+    // counts, extent, results and properties as text
+    pub fn info(&self) -> String {
+        let (lo, hi) = self.bounds();
+        let mut out = format!("nodes {}\nelements {}\n", self.vertices.len(), self.element_count());
+        let mut kinds: Vec<(&str, usize)> = Vec::new();
+        for e in &self.elements {
+            match kinds.iter_mut().find(|(k, _)| *k == e.kind.name()) {
+                Some((_, n)) => *n += 1,
+                None => kinds.push((e.kind.name(), 1)),
+            }
+        }
+        for (kind, n) in kinds {
+            out += &format!("  {} {}\n", kind, n);
+        }
+        out += &format!("min {:?}\nmax {:?}\n", lo, hi);
+        for f in &self.fields {
+            let (a, b) = f.range();
+            out += &format!("field {} ({:?}): {} .. {} {}\n", f.name, f.location, a, b, f.units);
+        }
+        for v in &self.vectors {
+            out += &format!("vectors {} ({:?})\n", v.name, v.location);
+        }
+        for t in &self.tensors {
+            out += &format!("tensors {} ({:?})\n", t.name, t.location);
+        }
+        if !self.steps.is_empty() {
+            out += &format!("steps {}\n", self.steps.len());
+        }
+        let topology = topology::check(self, &TopologySettings::default()).report();
+        out + &self.model.describe() + &topology + &quality::summary(self)
+    }
+
+    // recolor the vertices from a field, or back to the plain color when None
+    pub fn apply_colors(&mut self, field: Option<usize>, cmap: Colormap) {
+        let field = field.and_then(|i| self.fields.get(i));
+        match field {
+            Some(f) => {
+                let (lo, hi) = f.range();
+                let values = self.nodal_values(f, true);
+                for (v, val) in self.vertices.iter_mut().zip(&values) {
+                    let c = cmap.map(*val, lo, hi);
+                    v.color = [c[0], c[1], c[2], 1.0];
+                }
+            }
+            None => {
+                for v in self.vertices.iter_mut() {
+                    v.color = [DEFAULT_COLOR[0], DEFAULT_COLOR[1], DEFAULT_COLOR[2], 1.0];
+                }
+            }
+        }
+    }
+}
+
+// a result needs one value per node or per element, warns and drops it otherwise
+fn has_len(what: &str, name: &str, len: usize, location: Location, (nodes, elements): (usize, usize)) -> bool {
+    let expected = match location {
+        Location::Node => nodes,
+        Location::Element => elements,
+    };
+    if len != expected {
+        log::warn!("{} {} has {} values, expected {}, skipped", what, name, len, expected);
+    }
+    len == expected
+}
+
+// writes a mesh in the same format `TdObject::new` reads
+pub fn write_json(
+    filename: &str,
+    vers: Vec<[f32; 3]>,
+    elems: Vec<Element>,
+    fields: Vec<Field>,
+) -> std::io::Result<()> {
+    let json_data = JsonIn {
+        vers,
+        inds: Vec::new(),
+        elems,
+        fields,
+        vectors: Vec::new(),
+        tensors: Vec::new(),
+        steps: Vec::new(),
+        model: ModelData::default(),
+    };
+    fs::write(filename, serde_json::to_string_pretty(&json_data)?)
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable, Serialize, Deserialize)]
 pub struct Vertex {
-    position: [f32; 4],
-    color: [f32; 4],
+    pub position: [f32; 4],
+    pub color: [f32; 4],
 }
 
-fn vertex(p: [f32; 3], c: [i8; 3]) -> Vertex {
+fn vertex(p: [f32; 3], c: [f32; 3]) -> Vertex {
     Vertex {
         position: [p[0],p[1],p[2],1.0],
-        color: [c[0] as f32, c[1] as f32, c[2] as f32, 1.0],
+        color: [c[0], c[1], c[2], 1.0],
     }
 }
 
 fn create_vertices(pos_from_json: Vec<[f32; 3]>) -> Vec<Vertex> {
-    let col: [i8; 3] = [0, 0, 1];
+    let col = DEFAULT_COLOR;
     let mut data: Vec<Vertex> = Vec::with_capacity(pos_from_json.len());
     for i in pos_from_json {
         data.push(vertex(i, col));
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+
+use super::fields::{Field, Location, VectorField};
+
+const JACOBI_SWEEPS: usize = 32;
+
+// symmetric tensor per node or element, components ordered xx yy zz xy yz xz
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TensorField {
+    pub name: String,
+    #[serde(default)]
+    pub units: String,
+    #[serde(default)]
+    pub location: Location,
+    pub values: Vec<[f32; 6]>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quantity {
+    VonMises,
+    Tresca,
+    Principal(usize), // 0 is the largest
+    Pressure,
+    MaxShear,
+    Component(usize),
+}
+
+const COMPONENTS: [&str; 6] = ["XX", "YY", "ZZ", "XY", "YZ", "XZ"];
+
+impl Quantity {
+    pub const ALL: [Quantity; 13] = [
+        Quantity::VonMises,
+        Quantity::Tresca,
+        Quantity::Principal(0),
+        Quantity::Principal(1),
+        Quantity::Principal(2),
+        Quantity::Pressure,
+        Quantity::MaxShear,
+        Quantity::Component(0),
+        Quantity::Component(1),
+        Quantity::Component(2),
+        Quantity::Component(3),
+        Quantity::Component(4),
+        Quantity::Component(5),
+    ];
+
+    pub fn name(self) -> String {
+        match self {
+            Quantity::VonMises => "von Mises".to_string(),
+            Quantity::Tresca => "Tresca".to_string(),
+            Quantity::Principal(i) => format!("Principal {}", i + 1),
+            Quantity::Pressure => "Pressure".to_string(),
+            Quantity::MaxShear => "Max shear".to_string(),
+            Quantity::Component(i) => COMPONENTS[i].to_string(),
+        }
+    }
+
+    pub fn eval(self, t: &[f32; 6]) -> f32 {
+        let [xx, yy, zz, xy, yz, xz] = t.map(|v| v as f64);
+        let value = match self {
+            Quantity::VonMises => (0.5
+                * ((xx - yy).powi(2) + (yy - zz).powi(2) + (zz - xx).powi(2))
+                + 3.0 * (xy * xy + yz * yz + xz * xz))
+                .sqrt(),
+            Quantity::Tresca => {
+                let (p, _) = principal(t);
+                p[0] - p[2]
+            }
+            Quantity::Principal(i) => principal(t).0[i],
+            Quantity::Pressure => -(xx + yy + zz) / 3.0,
+            Quantity::MaxShear => {
+                let (p, _) = principal(t);
+                (p[0] - p[2]) / 2.0
+            }
+            Quantity::Component(i) => t[i] as f64,
+        };
+        value as f32
+    }
+}
+
+impl TensorField {
+    pub fn derive(&self, q: Quantity) -> Field {
+        Field {
+            name: format!("{} {}", self.name, q.name()),
+            units: self.units.clone(),
+            location: self.location,
+            values: self.values.iter().map(|t| q.eval(t)).collect(),
+            layer: None,
+        }
+    }
+
+    // direction of principal value i, as long as the value itself
+    pub fn principal_directions(&self, i: usize) -> VectorField {
+        VectorField {
+            name: format!("{} direction {}", self.name, i + 1),
+            units: self.units.clone(),
+            location: self.location,
+            values: self
+                .values
+                .iter()
+                .map(|t| {
+                    let (p, dirs) = principal(t);
+                    dirs[i].map(|d| (d * p[i].abs()) as f32)
+                })
+                .collect(),
+        }
+    }
+}
+
+// principal values (largest first) and their unit directions, by cyclic jacobi
+pub fn principal(t: &[f32; 6]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let t = t.map(|v| v as f64);
+    let mut a = [
+        [t[0], t[3], t[5]],
+        [t[3], t[1], t[4]],
+        [t[5], t[4], t[2]],
+    ];
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..JACOBI_SWEEPS {
+        let off = a[0][1].abs() + a[1][2].abs() + a[0][2].abs();
+        if off < 1e-12 * (a[0][0].abs() + a[1][1].abs() + a[2][2].abs()).max(1e-30) {
+            break;
+        }
+        for (p, q) in [(0, 1), (1, 2), (0, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            // signum(0.0) is 1.0 so theta == 0 gives the 45 degree rotation
+            let tan = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (tan * tan + 1.0).sqrt();
+            let s = tan * c;
+            // a <- J^T a J with the rotation in the p-q plane
+            for row in a.iter_mut() {
+                let (akp, akq) = (row[p], row[q]);
+                row[p] = c * akp - s * akq;
+                row[q] = s * akp + c * akq;
+            }
+            let (rp, rq) = (a[p], a[q]);
+            a[p] = std::array::from_fn(|k| c * rp[k] - s * rq[k]);
+            a[q] = std::array::from_fn(|k| s * rp[k] + c * rq[k]);
+            for row in v.iter_mut() {
+                let (vp, vq) = (row[p], row[q]);
+                row[p] = c * vp - s * vq;
+                row[q] = s * vp + c * vq;
+            }
+        }
+    }
+    // eigenvectors are the columns of v
+    let mut pairs: Vec<(f64, [f64; 3])> = (0..3)
+        .map(|i| (a[i][i], [v[0][i], v[1][i], v[2][i]]))
+        .collect();
+    pairs.sort_by(|x, y| y.0.total_cmp(&x.0));
+    (
+        [pairs[0].0, pairs[1].0, pairs[2].0],
+        [pairs[0].1, pairs[1].1, pairs[2].1],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-4 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn uniaxial() {
+        let t = [100.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        let (p, dirs) = principal(&t);
+        assert!(close(p[0], 100.0) && close(p[1], 0.0) && close(p[2], 0.0), "{:?}", p);
+        assert!(close(dirs[0][0].abs(), 1.0));
+        assert!(close(Quantity::Tresca.eval(&t) as f64, 100.0));
+        assert!(close(Quantity::MaxShear.eval(&t) as f64, 50.0));
+        assert!(close(Quantity::VonMises.eval(&t) as f64, 100.0));
+    }
+
+    #[test]
+    fn pure_shear() {
+        let t = [0.0, 0.0, 0.0, 50.0, 0.0, 0.0];
+        let (p, dirs) = principal(&t);
+        assert!(close(p[0], 50.0) && close(p[1], 0.0) && close(p[2], -50.0), "{:?}", p);
+        // the largest along the diagonal of the xy plane
+        let h = 0.5f64.sqrt();
+        assert!(close(dirs[0][0].abs(), h) && close(dirs[0][1].abs(), h));
+        assert!(close(Quantity::Tresca.eval(&t) as f64, 100.0));
+        assert!(close(Quantity::MaxShear.eval(&t) as f64, 50.0));
+        assert!(close(Quantity::VonMises.eval(&t) as f64, 50.0 * 3.0f64.sqrt()));
+    }
+
+    #[test]
+    fn hydrostatic() {
+        let t = [-30.0, -30.0, -30.0, 0.0, 0.0, 0.0];
+        assert!(close(Quantity::Pressure.eval(&t) as f64, 30.0));
+        assert!(close(Quantity::VonMises.eval(&t) as f64, 0.0));
+        assert!(close(Quantity::Tresca.eval(&t) as f64, 0.0));
+        for i in 0..3 {
+            assert!(close(Quantity::Principal(i).eval(&t) as f64, -30.0));
+        }
+    }
+
+    #[test]
+    fn principal_values_are_sorted() {
+        let t = [10.0, -20.0, 5.0, 7.0, 3.0, -4.0];
+        let (p, dirs) = principal(&t);
+        assert!(p[0] >= p[1] && p[1] >= p[2], "{:?}", p);
+        // the trace is kept and the directions are orthonormal
+        assert!(close(p.iter().sum(), -5.0));
+        for i in 0..3 {
+            for j in 0..3 {
+                let d: f64 = (0..3).map(|k| dirs[i][k] * dirs[j][k]).sum();
+                assert!(close(d, if i == j { 1.0 } else { 0.0 }));
+            }
+        }
+        for (i, value) in p.iter().enumerate() {
+            assert!(close(Quantity::Principal(i).eval(&t) as f64, *value));
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use super::elements::{self, ElementKind};
+use super::fields;
+use super::quality::{self, Metric};
+use super::td_comp::{TdObject, Vertex};
+
+// node crosses at size 1, relative to the model radius
+const MARK_SIZE: f32 = 0.015;
+
+const COINCIDENT_COLOR: [f32; 3] = [1.0, 0.2, 0.2];
+const UNUSED_COLOR: [f32; 3] = [1.0, 0.8, 0.0];
+const FREE_EDGE_COLOR: [f32; 3] = [1.0, 1.0, 0.2];
+const NONMANIFOLD_COLOR: [f32; 3] = [1.0, 0.2, 1.0];
+const INVERTED_COLOR: [f32; 3] = [1.0, 0.4, 0.0];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TopologySettings {
+    pub tolerance: f32, // for coincident nodes, relative to the model radius
+    pub highlight: bool,
+    pub regions: bool, // color the elements by connected region
+}
+
+impl Default for TopologySettings {
+    fn default() -> Self {
+        Self { tolerance: 1e-6, highlight: true, regions: false }
+    }
+}
+
+// what is wrong with the mesh, all by node or element index
+#[derive(Default)]
+pub struct Diagnostics {
+    pub coincident: Vec<Vec<u32>>, // groups of nodes within the tolerance
+    pub unused: Vec<u32>,
+    pub free_edges: Vec<[u32; 2]>, // edges of only one surface element
+    pub nonmanifold_edges: Vec<[u32; 2]>, // edges of three or more
+    pub regions: Vec<usize>, // connected region of each element
+    pub region_count: usize,
+    pub inverted: Vec<usize>, // volume elements with a negative volume
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    // the smaller index wins so merged nodes keep the first one
+    parent[a.max(b)] = a.min(b);
+}
+
+// nodes bucketed on a grid of the tolerance, each checked against its neighbours
+fn coincident(obj: &TdObject, tolerance: f32) -> Vec<Vec<u32>> {
+    let positions = obj.positions();
+    let cell = tolerance.max(f32::MIN_POSITIVE);
+    let key = |p: [f32; 3]| p.map(|v| (v / cell).floor() as i64);
+    let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    for (i, p) in positions.iter().enumerate() {
+        grid.entry(key(*p)).or_default().push(i);
+    }
+    let mut parent: Vec<usize> = (0..positions.len()).collect();
+    for (i, p) in positions.iter().enumerate() {
+        let k = key(*p);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let Some(near) = grid.get(&[k[0] + dx, k[1] + dy, k[2] + dz]) else {
+                        continue;
+                    };
+                    for j in near.iter().filter(|j| **j > i) {
+                        let q = positions[*j];
+                        let d2: f32 = (0..3).map(|c| (p[c] - q[c]) * (p[c] - q[c])).sum();
+                        if d2 <= tolerance * tolerance {
+                            union(&mut parent, i, *j);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let mut groups: HashMap<usize, Vec<u32>> = HashMap::new();
+    for i in 0..positions.len() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(i as u32);
+    }
+    let mut out: Vec<Vec<u32>> = groups.into_values().filter(|g| g.len() > 1).collect();
+    out.sort();
+    out
+}
+
+pub fn check(obj: &TdObject, settings: &TopologySettings) -> Diagnostics {
+    let (_, radius) = obj.center_radius();
+    let mut d = Diagnostics {
+        coincident: coincident(obj, settings.tolerance * radius),
+        ..Default::default()
+    };
+    if obj.elements.is_empty() {
+        return d;
+    }
+
+    let mut used = vec![false; obj.vertices.len()];
+    let mut parent: Vec<usize> = (0..obj.vertices.len()).collect();
+    let mut edge_count: HashMap<[u32; 2], usize> = HashMap::new();
+    for e in &obj.elements {
+        for n in &e.nodes {
+            used[*n as usize] = true;
+            union(&mut parent, e.nodes[0] as usize, *n as usize);
+        }
+        if matches!(e.kind, ElementKind::Tri3 | ElementKind::Quad4) {
+            for [a, b] in e.kind.edges() {
+                let (a, b) = (e.nodes[*a], e.nodes[*b]);
+                *edge_count.entry([a.min(b), a.max(b)]).or_insert(0) += 1;
+            }
+        }
+    }
+    d.unused = (0..obj.vertices.len() as u32).filter(|n| !used[*n as usize]).collect();
+    // unused duplicates are left behind by a merge, they are reported as unused
+    d.coincident.retain_mut(|g| {
+        g.retain(|n| used[*n as usize]);
+        g.len() > 1
+    });
+    for (edge, count) in edge_count {
+        match count {
+            1 => d.free_edges.push(edge),
+            2 => {}
+            _ => d.nonmanifold_edges.push(edge),
+        }
+    }
+    d.free_edges.sort();
+    d.nonmanifold_edges.sort();
+
+    // regions numbered in element order
+    let mut ids: HashMap<usize, usize> = HashMap::new();
+    for e in &obj.elements {
+        let root = find(&mut parent, e.nodes[0] as usize);
+        let next = ids.len();
+        d.regions.push(*ids.entry(root).or_insert(next));
+    }
+    d.region_count = ids.len();
+
+    let volumes = quality::field(obj, Metric::Volume).values;
+    d.inverted = (0..volumes.len()).filter(|i| volumes[*i] < 0.0).collect();
+    d
+}
+
+impl Diagnostics {
+    pub fn report(&self) -> String {
+        format!(
+            "coincident nodes {} in {} groups\nunused nodes {}\nfree edges {}\nnon-manifold edges {}\nregions {}\ninverted elements {}\n",
+            self.coincident.iter().map(|g| g.len()).sum::<usize>(),
+            self.coincident.len(),
+            self.unused.len(),
+            self.free_edges.len(),
+            self.nonmanifold_edges.len(),
+            self.region_count,
+            self.inverted.len(),
+        )
+    }
+
+    pub fn region_colors(&self) -> Vec<[f32; 4]> {
+        self.regions
+            .iter()
+            .map(|r| {
+                let c = fields::category_color(*r);
+                [c[0], c[1], c[2], 1.0]
+            })
+            .collect()
+    }
+
+    // crosses on the bad nodes, the bad edges and the edges of inverted elements
+    pub fn lines(&self, obj: &TdObject) -> Vec<Vertex> {
+        let (_, radius) = obj.center_radius();
+        let size = MARK_SIZE * radius;
+        let mut out = Vec::new();
+        let mut line = |a: u32, b: u32, c: [f32; 3]| {
+            out.extend([a, b].map(|n| Vertex { position: obj.display_position(n), color: [c[0], c[1], c[2], 1.0] }));
+        };
+        for group in &self.coincident {
+            for w in group.windows(2) {
+                line(w[0], w[1], COINCIDENT_COLOR);
+            }
+        }
+        for edge in &self.free_edges {
+            line(edge[0], edge[1], FREE_EDGE_COLOR);
+        }
+        for edge in &self.nonmanifold_edges {
+            line(edge[0], edge[1], NONMANIFOLD_COLOR);
+        }
+        for id in &self.inverted {
+            let e = &obj.elements[*id];
+            for [a, b] in e.kind.edges() {
+                line(e.nodes[*a], e.nodes[*b], INVERTED_COLOR);
+            }
+        }
+        let marked = self.coincident.iter().flatten().map(|n| (*n, COINCIDENT_COLOR));
+        for (n, c) in marked.chain(self.unused.iter().map(|n| (*n, UNUSED_COLOR))) {
+            let p = obj.display_position(n);
+            for k in 0..3 {
+                let mut a = p;
+                let mut b = p;
+                a[k] -= size;
+                b[k] += size;
+                out.extend([a, b].map(|position| Vertex { position, color: [c[0], c[1], c[2], 1.0] }));
+            }
+        }
+        out
+    }
+}
+
+// the element and model references of every coincident node moved to the
+// first of its group; the others are left unused so node results keep their
+// numbering. a group with two nodes of one element would collapse it, those
+// are skipped. returns how many nodes were merged away and groups skipped
+pub fn merge(obj: &mut TdObject, groups: &[Vec<u32>]) -> (usize, usize) {
+    let mut group_of: HashMap<u32, usize> = HashMap::new();
+    for (g, group) in groups.iter().enumerate() {
+        for n in group {
+            group_of.insert(*n, g);
+        }
+    }
+    let mut collapsing = vec![false; groups.len()];
+    for e in &obj.elements {
+        let mut seen = Vec::new();
+        for g in e.nodes.iter().filter_map(|n| group_of.get(n)) {
+            if seen.contains(g) {
+                collapsing[*g] = true;
+            }
+            seen.push(*g);
+        }
+    }
+    let mut target: HashMap<u32, u32> = HashMap::new();
+    for (group, _) in groups.iter().zip(&collapsing).filter(|(_, c)| !**c) {
+        for n in &group[1..] {
+            target.insert(*n, group[0]);
+        }
+    }
+    let map = |n: &mut u32| {
+        if let Some(t) = target.get(n) {
+            *n = *t;
+        }
+    };
+    for e in &mut obj.elements {
+        e.nodes.iter_mut().for_each(map);
+    }
+    // the first of a group is its smallest node, so the strip stays in u16
+    for i in &mut obj.indices {
+        if let Some(t) = target.get(&(*i as u32)) {
+            *i = *t as u16;
+        }
+    }
+    let model = &mut obj.model;
+    model.supports.iter_mut().for_each(|s| map(&mut s.node));
+    model.loads.iter_mut().for_each(|l| map(&mut l.node));
+    for set in model.node_sets.values_mut() {
+        set.iter_mut().for_each(map);
+        set.sort_unstable();
+        set.dedup();
+    }
+    let (surface, owner) = elements::surface_indices(&obj.elements);
+    obj.surface = surface;
+    obj.surface_owner = owner;
+    obj.edges = elements::edge_indices(&obj.elements);
+    (target.len(), collapsing.iter().filter(|c| **c).count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // three triangles on one edge, an unused node, an inverted tet and a
+    // triangle with a node on top of one of the tet's
+    fn mesh() -> TdObject {
+        let doc = json!({
+            "vers": [
+                [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0], [0.0, 0.0, 1.0],
+                [9.0, 9.0, 9.0],
+                [0.0, 0.0, 5.0], [1.0, 0.0, 5.0], [0.0, 1.0, 5.0], [0.0, 0.0, 6.0],
+                [0.0, 0.0, 5.0], [-1.0, 0.0, 5.0], [0.0, -1.0, 5.0],
+            ],
+            "inds": [10, 11],
+            "elems": [
+                { "kind": "tri3", "nodes": [0, 1, 2] },
+                { "kind": "tri3", "nodes": [1, 3, 2] },
+                { "kind": "tri3", "nodes": [1, 2, 4] },
+                { "kind": "tet4", "nodes": [6, 8, 7, 9] },
+                { "kind": "tri3", "nodes": [10, 11, 12] },
+            ],
+        });
+        TdObject::from_json(&doc.to_string())
+    }
+
+    #[test]
+    fn check_finds_every_problem() {
+        let d = check(&mesh(), &TopologySettings::default());
+        assert_eq!(d.coincident, vec![vec![6, 10]]);
+        assert_eq!(d.unused, vec![5]);
+        assert_eq!(d.nonmanifold_edges, vec![[1, 2]]);
+        // two edges left on each of the three fanned triangles, three on the lone one
+        assert_eq!(d.free_edges.len(), 9);
+        assert!(d.free_edges.contains(&[0, 1]) && d.free_edges.contains(&[10, 12]));
+        assert_eq!(d.region_count, 3);
+        assert_eq!(d.regions, vec![0, 0, 0, 1, 2]);
+        assert_eq!(d.inverted, vec![3]);
+    }
+
+    #[test]
+    fn merge_joins_coincident_nodes() {
+        let mut obj = mesh();
+        let groups = check(&obj, &TopologySettings::default()).coincident;
+        assert_eq!(merge(&mut obj, &groups), (1, 0));
+        assert_eq!(obj.elements[4].nodes, vec![6, 11, 12]);
+        assert_eq!(obj.indices, vec![6, 11]);
+        let d = check(&obj, &TopologySettings::default());
+        // the merged away node is left unused, the triangle now hangs off the tet
+        assert!(d.coincident.is_empty());
+        assert_eq!(d.unused, vec![5, 10]);
+        assert_eq!(d.region_count, 2);
+    }
+
+    #[test]
+    fn merge_skips_groups_that_collapse_an_element() {
+        let mut obj = mesh();
+        assert_eq!(merge(&mut obj, &[vec![0, 1], vec![6, 10]]), (1, 1));
+        assert_eq!(obj.elements[0].nodes, vec![0, 1, 2]);
+        assert_eq!(obj.elements[4].nodes, vec![6, 11, 12]);
+    }
+}